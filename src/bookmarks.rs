@@ -0,0 +1,60 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// Gets the path of the bookmarks file inside the config directory.
+fn bookmarks_path() -> Result<PathBuf, Error> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "No config directory found"))?;
+
+    Ok(config.join("mini").join("bookmarks"))
+}
+
+/// Loads all bookmarked paths. A missing bookmarks file is treated as having no bookmarks.
+pub fn load() -> Result<Vec<PathBuf>, Error> {
+    match fs::read_to_string(bookmarks_path()?) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Bookmarks a path. Returns false if the path was already bookmarked.
+pub fn add(path: &Path) -> Result<bool, Error> {
+    let path = path.canonicalize()?;
+    if load()?.contains(&path) {
+        return Ok(false);
+    }
+
+    let file_path = bookmarks_path()?;
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file_path)?;
+    writeln!(file, "{}", path.display())?;
+
+    Ok(true)
+}
+
+/// Removes a bookmarked path.
+pub fn remove(path: &Path) -> Result<(), Error> {
+    let mut contents = String::new();
+    for bookmark in load()?.iter().filter(|bookmark| *bookmark != path) {
+        writeln!(&mut contents, "{}", bookmark.display()).unwrap();
+    }
+
+    fs::write(bookmarks_path()?, contents)
+}
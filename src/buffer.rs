@@ -3,10 +3,13 @@ pub mod delete;
 pub mod edit;
 pub mod yank;
 
+#[cfg(test)]
+use crate::document::Document;
 use crate::{
     display::Display,
     message::{Message, MessageKind},
 };
+use std::path::PathBuf;
 use termion::event::Key;
 
 /// The result of a command entered by the user.
@@ -17,10 +20,27 @@ pub enum BufferResult {
     Error(String),
     ListBuffers,
     NewBuffer(BufferKind),
+    Open(Box<dyn Buffer>),
     Init(Box<dyn Buffer>),
     Log,
     Quit,
     ForceQuit,
+    WriteAll,
+    QuitAll,
+    ForceQuitAll,
+    CloseOthers,
+    ChangeDir(PathBuf),
+}
+
+impl BufferResult {
+    /// Appends a warning to the result of a successful command.
+    pub fn with_warning(self, warning: Option<String>) -> Self {
+        match (self, warning) {
+            (Self::Ok, Some(warning)) => Self::Info(warning),
+            (Self::Info(info), Some(warning)) => Self::Info(format!("{info}\n{warning}")),
+            (res, _) => res,
+        }
+    }
 }
 
 /// Enum of all available `Buffer` kinds.
@@ -78,4 +98,14 @@ pub trait Buffer {
 
     /// Asks if the buffer is ready to quit/has pending changes.
     fn can_quit(&self) -> Result<(), String>;
+
+    /// Writes pending changes of the buffer. Returns if anything was written.
+    fn write(&mut self) -> Result<bool, String>;
+
+    /// Cleans up before the buffer is closed, discarding unsaved changes.
+    fn close(&mut self);
+
+    /// Returns the main content of the buffer, for tests to read back the result of keys.
+    #[cfg(test)]
+    fn doc(&self) -> &Document;
 }
@@ -1,14 +1,24 @@
 mod apply_command;
 
 use crate::{
+    buffer::BufferResult,
+    clipboard::SharedClipboard,
+    command_history::SharedHistory,
     cursor::{self, Cursor},
+    display::Display,
     document::Document,
     message::{Message, MessageKind},
+    options::Options,
     selection::{Selection, SelectionKind},
-    viewport::Viewport,
+    viewport::{self, Viewport},
 };
-use arboard::Clipboard;
-use std::io::Error;
+use regex::Regex;
+use std::time::{Duration, Instant};
+
+/// The maximum number of rows the command line expands to when its text wraps.
+const CMD_MAX_ROWS: usize = 5;
+/// The time the info line stays inverted after the visual bell was rung.
+const BELL_DURATION: Duration = Duration::from_millis(150);
 
 /// A struct defining the base functionality of a buffer. Specialized buffers can keep
 /// it as a field to "inherit" this base. Buffers with completely separate functionality
@@ -38,25 +48,38 @@ pub struct BaseBuffer {
     /// Text selections in the document.
     pub selections: Vec<Selection>,
     active_selection: bool,
+    /// The anchors, heads and kinds of the selections before they were last cleared.
+    last_selections: Vec<(Cursor, Cursor, SelectionKind)>,
 
-    /// An instance of the system clipboard to yank to.
-    pub clipboard: Clipboard,
+    /// The clipboard to yank to and paste from, shared by all buffers.
+    pub clipboard: SharedClipboard,
 
     /// The vector of matches of a search.
     matches: Vec<(Cursor, Cursor)>,
     /// The index of the current match for navigation.
     matches_idx: Option<usize>,
+    /// The regex of the persistently highlighted matches.
+    highlight: Option<Regex>,
+    /// The highlighted matches.
+    pub highlights: Vec<(Cursor, Cursor)>,
 
-    /// The history of entered commands.
-    pub cmd_history: Vec<String>,
+    /// The history of entered commands, shared by all buffers.
+    pub cmd_history: SharedHistory,
     /// The current index in the command history.
     pub cmd_history_idx: usize,
+    /// The last command entered in this buffer during this session, repeated by `@:`.
+    pub last_command: Option<String>,
 
     /// The active message.
     pub message: Option<Message>,
 
+    /// The options of the buffer.
+    pub options: Options,
+
     /// Flag if the buffer needs re-rendering.
     pub rerender: bool,
+    /// The time the visual bell was rung, the info line is inverted until it expires.
+    bell: Option<Instant>,
 }
 
 impl BaseBuffer {
@@ -66,13 +89,15 @@ impl BaseBuffer {
         x_off: usize,
         y_off: usize,
         contents: Option<String>,
-    ) -> Result<Self, Error> {
+        clipboard: SharedClipboard,
+        cmd_history: SharedHistory,
+    ) -> Self {
         // Set the command view number width manually.
         // FIXME: this limits the bar to always be exactly one in height.
         let cmd_view = Viewport::new(w, 1, x_off, y_off, None);
 
         let count = contents.as_ref().map_or(1, |buff| buff.len().max(1));
-        Ok(Self {
+        Self {
             w,
             h,
             x_off,
@@ -87,14 +112,20 @@ impl BaseBuffer {
             cmd_view,
             selections: Vec::new(),
             active_selection: false,
-            clipboard: Clipboard::new().map_err(Error::other)?,
+            last_selections: Vec::new(),
+            clipboard,
             matches: Vec::new(),
             matches_idx: None,
-            cmd_history: Vec::new(),
+            highlight: None,
+            highlights: Vec::new(),
+            cmd_history,
             cmd_history_idx: 0,
+            last_command: None,
             message: None,
+            options: Options::new(),
             rerender: true,
-        })
+            bell: None,
+        }
     }
 
     /// Resizes the viewports of the buffer.
@@ -121,46 +152,91 @@ impl BaseBuffer {
         self.cmd_view.resize(w, 1, x_off, y_off, None);
     }
 
-    /// Jumps to the next search match if any.
-    pub fn next_match(&mut self) {
-        if self.matches.is_empty() {
-            return;
+    /// Renders the command line. Text that does not fit in one row wraps and expands the command line over the
+    /// document, scrolling once it reaches its maximum height.
+    pub fn render_command(&mut self, display: &mut Display) {
+        let text = self.cmd.line(0).unwrap().to_string();
+        let text = text.trim_end_matches(['\n', '\r']);
+
+        let rows = viewport::wrapped_rows(text, self.w)
+            .min(CMD_MAX_ROWS)
+            .min(self.h);
+        if rows != self.cmd_view.h {
+            self.cmd_view
+                .resize(self.w, rows, self.x_off, self.y_off, None);
         }
 
-        let idx = self.matches_idx.as_mut().unwrap();
-        *idx = (*idx + 1) % self.matches.len();
+        // The expanded command line overwrites lines of the document.
+        if rows > 1 {
+            self.doc_view.invalidate();
+        }
 
-        self.selections = vec![Selection::new(
-            self.matches[*idx].0,
-            self.matches[*idx].1,
-            SelectionKind::Normal,
-            None,
-            None,
-        )];
-        cursor::move_to(&mut self.doc, self.matches[*idx].0);
+        self.cmd_view.recalculate_wrapped_viewport(&self.cmd);
+        self.cmd_view.render_wrapped_bar(text, display);
     }
 
-    // Jumps to the previous search match if any.
-    pub fn prev_match(&mut self) {
-        if self.matches.is_empty() {
-            return;
+    /// Jumps to the next search match if any. Informs if the search wrapped around the end of the document.
+    pub fn next_match(&mut self) -> BufferResult {
+        let Some(idx) = self.matches_idx else {
+            return BufferResult::Ok;
+        };
+
+        let next = (idx + 1) % self.matches.len();
+        self.select_match(next);
+
+        if next <= idx {
+            return BufferResult::Info("Search hit BOTTOM, continuing at TOP".to_string());
         }
+        BufferResult::Ok
+    }
 
-        let idx = self.matches_idx.as_mut().unwrap();
-        if *idx != 0 {
-            *idx -= 1;
-        } else {
-            *idx = self.matches.len() - 1;
+    /// Jumps to the previous search match if any. Informs if the search wrapped around the start of the document.
+    pub fn prev_match(&mut self) -> BufferResult {
+        let Some(idx) = self.matches_idx else {
+            return BufferResult::Ok;
+        };
+
+        let prev = idx.checked_sub(1).unwrap_or(self.matches.len() - 1);
+        self.select_match(prev);
+
+        if prev >= idx {
+            return BufferResult::Info("Search hit TOP, continuing at BOTTOM".to_string());
+        }
+        BufferResult::Ok
+    }
+
+    /// Selects the match under or after the cursor and jumps there, continuing at the top of the document. Returns if
+    /// there was a match to select.
+    pub fn select_next_match_at_cursor(&mut self) -> bool {
+        self.update_highlights(false);
+        if self.matches.is_empty() {
+            return false;
         }
 
+        let cur = self.doc.cur;
+        let idx = self
+            .matches
+            .iter()
+            .position(|(_, end)| cur < *end)
+            .unwrap_or(0);
+        self.select_match(idx);
+
+        true
+    }
+
+    /// Selects the match at the index and jumps there.
+    fn select_match(&mut self, idx: usize) {
+        let (start, end) = self.matches[idx];
+
+        self.matches_idx = Some(idx);
         self.selections = vec![Selection::new(
-            self.matches[*idx].0,
-            self.matches[*idx].1,
+            start,
+            end,
             SelectionKind::Normal,
             None,
             None,
         )];
-        cursor::move_to(&mut self.doc, self.matches[*idx].0);
+        cursor::move_to(&mut self.doc, start);
     }
 
     /// Clears the existing matches of the buffer.
@@ -169,6 +245,80 @@ impl BaseBuffer {
         self.matches_idx = None;
     }
 
+    /// Gets the ranges to search in, which are all selections or the entire document if nothing is selected.
+    pub fn search_ranges(&mut self) -> Vec<(Cursor, Cursor)> {
+        if self.selections.is_empty() {
+            let y = self.doc.len().saturating_sub(1);
+            let x = self.doc.line_count(y).unwrap_or(0);
+            return vec![(Cursor::new(0, 0), Cursor::new(x, y))];
+        }
+
+        self.selections.sort_unstable();
+        self.selections.iter().map(Selection::range).collect()
+    }
+
+    /// Finds all matches of a regex within the given ranges.
+    pub fn find_matches(
+        &self,
+        regex: &Regex,
+        ranges: &[(Cursor, Cursor)],
+    ) -> Vec<(Cursor, Cursor)> {
+        let mut matches = Vec::new();
+        for (start, end) in ranges {
+            let Some(hay) = self.doc.get_range(*start, *end) else {
+                continue;
+            };
+            let hay = hay.to_string();
+            matches.extend(regex.find_iter(&hay).map(|mat| {
+                let start_pos = cursor::pos_after_text(start, &hay[..mat.start()]);
+                let end_pos = cursor::pos_after_text(start, &hay[..mat.end()]);
+                (start_pos, end_pos)
+            }));
+        }
+
+        matches
+    }
+
+    /// Persistently highlights all matches of a regex in the document. Passing `None` clears the highlight.
+    pub fn set_highlight(&mut self, regex: Option<Regex>) {
+        self.highlight = regex;
+        self.highlights.clear();
+        self.clear_matches();
+        self.update_highlights(true);
+    }
+
+    /// Updates the highlighted matches if the document changed since the last render or `force` is set. The
+    /// highlighted matches are navigable if no other matches exist.
+    pub fn update_highlights(&mut self, force: bool) {
+        let Some(regex) = &self.highlight else {
+            return;
+        };
+
+        if force || self.doc.has_dirty() {
+            let y = self.doc.len().saturating_sub(1);
+            let x = self.doc.line_count(y).unwrap_or(0);
+            let highlights = self.find_matches(regex, &[(Cursor::new(0, 0), Cursor::new(x, y))]);
+
+            // Navigated highlights move with the changes of the document.
+            if self.matches == self.highlights {
+                self.clear_matches();
+            }
+            self.highlights = highlights;
+        }
+
+        if self.matches.is_empty() && !self.highlights.is_empty() {
+            self.matches.clone_from(&self.highlights);
+
+            // Point at the match before the cursor so the next match is the first after the cursor.
+            let cur = self.doc.cur;
+            let idx = self.matches.iter().position(|(start, _)| cur.le(start));
+            self.matches_idx = Some(match idx {
+                Some(0) | None => self.matches.len() - 1,
+                Some(idx) => idx - 1,
+            });
+        }
+    }
+
     /// Adds a new or reactivates an existing selection.
     pub fn add_selection(&mut self, kind: SelectionKind) {
         let cur = self.doc.cur;
@@ -223,6 +373,61 @@ impl BaseBuffer {
         self.active_selection = true;
     }
 
+    /// Gets the amount of lines half-page scrolling moves by, the `scroll` option or half the viewport height.
+    pub const fn half_page(&self) -> usize {
+        if self.options.scroll == 0 {
+            self.doc_view.h / 2
+        } else {
+            self.options.scroll
+        }
+    }
+
+    /// Moves the cursor one line down or up. At the last or first line the `edgemotion` option either rings the visual
+    /// bell or wraps around to the other end of the document.
+    pub fn vertical_motion(&mut self, down: bool, n: usize) {
+        let y = self.doc.cur.y;
+        if down {
+            cursor::down(&mut self.doc, n);
+        } else {
+            cursor::up(&mut self.doc, n);
+        }
+
+        if self.doc.cur.y == y {
+            let len = self.doc.len();
+            match self.options.edgemotion.as_str() {
+                "bell" => self.bell = Some(Instant::now()),
+                "wrap" if down => cursor::up(&mut self.doc, y),
+                "wrap" => cursor::down(&mut self.doc, len),
+                _ => {}
+            }
+        }
+
+        self.update_selection();
+    }
+
+    /// Checks if the visual bell is ringing.
+    pub const fn bell(&self) -> bool {
+        self.bell.is_some()
+    }
+
+    /// Stops the visual bell once it rang long enough.
+    pub fn bell_tick(&mut self) {
+        if self
+            .bell
+            .is_some_and(|bell| bell.elapsed() >= BELL_DURATION)
+        {
+            self.bell = None;
+            self.rerender = true;
+        }
+    }
+
+    /// Returns if a pending multi-key input has to be aborted, since no key followed the last one pressed at
+    /// `last_input` within 'timeoutlen'. A 'timeoutlen' of zero waits indefinitely.
+    pub fn pending_timed_out(&self, last_input: Instant) -> bool {
+        let timeoutlen = self.options.timeoutlen;
+        timeoutlen != 0 && last_input.elapsed() >= Duration::from_millis(timeoutlen)
+    }
+
     /// Updates the last selection to the new position.
     pub fn update_selection(&mut self) {
         if !self.active_selection {
@@ -234,25 +439,66 @@ impl BaseBuffer {
         }
     }
 
-    /// Clears all selections.
+    /// Clears all selections. They are remembered to be restored by `reselect`.
     pub fn clear_selections(&mut self) {
+        if !self.selections.is_empty() {
+            self.last_selections = self
+                .selections
+                .iter()
+                .map(|sel| (sel.anchor, sel.head, sel.kind))
+                .collect();
+        }
+
         self.selections.clear();
         self.active_selection = false;
     }
 
+    /// Restores the selections from before they were last cleared and moves the cursor to the head of the last one,
+    /// continuing it. Positions are clamped to the document in case it changed since.
+    pub fn reselect(&mut self) {
+        if self.last_selections.is_empty() {
+            return;
+        }
+
+        self.selections.clear();
+        for (anchor, head, kind) in self.last_selections.clone() {
+            // Moving the cursor clamps the positions to the document.
+            cursor::move_to(&mut self.doc, anchor);
+            let anchor = self.doc.cur;
+            cursor::move_to(&mut self.doc, head);
+            let head = self.doc.cur;
+
+            self.selections.push(Selection::new(
+                anchor,
+                head,
+                kind,
+                self.doc.line_count(anchor.y),
+                self.doc.line_count(head.y),
+            ));
+        }
+        self.active_selection = true;
+    }
+
+    /// Adds an entered command to the end of the command history, removing earlier occurrences of it. The history is
+    /// persisted across sessions unless the `history` option is zero.
+    pub fn push_command_history(&mut self, cmd: &str) {
+        self.last_command = Some(cmd.to_string());
+        self.cmd_history
+            .borrow_mut()
+            .push(cmd, self.options.history);
+    }
+
     /// Loads the next command history item.
     pub fn next_command_history(&mut self) {
-        if self.cmd_history_idx == self.cmd_history.len() {
+        let history = self.cmd_history.borrow();
+        if self.cmd_history_idx == history.len() {
             return;
         }
 
+        // Moving past the newest command clears the command line.
         self.cmd_history_idx += 1;
-        if self.cmd_history_idx == self.cmd_history.len() {
-            self.cmd.from("");
-        } else {
-            self.cmd
-                .from(self.cmd_history[self.cmd_history_idx].as_str());
-        }
+        self.cmd
+            .from(history.get(self.cmd_history_idx).unwrap_or_default());
 
         cursor::jump_to_end_of_line(&mut self.cmd);
     }
@@ -264,8 +510,9 @@ impl BaseBuffer {
         }
 
         self.cmd_history_idx -= 1;
-        self.cmd
-            .from(self.cmd_history[self.cmd_history_idx].as_str());
+        if let Some(cmd) = self.cmd_history.borrow().get(self.cmd_history_idx) {
+            self.cmd.from(cmd);
+        }
 
         cursor::jump_to_end_of_line(&mut self.cmd);
     }
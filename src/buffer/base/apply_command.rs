@@ -1,64 +1,26 @@
 use crate::{
-    INFO_MSG,
+    INFO_MSG, bookmarks,
     buffer::{BufferKind, BufferResult, base::BaseBuffer},
-    cursor::{self, Cursor},
+    buffer_impls::{files_buffer::FilesBuffer, text_buffer::TextBuffer},
+    clipboard, cursor,
+    message::MessageKind,
     selection::{Selection, SelectionKind},
-    util::line_column,
+    util::{PathArg, line_column, parse_args, path_arg, unknown_vars_warning},
 };
 use regex::Regex;
+use std::path::PathBuf;
 
 impl BaseBuffer {
     fn search(&mut self, args: &str) -> BufferResult {
-        if args.len() == 2 || !args.starts_with('/') || !args.ends_with('/') {
-            return BufferResult::Error(
-                "Expected a valid regular expression like '/<regex>/'".to_string(),
-            );
-        }
-
-        let regex = match Regex::new(&args[1..args.len() - 1]) {
+        let regex = match parse_regex(args) {
             Ok(regex) => regex,
-            Err(err) => {
-                return BufferResult::Error(format!(
-                    "'{args}' is not a valid regular expression:\n{err}"
-                ));
-            }
+            Err(err) => return BufferResult::Error(err),
         };
 
         // Use selections or search entire buffer.
-        self.selections.sort_unstable();
-        let selections = if self.selections.is_empty() {
-            let start = Cursor::new(0, 0);
-            let end = {
-                let y = self.doc.len().saturating_sub(1);
-                let x = self.doc.line_count(y).unwrap_or(0);
-                Cursor::new(x, y)
-            };
-
-            &vec![Selection::new(
-                start,
-                end,
-                SelectionKind::Normal,
-                None,
-                None,
-            )]
-        } else {
-            &self.selections
-        };
-
-        self.matches.clear();
+        let ranges = self.search_ranges();
+        self.matches = self.find_matches(&regex, &ranges);
         self.matches_idx = None;
-        for selection in selections {
-            let (start, end) = selection.range();
-            let hay = self.doc.get_range(start, end).unwrap().to_string();
-            self.matches = regex
-                .find_iter(&hay)
-                .map(|mat| {
-                    let start_pos = cursor::pos_after_text(&start, &hay[..mat.start()]);
-                    let end_pos = cursor::pos_after_text(&start, &hay[..mat.end()]);
-                    (start_pos, end_pos)
-                })
-                .collect();
-        }
 
         if self.matches.is_empty() {
             return BufferResult::Info("No matches found".to_string());
@@ -90,6 +52,112 @@ impl BaseBuffer {
         BufferResult::Ok
     }
 
+    fn highlight(&mut self, args: &str) -> BufferResult {
+        let regex = match parse_regex(args) {
+            Ok(regex) => regex,
+            Err(err) => return BufferResult::Error(err),
+        };
+
+        self.set_highlight(Some(regex));
+        if self.highlights.is_empty() {
+            return BufferResult::Info("No matches found".to_string());
+        }
+
+        BufferResult::Ok
+    }
+
+    fn set(&mut self, args: &str) -> BufferResult {
+        if args.is_empty() {
+            return BufferResult::Info(self.options.list());
+        }
+
+        // Values containing whitespace are quoted or escaped like paths.
+        let mut unknown = Vec::new();
+        let args = match parse_args(args, &mut unknown) {
+            Ok(args) => args,
+            Err(err) => return BufferResult::Error(err),
+        };
+
+        // Nothing changes unless all options are valid.
+        let mut options = self.options.clone();
+        for arg in &args {
+            if let Err(err) = options.set(arg) {
+                return BufferResult::Error(err);
+            }
+        }
+
+        // The clipboard is shared, so switching it affects all buffers.
+        if options.clipboard != self.options.clipboard {
+            match clipboard::by_name(&options.clipboard) {
+                Ok(provider) => *self.clipboard.borrow_mut() = provider,
+                Err(err) => return BufferResult::Error(err),
+            }
+        }
+        self.options = options;
+
+        self.doc.set_word_chars(&self.options.iskeyword);
+        self.doc_view.wrap = self.options.wrap;
+
+        // Options might change how the buffer is rendered.
+        self.doc_view.invalidate();
+        self.rerender = true;
+        BufferResult::Ok.with_warning(unknown_vars_warning(&unknown))
+    }
+
+    /// Bookmarks the path given as argument, or the path a buffer bookmarks by default if none is given.
+    pub fn bookmark_command(args: &str, default: Option<PathBuf>) -> BufferResult {
+        let PathArg { path, warning } = match path_arg(args) {
+            Ok(arg) => arg,
+            Err(res) => return res,
+        };
+
+        let path = if path.is_empty() {
+            match default {
+                Some(path) => path,
+                None => return BufferResult::Error("Buffer has no file to bookmark".to_string()),
+            }
+        } else {
+            PathBuf::from(path)
+        };
+
+        match bookmarks::add(&path) {
+            Ok(true) => BufferResult::Info(format!("Bookmarked '{}'", path.display())),
+            Ok(false) => BufferResult::Info(format!("'{}' is already bookmarked", path.display())),
+            Err(err) => BufferResult::Error(err.to_string()),
+        }
+        .with_warning(warning)
+    }
+
+    fn open_bookmarks(&self) -> BufferResult {
+        match FilesBuffer::bookmarks(
+            self.w,
+            self.h,
+            self.x_off,
+            self.y_off,
+            self.clipboard.clone(),
+            self.cmd_history.clone(),
+        ) {
+            Ok(buffer) => BufferResult::Open(Box::new(buffer)),
+            Err(err) => BufferResult::Error(err.to_string()),
+        }
+    }
+
+    fn open_help(&self) -> BufferResult {
+        // The help is prose, so it wraps at the window width instead of scrolling horizontally.
+        match TextBuffer::from_wrapped_contents(
+            self.w,
+            self.h,
+            self.x_off,
+            self.y_off,
+            &help(),
+            self.clipboard.clone(),
+            self.cmd_history.clone(),
+        ) {
+            Ok(buffer) => BufferResult::Open(Box::new(buffer)),
+            Err(err) => BufferResult::Error(err.to_string()),
+        }
+    }
+
     fn goto(&mut self, args: &str) -> BufferResult {
         let (x, y) = line_column(args);
 
@@ -105,6 +173,50 @@ impl BaseBuffer {
         BufferResult::Ok
     }
 
+    fn goto_column(&mut self, args: &str) -> BufferResult {
+        match args.parse::<usize>() {
+            Ok(col) => {
+                // Minus 1 since text coordinates are 0 indexed.
+                cursor::jump_to_column(&mut self.doc, col.saturating_sub(1));
+                BufferResult::Ok
+            }
+            Err(err) => BufferResult::Error(err.to_string()),
+        }
+    }
+
+    fn change_dir(args: &str) -> BufferResult {
+        let PathArg { path, warning } = match path_arg(args) {
+            Ok(arg) => arg,
+            Err(res) => return res,
+        };
+
+        // Like in a shell, no path changes to the home directory.
+        let path = if path.is_empty() {
+            match std::env::var("HOME") {
+                Ok(home) => home,
+                Err(_) => return BufferResult::Error("No home directory found".to_string()),
+            }
+        } else {
+            path
+        };
+
+        let dir = match std::fs::canonicalize(&path).and_then(|dir| {
+            std::env::set_current_dir(&dir)?;
+            Ok(dir)
+        }) {
+            Ok(dir) => dir,
+            Err(err) => {
+                let mut err = format!("Failed to change directory to '{path}':\n{err}");
+                if let Some(warning) = warning {
+                    err = format!("{err}\n{warning}");
+                }
+                return BufferResult::Error(err);
+            }
+        };
+
+        BufferResult::ChangeDir(dir)
+    }
+
     /// Applies the command entered during command mode.
     pub fn apply_command(&mut self, input: String) -> Result<BufferResult, String> {
         if input.is_empty() {
@@ -119,12 +231,24 @@ impl BaseBuffer {
         match cmd {
             "q" => Ok(BufferResult::Quit),
             "qq" => Ok(BufferResult::ForceQuit),
-            "?" => Ok(BufferResult::Info(format!(
-                "Mini - A terminal text-editor (v{})\n\n{INFO_MSG}",
-                option_env!("CARGO_PKG_VERSION").or(Some("?.?.?")).unwrap()
-            ))),
+            "wa" => Ok(BufferResult::WriteAll),
+            "qa" => Ok(BufferResult::QuitAll),
+            "qqa" => Ok(BufferResult::ForceQuitAll),
+            "only" => Ok(BufferResult::CloseOthers),
+            "?" => {
+                self.set_message(MessageKind::Help, help());
+                Ok(BufferResult::Ok)
+            }
+            "??" => Ok(self.open_help()),
             "j" => Ok(self.goto(args)),
+            "|" => Ok(self.goto_column(args)),
             "s" => Ok(self.search(args)),
+            "match" => Ok(self.highlight(args)),
+            "noh" => {
+                self.set_highlight(None);
+                Ok(BufferResult::Ok)
+            }
+            "set" => Ok(self.set(args)),
             "cb" => match args.parse::<usize>() {
                 Ok(idx) => Ok(BufferResult::Change(idx)),
                 Err(err) => Ok(BufferResult::Error(err.to_string())),
@@ -138,8 +262,28 @@ impl BaseBuffer {
                     BufferKind::list()
                 ))),
             },
+            "obm" => Ok(self.open_bookmarks()),
             "log" => Ok(BufferResult::Log),
+            "cd" => Ok(Self::change_dir(args)),
             _ => Err(input),
         }
     }
 }
+
+/// Returns the help message.
+fn help() -> String {
+    format!(
+        "Mini - A terminal text-editor (v{})\n\n{INFO_MSG}",
+        option_env!("CARGO_PKG_VERSION").or(Some("?.?.?")).unwrap()
+    )
+}
+
+/// Parses a regex of the form `/<regex>/`.
+fn parse_regex(args: &str) -> Result<Regex, String> {
+    if args.len() <= 2 || !args.starts_with('/') || !args.ends_with('/') {
+        return Err("Expected a valid regular expression like '/<regex>/'".to_string());
+    }
+
+    Regex::new(&args[1..args.len() - 1])
+        .map_err(|err| format!("'{args}' is not a valid regular expression:\n{err}"))
+}
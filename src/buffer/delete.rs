@@ -1,5 +1,5 @@
 use crate::{
-    cursor,
+    cursor::{self, Cursor, FindChar},
     document::Document,
     history::{History, Replace},
     selection::{Selection, SelectionKind},
@@ -30,8 +30,11 @@ macro_rules! delete {
         $self.base.clear_matches();
         $self.base.clear_selections();
     }};
-    ($self:ident, $func:ident, REPEAT) => {{
-        $crate::buffer::delete::$func(&mut $self.base.doc, Some(&mut $self.history), 1);
+    ($self:ident, $func:ident, REPEAT) => {
+        $crate::delete!($self, $func, REPEAT, 1)
+    };
+    ($self:ident, $func:ident, REPEAT, $n:expr) => {{
+        $crate::buffer::delete::$func(&mut $self.base.doc, Some(&mut $self.history), $n);
         // Deletions might cause matches and selections to become invalid.
         $self.base.clear_matches();
         $self.base.clear_selections();
@@ -55,8 +58,11 @@ macro_rules! change {
         $crate::buffer::delete::$func(&mut $self.base.doc, Some(&mut $self.history));
         $self.change_mode(Mode::Insert);
     }};
-    ($self:ident, $func:ident, REPEAT) => {{
-        $crate::buffer::delete::$func(&mut $self.base.doc, Some(&mut $self.history), 1);
+    ($self:ident, $func:ident, REPEAT) => {
+        $crate::change!($self, $func, REPEAT, 1)
+    };
+    ($self:ident, $func:ident, REPEAT, $n:expr) => {{
+        $crate::buffer::delete::$func(&mut $self.base.doc, Some(&mut $self.history), $n);
         $self.change_mode(Mode::Insert);
     }};
 }
@@ -70,37 +76,78 @@ pub fn selection(
     let mut changes = Vec::new();
 
     selections.sort_unstable();
+    // The start of the last deleted selection. Parts of earlier selections overlapping it were already deleted.
+    let mut limit: Option<Cursor> = None;
     for selection in selections.iter().rev() {
         let (start, end) = selection.range();
+        let end = limit.map_or(end, |limit| end.min(limit).max(start));
+        limit = Some(start);
 
-        if let Some(data) = doc.get_range(start, end) {
+        if let Some(data) = doc.get_range(start, end).map(|data| data.to_string())
+            && !data.is_empty()
+        {
+            doc.remove_range(start, end);
             changes.push(Replace {
                 pos: start,
-                delete_data: data.to_string(),
+                delete_data: data,
                 insert_data: String::new(),
             });
         }
 
-        doc.remove_range(start, end);
-
         // Place cursor at the beginning of the deleted area.
         cursor::move_to(doc, start);
     }
 
-    if let Some(history) = history.as_mut() {
+    // Don't record empty changes which would make undo seemingly do nothing.
+    if let Some(history) = history.as_mut()
+        && !changes.is_empty()
+    {
         history.add_change(changes);
     }
 }
 
+/// Deletes to the nth occurrence of a character in the line of the cursor. Does nothing if the character is not found.
+/// Returns if it was found.
+pub fn find_char(
+    doc: &mut Document,
+    history: Option<&mut History>,
+    find: FindChar,
+    ch: char,
+    n: usize,
+) -> bool {
+    let start = doc.cur;
+    if !find.apply(doc, ch, n) {
+        return false;
+    }
+
+    let end = if find.inclusive() {
+        Cursor::new(doc.cur.x + 1, doc.cur.y)
+    } else {
+        doc.cur
+    };
+    selection(
+        doc,
+        &mut [Selection::new(
+            start,
+            end,
+            SelectionKind::Normal,
+            None,
+            None,
+        )],
+        history,
+    );
+
+    true
+}
+
 /// Deletes a line.
 pub fn line(doc: &mut Document, history: Option<&mut History>, n: usize) {
     if doc.len() == 1 && doc.line(0).unwrap().len_chars() == 0 {
         return;
     }
 
-    if doc.cur.y + n >= doc.len() {
-        cursor::up(doc, doc.cur.y + n - doc.len());
-    }
+    // Counts past the end of the document delete until its end.
+    let n = n.min(doc.len() - doc.cur.y);
 
     // Begin of selection at the end of one line above the first line or at beginning of current line
     // if in the first line.
@@ -113,10 +160,10 @@ pub fn line(doc: &mut Document, history: Option<&mut History>, n: usize) {
     }
 
     // End selection at the end of the last line or at the beginning of the next line if selection started
-    // in the first line.
+    // in the first line and there is a next line.
     let tmp2 = doc.cur;
     cursor::down(doc, n);
-    if tmp1.y != 0 || tmp2.y + 1 == doc.len() {
+    if tmp1.y != 0 || tmp2.y + n >= doc.len() {
         cursor::jump_to_end_of_line(doc);
     } else {
         cursor::jump_to_beginning_of_line(doc);
@@ -138,7 +185,24 @@ pub fn line(doc: &mut Document, history: Option<&mut History>, n: usize) {
     if tmp1.y != 0 {
         cursor::down(doc, 1);
     }
-    cursor::jump_to_beginning_of_line(doc);
+    // Deleting the last lines must not leave the cursor on the empty line after the final line break.
+    if doc.cur.y > 0 && doc.cur.y + 1 == doc.len() && doc.line_count(doc.cur.y) == Some(0) {
+        cursor::up(doc, 1);
+    }
+    cursor::jump_to_first_non_blank(doc);
+}
+
+/// Deletes characters under the cursor without joining lines. Like in Vim, deleting the last character of a line leaves
+/// the cursor on the new last character instead of the end of the line.
+pub fn character(doc: &mut Document, history: Option<&mut History>, n: usize) {
+    let count = doc.line_count(doc.cur.y).unwrap_or(0);
+    right(doc, history, n);
+
+    let new_count = doc.line_count(doc.cur.y).unwrap_or(0);
+    let end_of_line = doc.cur.x + usize::from(doc.ends_with_newline(doc.cur.y)) == new_count;
+    if new_count < count && end_of_line {
+        cursor::left(doc, 1);
+    }
 }
 
 delete_fn!(left, left, doc = "Deletes left of the cursor.", n);
@@ -206,3 +270,138 @@ delete_fn!(
     jump_to_end_of_file,
     doc = "Deletes until the end of the file."
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that only a single empty line is left with the cursor at its start.
+    fn assert_empty(doc: &Document) {
+        assert_eq!(doc.len(), 1);
+        assert_eq!(
+            doc.line(0).map(|line| line.to_string()).as_deref(),
+            Some("")
+        );
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 0));
+    }
+
+    #[test]
+    fn deleting_all_lines_leaves_one_empty_line() {
+        let mut doc = Document::new(0, 1, Some(String::from("abc\n  def\nghi")));
+        cursor::move_to(&mut doc, Cursor::new(0, 0));
+
+        // The count reaches past the end of the document.
+        line(&mut doc, None, 10);
+        assert_empty(&doc);
+
+        // Deleting the only empty line changes nothing.
+        line(&mut doc, None, 1);
+        assert_empty(&doc);
+    }
+
+    #[test]
+    fn deleting_lines_from_the_end_keeps_the_cursor_in_the_document() {
+        let mut doc = Document::new(0, 0, Some(String::from("abc\ndef\n")));
+        cursor::move_to(&mut doc, Cursor::new(0, 1));
+
+        line(&mut doc, None, 5);
+        assert_eq!(doc.len(), 1);
+        assert_eq!(doc.line(0).unwrap().to_string(), "abc");
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 0));
+
+        line(&mut doc, None, 1);
+        assert_empty(&doc);
+    }
+
+    #[test]
+    fn deleting_a_selection_of_everything_leaves_one_empty_line() {
+        let mut doc = Document::new(0, 0, Some(String::from("abc\ndef\n")));
+        let mut history = History::new();
+        cursor::move_to(&mut doc, Cursor::new(2, 1));
+
+        let end = Cursor::new(0, doc.len() - 1);
+        let mut selections = [Selection::new(
+            Cursor::new(0, 0),
+            end,
+            SelectionKind::Normal,
+            None,
+            None,
+        )];
+        selection(&mut doc, &mut selections, Some(&mut history));
+        assert_empty(&doc);
+    }
+
+    #[test]
+    fn deleting_characters_never_joins_lines() {
+        let mut doc = Document::new(0, 0, Some(String::from("abc\n\nxy")));
+
+        // The last character of a line leaves the cursor on the new last character.
+        cursor::move_to(&mut doc, Cursor::new(2, 0));
+        character(&mut doc, None, 1);
+        assert_eq!(doc.line(0).unwrap().to_string(), "ab\n");
+        assert_eq!((doc.cur.x, doc.cur.y), (1, 0));
+
+        // Counts stop at the end of the line.
+        character(&mut doc, None, 5);
+        assert_eq!(doc.line(0).unwrap().to_string(), "a\n");
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 0));
+
+        // An empty line stays.
+        cursor::move_to(&mut doc, Cursor::new(0, 1));
+        character(&mut doc, None, 1);
+        assert_eq!(doc.len(), 3);
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 1));
+
+        // At the end of the file the last character is deleted once.
+        cursor::move_to(&mut doc, Cursor::new(1, 2));
+        character(&mut doc, None, 1);
+        assert_eq!(doc.line(2).unwrap().to_string(), "x");
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 2));
+        character(&mut doc, None, 1);
+        character(&mut doc, None, 1);
+        assert_eq!(doc.line(2).unwrap().to_string(), "");
+
+        let mut text = Vec::new();
+        doc.write_to(&mut text).unwrap();
+        assert_eq!(text, b"a\n\n");
+    }
+
+    #[test]
+    fn deleting_the_last_lines_moves_the_cursor_up() {
+        let mut doc = Document::new(0, 0, Some(String::from("one\n  two\n    three\nfour")));
+
+        // The last line leaves the cursor on the first non-blank of the new last line.
+        cursor::move_to(&mut doc, Cursor::new(2, 3));
+        line(&mut doc, None, 1);
+        assert_eq!(doc.len(), 3);
+        assert_eq!(doc.line(2).unwrap().to_string(), "    three");
+        assert_eq!((doc.cur.x, doc.cur.y), (4, 2));
+
+        // The second to last line moves the cursor to the following line.
+        cursor::move_to(&mut doc, Cursor::new(0, 1));
+        line(&mut doc, None, 1);
+        assert_eq!(doc.line(1).unwrap().to_string(), "    three");
+        assert_eq!((doc.cur.x, doc.cur.y), (4, 1));
+
+        line(&mut doc, None, 1);
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 0));
+        line(&mut doc, None, 1);
+        assert_empty(&doc);
+    }
+
+    #[test]
+    fn repeated_line_deletes_empty_the_document() {
+        let mut doc = Document::new(0, 0, Some(String::from("a\nb\n\nc\n")));
+        cursor::move_to(&mut doc, Cursor::new(0, 3));
+
+        let mut lens = Vec::new();
+        for _ in 0..6 {
+            line(&mut doc, None, 1);
+            assert!(doc.cur.y < doc.len());
+            assert!(doc.line(doc.cur.y).is_some());
+            lens.push(doc.len());
+        }
+        assert_eq!(lens, [4, 3, 2, 1, 1, 1]);
+        assert_empty(&doc);
+    }
+}
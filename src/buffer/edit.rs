@@ -1,9 +1,10 @@
 use crate::{
-    cursor,
+    cursor::{self, Cursor},
     document::Document,
     history::{History, Replace},
-    util::TAB_WIDTH,
+    util::text_width,
 };
+use ropey::RopeSlice;
 
 /// Writes a char at the current cursor position.
 /// The cursor will be after the new char.
@@ -26,13 +27,13 @@ pub fn write_char(doc: &mut Document, history: Option<&mut History>, ch: char) {
     }
 }
 
-/// Writes a tab at the current cursor position.
-/// The cursor will be after the tab.
-pub fn write_tab(doc: &mut Document, history: Option<&mut History>, relative: bool) {
+/// Writes a tab of `width` spaces at the current cursor position. A relative tab only fills up to the next multiple of
+/// the width. The cursor will be after the tab.
+pub fn write_tab(doc: &mut Document, history: Option<&mut History>, width: usize, relative: bool) {
     let n = if relative {
-        TAB_WIDTH - (doc.cur.x % TAB_WIDTH)
+        width - (doc.cur.x % width)
     } else {
-        TAB_WIDTH
+        width
     };
     let spaces = " ".repeat(n);
 
@@ -48,6 +49,193 @@ pub fn write_tab(doc: &mut Document, history: Option<&mut History>, relative: bo
     cursor::right(doc, n);
 }
 
+/// Removes one level of `width` columns from the indentation of the line of the cursor, if the cursor is within the
+/// indentation. Spaces fill up to the previous indentation stop if removing a tab went past it. The cursor keeps its
+/// place relative to the end of the indentation.
+pub fn dedent(doc: &mut Document, history: Option<&mut History>, width: usize) {
+    let y = doc.cur.y;
+    let Some(line) = doc.line(y) else {
+        return;
+    };
+    let old = indentation(line);
+    let old_len = old.chars().count();
+    if doc.cur.x > old_len || old_len == 0 {
+        return;
+    }
+
+    let new = dedented(&old, width);
+
+    let (start, end) = (Cursor::new(0, y), Cursor::new(old_len, y));
+    let x = doc.cur.x;
+    doc.replace_range(start, end, &new);
+    cursor::move_to(
+        doc,
+        Cursor::new((x + new.chars().count()).saturating_sub(old_len), y),
+    );
+
+    if let Some(history) = history {
+        history.add_change(vec![Replace {
+            pos: start,
+            delete_data: old,
+            insert_data: new,
+        }]);
+    }
+}
+
+/// Removes one level of `width` columns from an indentation. Spaces fill up to the previous indentation stop if
+/// removing a tab went past it.
+fn dedented(indent: &str, width: usize) -> String {
+    let target = text_width(indent, usize::MAX).saturating_sub(1) / width * width;
+    let mut new = indent.to_string();
+    while text_width(&new, usize::MAX) > target {
+        new.pop();
+    }
+    let fill = target - text_width(&new, usize::MAX);
+    new.push_str(&" ".repeat(fill));
+
+    new
+}
+
+/// Gets the indentation of a line.
+fn indentation(line: RopeSlice) -> String {
+    line.chars()
+        .take_while(|ch| matches!(ch, ' ' | '\t'))
+        .collect()
+}
+
+/// Prepends `unit` to the indentation of the lines in the ranges `(first, last)` as one change. Empty lines are left
+/// as they are. Returns if any line was indented.
+pub fn indent_lines(
+    doc: &mut Document,
+    history: Option<&mut History>,
+    ranges: &[(usize, usize)],
+    unit: &str,
+) -> bool {
+    let mut changes = Vec::new();
+    for &(first, last) in ranges {
+        for y in first..=last.min(doc.len().saturating_sub(1)) {
+            let Some(line) = doc.line(y) else {
+                continue;
+            };
+            if line.chars().all(|ch| matches!(ch, '\n' | '\r')) {
+                continue;
+            }
+
+            let pos = Cursor::new(0, y);
+            doc.replace_range(pos, pos, unit);
+            changes.push(Replace {
+                pos,
+                delete_data: String::new(),
+                insert_data: unit.to_string(),
+            });
+        }
+    }
+
+    if changes.is_empty() {
+        return false;
+    }
+
+    if let Some(history) = history {
+        history.add_change(changes);
+    }
+
+    true
+}
+
+/// Removes `levels` levels of `width` columns from the indentation of the lines in the ranges `(first, last)` as one
+/// change. Lines indented by less lose all of their indentation. Returns if any line was dedented.
+pub fn dedent_lines(
+    doc: &mut Document,
+    history: Option<&mut History>,
+    ranges: &[(usize, usize)],
+    width: usize,
+    levels: usize,
+) -> bool {
+    let mut changes = Vec::new();
+    for &(first, last) in ranges {
+        for y in first..=last.min(doc.len().saturating_sub(1)) {
+            let Some(old) = doc.line(y).map(indentation) else {
+                continue;
+            };
+            if old.is_empty() {
+                continue;
+            }
+
+            let mut new = old.clone();
+            for _ in 0..levels {
+                new = dedented(&new, width);
+            }
+
+            let (start, end) = (Cursor::new(0, y), Cursor::new(old.chars().count(), y));
+            doc.replace_range(start, end, &new);
+            changes.push(Replace {
+                pos: start,
+                delete_data: old,
+                insert_data: new,
+            });
+        }
+    }
+
+    if changes.is_empty() {
+        return false;
+    }
+
+    if let Some(history) = history {
+        history.add_change(changes);
+    }
+
+    true
+}
+
+/// Toggles the case of the characters in the ranges `(start, end)` as one change. Characters whose other case consists of
+/// several characters, like 'ß' and 'SS', are replaced by all of them. The ranges have to be sorted and must not
+/// overlap. Returns the end of the first range after toggling.
+pub fn toggle_case(
+    doc: &mut Document,
+    history: Option<&mut History>,
+    ranges: &[(Cursor, Cursor)],
+) -> Option<Cursor> {
+    let mut changes = Vec::new();
+    let mut pos = None;
+    // Toggle back to front so that changed lengths don't shift the following ranges.
+    for &(start, end) in ranges.iter().rev() {
+        let Some(old) = doc.get_range(start, end).map(|text| text.to_string()) else {
+            continue;
+        };
+
+        let mut new = String::with_capacity(old.len());
+        for ch in old.chars() {
+            if ch.is_lowercase() {
+                new.extend(ch.to_uppercase());
+            } else if ch.is_uppercase() {
+                new.extend(ch.to_lowercase());
+            } else {
+                new.push(ch);
+            }
+        }
+
+        if new == old {
+            pos = Some(end);
+            continue;
+        }
+
+        pos = Some(doc.replace_range(start, end, &new));
+        changes.push(Replace {
+            pos: start,
+            delete_data: old,
+            insert_data: new,
+        });
+    }
+
+    if let Some(history) = history
+        && !changes.is_empty()
+    {
+        history.add_change(changes);
+    }
+
+    pos
+}
+
 /// Deletes a character at the current cursor position. The cursor will be at the delete chars position.
 pub fn delete_char(doc: &mut Document, history: Option<&mut History>) {
     cursor::left(doc, 1);
@@ -60,3 +248,257 @@ pub fn delete_char(doc: &mut Document, history: Option<&mut History>) {
         }]);
     }
 }
+
+/// Rewraps the line ranges `(first, last)` to `width` columns as one change. Paragraphs separated by blank lines are
+/// wrapped separately and keep the indentation of their first line, followed by the comment leader of `commentstring`
+/// if all of their lines are commented. The ranges have to be sorted and must not overlap. Returns if the text changed.
+pub fn reflow(
+    doc: &mut Document,
+    history: Option<&mut History>,
+    ranges: &[(usize, usize)],
+    width: usize,
+    commentstring: &str,
+) -> bool {
+    let ending = if doc.line_ending() == "crlf" {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    // Only line comments without a closing part can be continued on the next line.
+    let mark = commentstring
+        .strip_suffix("%s")
+        .map(str::trim)
+        .filter(|mark| !mark.is_empty());
+
+    let mut changes = Vec::new();
+    // Reflow bottom-up so that changed line counts don't shift the following ranges.
+    for &(first, last) in ranges.iter().rev() {
+        let Some(line) = doc.line(last) else {
+            continue;
+        };
+        let len = line
+            .to_string()
+            .trim_end_matches(['\n', '\r'])
+            .chars()
+            .count();
+        let (start, end) = (Cursor::new(0, first), Cursor::new(len, last));
+        let Some(old) = doc.get_range(start, end).map(|text| text.to_string()) else {
+            continue;
+        };
+
+        let lines = old
+            .split('\n')
+            .map(|line| line.trim_end_matches('\r'))
+            .collect::<Vec<_>>();
+        let mut new_lines = Vec::new();
+        let mut idx = 0;
+        while idx < lines.len() {
+            if is_blank(lines[idx], mark) {
+                new_lines.push(lines[idx].to_string());
+                idx += 1;
+                continue;
+            }
+
+            let paragraph_start = idx;
+            while idx < lines.len() && !is_blank(lines[idx], mark) {
+                idx += 1;
+            }
+            new_lines.extend(wrap_paragraph(&lines[paragraph_start..idx], width, mark));
+        }
+
+        let new = new_lines.join(ending);
+        if new == old {
+            continue;
+        }
+
+        doc.replace_range(start, end, &new);
+        changes.push(Replace {
+            pos: start,
+            delete_data: old,
+            insert_data: new,
+        });
+    }
+
+    if changes.is_empty() {
+        return false;
+    }
+
+    if let Some(history) = history {
+        history.add_change(changes);
+    }
+
+    true
+}
+
+/// Joins the line ranges `(first, last)` into one line each as one change. If `trim` is set the leading whitespace of
+/// joined lines is replaced by a single space, which is left out if the line before ends with whitespace or the joined
+/// line is empty or starts with `)`. Otherwise the lines are concatenated as-is. The ranges have to be sorted and must
+/// not overlap. Returns the position of the last join of the first range if any lines were joined.
+pub fn join(
+    doc: &mut Document,
+    history: Option<&mut History>,
+    ranges: &[(usize, usize)],
+    trim: bool,
+) -> Option<Cursor> {
+    let mut changes = Vec::new();
+    let mut pos = None;
+    // Join bottom-up so that the removed lines don't shift the following ranges.
+    for &(first, last) in ranges.iter().rev() {
+        let Some(line) = doc.line(last).filter(|_| first < last) else {
+            continue;
+        };
+        let len = line
+            .to_string()
+            .trim_end_matches(['\n', '\r'])
+            .chars()
+            .count();
+        let (start, end) = (Cursor::new(0, first), Cursor::new(len, last));
+        let Some(old) = doc.get_range(start, end).map(|text| text.to_string()) else {
+            continue;
+        };
+
+        let mut lines = old.split('\n').map(|line| line.trim_end_matches('\r'));
+        let mut new = lines.next().unwrap_or_default().to_string();
+        let mut join_x = 0;
+        for line in lines {
+            join_x = new.chars().count();
+            if !trim {
+                new.push_str(line);
+                continue;
+            }
+
+            let line = line.trim_start();
+            if !line.is_empty() && !line.starts_with(')') && !new.ends_with(char::is_whitespace) {
+                new.push(' ');
+            }
+            new.push_str(line);
+        }
+
+        doc.replace_range(start, end, &new);
+        pos = Some(Cursor::new(join_x, first));
+        changes.push(Replace {
+            pos: start,
+            delete_data: old,
+            insert_data: new,
+        });
+    }
+
+    if let Some(history) = history
+        && !changes.is_empty()
+    {
+        history.add_change(changes);
+    }
+
+    pos
+}
+
+/// Breaks the line of the cursor at the last whitespace that keeps the text before it within `width` columns, if the
+/// text before the cursor is wider. The whitespace is replaced by a line break and the indentation of the line, as one
+/// change. Lines without such whitespace, like a single long word, are left as-is. Returns if the line was broken.
+pub fn wrap_line(doc: &mut Document, history: Option<&mut History>, width: usize) -> bool {
+    let y = doc.cur.y;
+    if doc.line_width(y, doc.cur.x) <= width {
+        return false;
+    }
+    let Some(line) = doc.line(y) else {
+        return false;
+    };
+
+    let chars = line.chars().take(doc.cur.x).collect::<Vec<_>>();
+    let indent = chars
+        .iter()
+        .take_while(|ch| ch.is_whitespace())
+        .collect::<String>();
+    let indent_len = indent.chars().count();
+
+    // Find the last whitespace run after the indentation that is followed by text before the cursor.
+    let mut brk = None;
+    let mut x = indent_len;
+    while x < chars.len() {
+        if !chars[x].is_whitespace() {
+            x += 1;
+            continue;
+        }
+
+        let start = x;
+        while x < chars.len() && chars[x].is_whitespace() {
+            x += 1;
+        }
+        if x < chars.len() && doc.line_width(y, start) <= width {
+            brk = Some((start, x));
+        }
+    }
+    let Some((start, end)) = brk else {
+        return false;
+    };
+
+    let ending = if doc.line_ending() == "crlf" {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let old = chars[start..end].iter().collect::<String>();
+    let new = format!("{ending}{indent}");
+
+    let (pos1, pos2) = (Cursor::new(start, y), Cursor::new(end, y));
+    let cur_x = doc.cur.x;
+    doc.replace_range(pos1, pos2, &new);
+    doc.cur = Cursor::new(indent_len + cur_x - end, y + 1);
+
+    if let Some(history) = history {
+        history.add_change(vec![Replace {
+            pos: pos1,
+            delete_data: old,
+            insert_data: new,
+        }]);
+    }
+
+    true
+}
+
+/// Checks if a line separates paragraphs, either because it is empty or only contains whitespace and a comment mark.
+fn is_blank(line: &str, mark: Option<&str>) -> bool {
+    let line = line.trim();
+    line.is_empty() || mark == Some(line)
+}
+
+/// Wraps the words of the lines of a paragraph greedily to `width` columns. Words longer than the width are kept on a
+/// line of their own.
+fn wrap_paragraph(lines: &[&str], width: usize, mark: Option<&str>) -> Vec<String> {
+    let indent = lines[0]
+        .chars()
+        .take_while(|ch| ch.is_whitespace())
+        .collect::<String>();
+    let mark = mark.filter(|mark| lines.iter().all(|line| line.trim_start().starts_with(mark)));
+    let leader = mark.map_or_else(|| indent.clone(), |mark| format!("{indent}{mark} "));
+    let leader_width = text_width(&leader, usize::MAX);
+
+    let words = lines.iter().flat_map(|line| {
+        let line = line.trim_start();
+        mark.and_then(|mark| line.strip_prefix(mark))
+            .unwrap_or(line)
+            .split_whitespace()
+    });
+
+    let mut wrapped = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in words {
+        let word_width = text_width(word, usize::MAX);
+        if !line.is_empty() && leader_width + line_width + 1 + word_width > width {
+            wrapped.push(format!("{leader}{line}"));
+            line.clear();
+            line_width = 0;
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    wrapped.push(format!("{leader}{line}"));
+
+    wrapped
+}
@@ -1,17 +1,17 @@
 use crate::{
     buffer::BufferResult,
-    cursor,
+    clipboard::ClipboardProvider,
+    cursor::{self, Cursor, FindChar},
     document::Document,
     selection::{Selection, SelectionKind},
 };
-use arboard::Clipboard;
 
 macro_rules! yank_fn {
     ($func:ident, $func_call:ident, $comment:meta $(,$n:ident)?) => {
         #[$comment]
         pub fn $func(
             doc: &mut Document,
-            clipboard: &mut Clipboard,
+            clipboard: &mut dyn ClipboardProvider,
             $($n: usize,)?
         ) -> Result<(), BufferResult> {
             let tmp_doc_cur = doc.cur;
@@ -40,15 +40,23 @@ macro_rules! yank_fn {
 /// Convenience macro for calling yank functions. Expects a `BaseBuffer` as member `base`.
 macro_rules! yank {
     ($self:ident, $func:ident) => {
-        match $crate::buffer::yank::$func(&mut $self.base.doc, &mut $self.base.clipboard) {
+        match $crate::buffer::yank::$func(
+            &mut $self.base.doc,
+            $self.base.clipboard.borrow_mut().as_mut(),
+        ) {
             Ok(()) => {}
             Err(err) => return err,
         }
     };
-    ($self:ident, $func:ident, REPEAT) => {{
-        if let Err(err) =
-            $crate::buffer::yank::$func(&mut $self.base.doc, &mut $self.base.clipboard, 1)
-        {
+    ($self:ident, $func:ident, REPEAT) => {
+        $crate::yank!($self, $func, REPEAT, 1)
+    };
+    ($self:ident, $func:ident, REPEAT, $n:expr) => {{
+        if let Err(err) = $crate::buffer::yank::$func(
+            &mut $self.base.doc,
+            $self.base.clipboard.borrow_mut().as_mut(),
+            $n,
+        ) {
             return err;
         }
     }};
@@ -56,7 +64,7 @@ macro_rules! yank {
         if let Err(err) = $crate::buffer::yank::$func(
             &mut $self.base.doc,
             &mut $self.base.selections,
-            &mut $self.base.clipboard,
+            $self.base.clipboard.borrow_mut().as_mut(),
         ) {
             return err;
         }
@@ -69,29 +77,64 @@ macro_rules! yank {
 pub fn selection(
     doc: &Document,
     selections: &mut [Selection],
-    clipboard: &mut Clipboard,
+    clipboard: &mut dyn ClipboardProvider,
 ) -> Result<(), BufferResult> {
     let mut buff = Vec::new();
 
     selections.sort_unstable();
     for selection in selections {
         let (start, end) = selection.range();
-        buff.push(doc.get_range(start, end).unwrap().to_string());
+        if let Some(text) = doc.get_range(start, end) {
+            buff.push(text.to_string());
+        }
     }
 
     if !buff.is_empty() {
         let res = clipboard.set_text(buff.join("\n"));
         return match res {
             Ok(()) => Ok(()),
-            Err(err) => Err(BufferResult::Error(err.to_string())),
+            Err(err) => Err(BufferResult::Error(err)),
         };
     }
 
     Ok(())
 }
 
+/// Yanks to the nth occurrence of a character in the line of the cursor. Does nothing if the character is not found.
+pub fn find_char(
+    doc: &mut Document,
+    clipboard: &mut dyn ClipboardProvider,
+    find: FindChar,
+    ch: char,
+    n: usize,
+) -> Result<(), BufferResult> {
+    let start = doc.cur;
+    if !find.apply(doc, ch, n) {
+        return Ok(());
+    }
+
+    let end = if find.inclusive() {
+        Cursor::new(doc.cur.x + 1, doc.cur.y)
+    } else {
+        doc.cur
+    };
+    doc.cur = start;
+
+    selection(
+        doc,
+        &mut [Selection::new(
+            start,
+            end,
+            SelectionKind::Normal,
+            None,
+            None,
+        )],
+        clipboard,
+    )
+}
+
 /// Yanks a line.
-pub fn line(doc: &Document, clipboard: &mut Clipboard) -> Result<(), BufferResult> {
+pub fn line(doc: &Document, clipboard: &mut dyn ClipboardProvider) -> Result<(), BufferResult> {
     selection(
         doc,
         &mut [Selection::new(
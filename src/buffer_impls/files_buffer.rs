@@ -1,18 +1,32 @@
 mod apply_command;
 mod interact;
+mod loader;
 
 use crate::{
+    bookmarks,
     buffer::{Buffer, BufferKind, BufferResult, base::BaseBuffer, edit},
-    cursor::{self, CursorStyle},
+    buffer_impls::files_buffer::loader::{DirLoader, DirLoaderResult},
+    clipboard::SharedClipboard,
+    command_history::SharedHistory,
+    cursor::{self, Cursor, CursorStyle},
     display::Display,
     document::Document,
     jump,
     message::{Message, MessageKind},
     movement,
     selection::SelectionKind,
-    shift, yank,
+    shift,
+    util::{TAB_WIDTH, quote_path},
+    viewport::Viewport,
+    yank,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Error,
+    path::PathBuf,
+    sync::mpsc::TryRecvError,
+    time::Instant,
 };
-use std::{io::Error, path::PathBuf};
 use termion::event::Key;
 
 enum Mode {
@@ -23,6 +37,8 @@ enum Mode {
 enum ViewMode {
     Normal,
     Yank,
+    Scroll,
+    Repeat,
 }
 
 /// A file browser buffer.
@@ -38,6 +54,24 @@ pub struct FilesBuffer {
     path: PathBuf,
     /// All entries of the dir containing the current item.
     entries: Vec<PathBuf>,
+    /// If the buffer lists the bookmarks instead of a directory.
+    bookmarks: bool,
+    /// The directories expanded in tree mode.
+    expanded: HashSet<PathBuf>,
+    /// The loader streaming in the entries of a directory, if it is still being read.
+    loader: Option<DirLoader>,
+    /// The cached type labels of the entries shown in the info line.
+    types: HashMap<PathBuf, &'static str>,
+
+    /// The preview content of the selected entry.
+    preview: Document,
+    /// The viewport of the preview.
+    preview_view: Viewport,
+    /// The entry currently loaded into the preview.
+    preview_entry: Option<PathBuf>,
+
+    /// The time of the last input, used to detect pending input timeouts.
+    last_input: Instant,
 }
 
 impl FilesBuffer {
@@ -47,17 +81,39 @@ impl FilesBuffer {
         x_off: usize,
         y_off: usize,
         path: PathBuf,
+        clipboard: SharedClipboard,
+        cmd_history: SharedHistory,
     ) -> Result<Self, Error> {
-        let mut entries = Vec::new();
-        let contents = Self::load_dir(&path, &mut entries)?;
+        // The entries are streamed in by the loader.
+        let loader = DirLoader::new(&Self::listed_dir(&path)?)?;
+
+        let mut base = BaseBuffer::new(
+            w,
+            h,
+            x_off,
+            y_off,
+            Some(String::from("..")),
+            clipboard,
+            cmd_history,
+        );
+        base.options.files = true;
 
         Ok(Self {
-            base: BaseBuffer::new(w, h, x_off, y_off, Some(contents))?,
+            base,
             mode: Mode::View,
             view_mode: ViewMode::Normal,
             info: Document::new(0, 0, None),
             path,
-            entries,
+            entries: Vec::new(),
+            bookmarks: false,
+            expanded: HashSet::new(),
+            loader: Some(loader),
+            types: HashMap::new(),
+            preview: Document::new(0, 0, None),
+            // FIXME: this limits the bar to always be exactly one in height.
+            preview_view: Viewport::new(w - w / 2, h - 1, x_off + w / 2, y_off + 1, None),
+            preview_entry: None,
+            last_input: Instant::now(),
         })
     }
 
@@ -80,15 +136,78 @@ impl FilesBuffer {
         }
 
         match new_mode {
-            Mode::Command => self.base.cmd_history_idx = self.base.cmd_history.len(),
+            Mode::Command => self.base.cmd_history_idx = self.base.cmd_history.borrow().len(),
             Mode::View => {}
         }
 
         self.mode = new_mode;
     }
 
+    /// Creates a files buffer listing the bookmarks.
+    pub fn bookmarks(
+        w: usize,
+        h: usize,
+        x_off: usize,
+        y_off: usize,
+        clipboard: SharedClipboard,
+        cmd_history: SharedHistory,
+    ) -> Result<Self, Error> {
+        let mut buffer = Self::new(
+            w,
+            h,
+            x_off,
+            y_off,
+            std::env::current_dir()?,
+            clipboard,
+            cmd_history,
+        )?;
+        buffer.bookmarks = true;
+        buffer.loader = None;
+        buffer
+            .base
+            .doc
+            .from(Self::load_bookmarks(&mut buffer.entries)?.as_str());
+
+        Ok(buffer)
+    }
+
+    /// Splits the buffer width between the entry list and the preview if the preview is enabled.
+    fn layout(&mut self) {
+        let (w, h, x_off, y_off) = (self.base.w, self.base.h, self.base.x_off, self.base.y_off);
+        let doc_w = if self.base.options.preview { w / 2 } else { w };
+        if self.base.doc_view.w == doc_w {
+            return;
+        }
+
+        // FIXME: this limits the bar to always be exactly one in height.
+        self.base
+            .doc_view
+            .resize(doc_w, h - 1, x_off, y_off + 1, Some(self.base.doc.len()));
+        self.preview_view
+            .resize(w - doc_w, h - 1, x_off + doc_w, y_off + 1, None);
+    }
+
     fn refresh(&mut self) -> BufferResult {
-        match Self::load_dir(&self.path, &mut self.entries) {
+        // Dropping the loader stops loading the previous listing.
+        self.loader = None;
+        self.types.clear();
+
+        let contents = if self.bookmarks {
+            Self::load_bookmarks(&mut self.entries)
+        } else if self.base.options.tree {
+            Self::load_dir(&self.path, &mut self.entries, &self.expanded)
+        } else {
+            // The entries are streamed in by the loader.
+            Self::listed_dir(&self.path)
+                .and_then(|dir| DirLoader::new(&dir))
+                .map(|loader| {
+                    self.loader = Some(loader);
+                    self.entries.clear();
+                    String::from("..")
+                })
+        };
+
+        match contents {
             Ok(contents) => {
                 // Set contents moves the doc.cur to the beginning.
                 self.base.doc.from(contents.as_str());
@@ -105,17 +224,136 @@ impl FilesBuffer {
         }
     }
 
+    /// Appends the entries read by the loader since the last tick to the listing. Once loading finished the listing is
+    /// sorted, keeping the cursor on the selected entry.
+    fn load_tick(&mut self) -> BufferResult {
+        let Some(loader) = &mut self.loader else {
+            return BufferResult::Ok;
+        };
+
+        let mut res = None;
+        let mut received = String::new();
+        // Greedily read as much as possible.
+        loop {
+            match loader.rx.try_recv() {
+                Ok(DirLoaderResult::Entries(batch)) => {
+                    received.push_str(&loader.append(&mut self.entries, batch));
+                }
+                Ok(DirLoaderResult::Error(err)) => {
+                    res = Some(BufferResult::Error(err));
+                    break;
+                }
+                Ok(DirLoaderResult::Done) => {
+                    res = Some(BufferResult::Ok);
+                    break;
+                }
+                // Ignore empty error since we're waiting on entries.
+                Err(TryRecvError::Empty) => break,
+                Err(err) => {
+                    res = Some(BufferResult::Error(err.to_string()));
+                    break;
+                }
+            }
+        }
+
+        if !received.is_empty() {
+            // The listing is not modified by the user, it just grows.
+            self.base.doc.append_str(&received);
+            self.base.doc.edited = false;
+            self.base.rerender = true;
+        }
+
+        // Loading finished or failed.
+        let Some(res) = res else {
+            return BufferResult::Ok;
+        };
+        let mut loader = self.loader.take().unwrap();
+        self.base.rerender = true;
+
+        let selected = self
+            .base
+            .doc
+            .cur
+            .y
+            .checked_sub(1)
+            .and_then(|idx| self.entries.get(idx).cloned());
+        if let Some(lines) = loader.sort(&mut self.entries) {
+            let mut contents = String::from("..");
+            for line in &lines {
+                contents.push('\n');
+                contents.push_str(line);
+            }
+
+            // Set contents moves the doc.cur to the beginning.
+            let (x, scroll_y) = (self.base.doc.cur.x, self.base.doc_view.scroll_y);
+            self.base.doc.from(contents.as_str());
+            let y = selected.map_or(0, |entry| {
+                self.entries.binary_search(&entry).map_or(0, |idx| idx + 1)
+            });
+            cursor::move_to(&mut self.base.doc, Cursor::new(x, y));
+            self.base.doc_view.scroll_y = scroll_y;
+
+            // Sorting moves the lines under matches and selections.
+            self.base.clear_matches();
+            self.base.clear_selections();
+        }
+
+        res
+    }
+
+    /// Gets the type label of an entry, `stat`ing it only the first time.
+    fn entry_type(&mut self, idx: usize) -> &'static str {
+        let entry = &self.entries[idx];
+        if let Some(kind) = self.types.get(entry) {
+            return kind;
+        }
+
+        let kind = if entry.is_symlink() {
+            " [Symlink]"
+        } else if entry.is_dir() {
+            " [Dir]"
+        } else {
+            " [File]"
+        };
+        self.types.insert(entry.clone(), kind);
+
+        kind
+    }
+
     fn selected_remove_command<S: AsRef<str>>(&mut self, cmd: S) -> BufferResult {
-        if self.base.doc.cur.y == 0 {
+        let Some(entry) = self
+            .base
+            .doc
+            .cur
+            .y
+            .checked_sub(1)
+            .and_then(|idx| self.entries.get(idx))
+        else {
             return BufferResult::Ok;
+        };
+
+        // Only remove the bookmark, not the bookmarked file.
+        if self.bookmarks {
+            if let Err(err) = bookmarks::remove(entry) {
+                return BufferResult::Error(err.to_string());
+            }
+
+            return self.refresh();
         }
 
+        // Use the path of the entry since the line can be indented or contain a symlink target.
+        let suffix = if entry.is_dir() && !entry.is_symlink() {
+            "/"
+        } else {
+            ""
+        };
+
         // Set the command and move the cursor to be at the end of the input.
         self.base.cmd.from(
             format!(
                 "{} {}",
                 cmd.as_ref(),
-                self.base.doc.line(self.base.doc.cur.y).unwrap()
+                quote_path(&format!("{}{suffix}", entry.display()))
             )
             .as_str(),
         );
@@ -138,24 +376,38 @@ impl FilesBuffer {
         let view_mode = match self.view_mode {
             ViewMode::Normal => "",
             ViewMode::Yank => " [yank]",
+            ViewMode::Scroll => " [scroll]",
+            ViewMode::Repeat => " [repeat]",
         };
         // No plus 1 since the first entry is always ".." and not really a directory entry.
         let curr = self.base.doc.cur.y;
         let curr_type = match curr {
             0 => " [Parent Dir]",
-            idx if self.entries[idx - 1].is_symlink() => " [Symlink]",
-            idx if self.entries[idx - 1].is_dir() => " [Dir]",
-            _ => " [File]",
+            idx => self.entry_type(idx - 1),
+        };
+        let kind = if self.bookmarks {
+            "[Bookmarks]"
+        } else {
+            "[Files]"
         };
         let entries = self.entries.len();
         let entries_label = if entries == 1 { "Entry" } else { "Entries" };
+        let loading = if self.loader.is_some() {
+            " [loading…]"
+        } else {
+            ""
+        };
 
         write!(
             &mut info_line,
-            "[Files]{mode} [{curr}/{entries} {entries_label}]{curr_type}{view_mode}",
+            "{kind}{mode} [{curr}/{entries} {entries_label}]{loading}{curr_type}{view_mode}",
         )
         .unwrap();
 
+        if self.base.doc_view.scroll_x > 0 {
+            write!(&mut info_line, " [offset {}]", self.base.doc_view.scroll_x).unwrap();
+        }
+
         match self.base.selections.len() {
             0 => {}
             1 => write!(&mut info_line, " [1 selection]").unwrap(),
@@ -165,6 +417,16 @@ impl FilesBuffer {
         self.info.from(info_line.as_str());
     }
 
+    /// Gets the keys of a pending multi-key input.
+    const fn pending_keys(&self) -> &'static str {
+        match self.view_mode {
+            ViewMode::Normal => "",
+            ViewMode::Yank => "y",
+            ViewMode::Scroll => "z",
+            ViewMode::Repeat => "@",
+        }
+    }
+
     /// Handles self defined view actions.
     fn view_tick(&mut self, key: Option<Key>) -> BufferResult {
         let Some(key) = key else {
@@ -175,12 +437,16 @@ impl FilesBuffer {
             ViewMode::Normal => match key {
                 Key::Char('h') | Key::Left => movement!(self, left),
                 Key::Char('H') => shift!(self, shift_left),
-                Key::Char('j') | Key::Down => movement!(self, down),
+                Key::Char('j') | Key::Down => self.base.vertical_motion(true, 1),
                 Key::Char('J') => shift!(self, shift_right),
-                Key::Char('k') | Key::Up => movement!(self, up),
+                Key::Char('k') | Key::Up => self.base.vertical_motion(false, 1),
                 Key::Char('K') => shift!(self, shift_up),
                 Key::Char('l') | Key::Right => movement!(self, right),
                 Key::Char('L') => shift!(self, shift_down),
+                Key::Ctrl('d') => shift!(self, page_down, self.base.half_page()),
+                Key::Ctrl('u') => shift!(self, page_up, self.base.half_page()),
+                Key::Ctrl('f') | Key::PageDown => shift!(self, page_down, self.base.doc_view.h),
+                Key::Ctrl('b') | Key::PageUp => shift!(self, page_up, self.base.doc_view.h),
                 Key::Char('w') => movement!(self, next_word),
                 Key::Char('W') => movement!(self, next_word_end),
                 Key::Char('b') => movement!(self, prev_word),
@@ -189,9 +455,9 @@ impl FilesBuffer {
                 Key::Char('S') => movement!(self, prev_whitespace),
                 Key::Char('}') => movement!(self, next_empty_line),
                 Key::Char('{') => movement!(self, prev_empty_line),
-                Key::Char('<') => jump!(self, jump_to_beginning_of_line),
-                Key::Char('>') => jump!(self, jump_to_end_of_line),
-                Key::Char('.') => jump!(self, jump_to_matching_opposite),
+                Key::Char('<') | Key::Home => jump!(self, jump_to_beginning_of_line),
+                Key::Char('>') | Key::End => jump!(self, jump_to_end_of_line),
+                Key::Char('%') => jump!(self, jump_to_matching_opposite),
                 Key::Char('g') => jump!(self, jump_to_end_of_file),
                 Key::Char('G') => jump!(self, jump_to_beginning_of_file),
                 Key::Char('v') => {
@@ -204,9 +470,10 @@ impl FilesBuffer {
                 }
                 Key::Esc => self.base.clear_selections(),
                 Key::Char('y') => self.view_mode = ViewMode::Yank,
+                Key::Char('z') => self.view_mode = ViewMode::Scroll,
                 Key::Char(' ') => self.change_mode(Mode::Command),
-                Key::Char('n') => self.base.next_match(),
-                Key::Char('N') => self.base.prev_match(),
+                Key::Char('n') => return self.base.next_match(),
+                Key::Char('N') => return self.base.prev_match(),
                 Key::Char('r') => return self.refresh(),
                 Key::Char('\n') => {
                     return self
@@ -218,8 +485,23 @@ impl FilesBuffer {
                 }
                 Key::Char('d') => return self.selected_remove_command("rm"),
                 Key::Char('D') => return self.selected_remove_command("rm!"),
+                Key::Char('@') => self.view_mode = ViewMode::Repeat,
                 _ => {}
             },
+            ViewMode::Repeat => {
+                self.view_mode = ViewMode::Normal;
+                if key == Key::Char(':') {
+                    return self.repeat_command();
+                }
+            }
+            ViewMode::Scroll => {
+                match key {
+                    Key::Char('h') => shift!(self, scroll_left),
+                    Key::Char('l') => shift!(self, scroll_right),
+                    _ => {}
+                }
+                self.view_mode = ViewMode::Normal;
+            }
             ViewMode::Yank => {
                 match key {
                     Key::Char('v') => yank!(self, selection, SELECTION),
@@ -236,7 +518,7 @@ impl FilesBuffer {
                     Key::Char('{') => yank!(self, prev_empty_line, REPEAT),
                     Key::Char('<') => yank!(self, beginning_of_line),
                     Key::Char('>') => yank!(self, end_of_line),
-                    Key::Char('.') => yank!(self, matching_opposite),
+                    Key::Char('%') => yank!(self, matching_opposite),
                     Key::Char('g') => yank!(self, end_of_file),
                     Key::Char('G') => yank!(self, beginning_of_file),
                     _ => {}
@@ -266,16 +548,13 @@ impl FilesBuffer {
                 // Commands have only one line.
                 let cmd = self.base.cmd.line(0).unwrap().to_string();
                 if !cmd.is_empty() {
-                    self.base.cmd_history.push(cmd.clone());
+                    self.base.push_command_history(&cmd);
                 }
                 self.change_mode(Mode::View);
 
-                match self.base.apply_command(cmd) {
-                    Ok(res) => return res,
-                    Err(cmd) => return self.apply_command(&cmd),
-                }
+                return self.run_command(cmd);
             }
-            Key::Char('\t') => edit::write_tab(&mut self.base.cmd, None, false),
+            Key::Char('\t') => edit::write_tab(&mut self.base.cmd, None, TAB_WIDTH, false),
             Key::Backspace => edit::delete_char(&mut self.base.cmd, None),
             Key::Char(ch) => edit::write_char(&mut self.base.cmd, None, ch),
             _ => {}
@@ -283,6 +562,46 @@ impl FilesBuffer {
 
         BufferResult::Ok
     }
+
+    /// Aborts a pending multi-key input if it was not completed in time.
+    fn pending_timeout(&mut self) {
+        if matches!(self.view_mode, ViewMode::Normal)
+            || !self.base.pending_timed_out(self.last_input)
+        {
+            return;
+        }
+
+        self.view_mode = ViewMode::Normal;
+        self.base.rerender = true;
+    }
+
+    /// Runs a command of the base buffer or of the files buffer.
+    fn run_command(&mut self, cmd: String) -> BufferResult {
+        let tree = self.base.options.tree;
+        match self.base.apply_command(cmd) {
+            Ok(res) => {
+                // Switching between the flat and tree listing requires reloading the entries.
+                if self.base.options.tree != tree
+                    && let BufferResult::Error(err) = self.refresh()
+                {
+                    return BufferResult::Error(err);
+                }
+
+                res
+            }
+            Err(cmd) => self.apply_command(&cmd),
+        }
+    }
+
+    /// Runs the last command entered in this session again. Commands loaded from the history of earlier sessions are
+    /// not repeated. The files buffer has no counts, so the command is run only once.
+    fn repeat_command(&mut self) -> BufferResult {
+        let Some(cmd) = self.base.last_command.clone() else {
+            return BufferResult::Info("No previous command".to_string());
+        };
+
+        self.run_command(cmd)
+    }
 }
 
 impl Buffer for FilesBuffer {
@@ -300,33 +619,57 @@ impl Buffer for FilesBuffer {
 
     fn render(&mut self, display: &mut Display) {
         self.base.rerender = false;
+        display.set_truecolor(self.base.options.termguicolors);
 
         let (cursor_style, cmd) = match self.mode {
-            Mode::View => (CursorStyle::SteadyBlock, false),
-            Mode::Command => (CursorStyle::SteadyBar, true),
+            Mode::View => (self.base.options.guicursor.view, false),
+            Mode::Command => (self.base.options.guicursor.command, true),
         };
 
-        self.base.doc_view.recalculate_viewport(&self.base.doc);
-        self.base.doc_view.render_gutter(display, &self.base.doc);
+        self.layout();
+
         self.base
             .doc_view
-            .render_document(display, &self.base.doc, &self.base.selections);
-
-        if cmd {
-            self.base.cmd_view.recalculate_viewport(&self.base.cmd);
+            .recalculate_viewport(&self.base.doc, self.base.options.scrolloff);
+        self.base
+            .doc_view
+            .render_gutter(display, &self.base.doc, &self.base.options);
+        self.base.update_highlights(false);
+        self.base.doc_view.render_document(
+            display,
+            &mut self.base.doc,
+            &self.base.selections,
+            &self.base.highlights,
+            &self.base.options,
+        );
 
-            self.base.cmd_view.render_bar(
-                self.base.cmd.line(0).unwrap().to_string().trim_end(),
-                0,
+        if self.base.options.preview {
+            self.load_preview();
+            self.preview_view.render_document(
                 display,
+                &mut self.preview,
+                &[],
+                &[],
+                &self.base.options,
             );
+        }
+
+        if cmd {
+            self.base.render_command(display);
+
+            // The expanded command line overwrites lines of the preview.
+            if self.base.cmd_view.h > 1 {
+                self.preview_view.invalidate();
+            }
         } else {
-            self.base.info_view.recalculate_viewport(&self.info);
+            self.base.info_view.recalculate_viewport(&self.info, 0);
             self.info_line();
 
-            self.base.info_view.render_bar(
+            self.base.info_view.render_split_bar(
                 self.info.line(0).unwrap().to_string().trim_end(),
+                self.pending_keys(),
                 0,
+                self.base.bell(),
                 display,
             );
         }
@@ -339,22 +682,40 @@ impl Buffer for FilesBuffer {
             return;
         }
 
-        let (view, doc) = if cmd {
-            (&self.base.cmd_view, &self.base.cmd)
+        if cmd {
+            self.base
+                .cmd_view
+                .render_wrapped_cursor(display, &self.base.cmd, cursor_style);
         } else {
-            (&self.base.doc_view, &self.base.doc)
-        };
-        view.render_cursor(display, doc, cursor_style);
+            self.base
+                .doc_view
+                .render_cursor(display, &self.base.doc, cursor_style);
+        }
     }
 
     fn resize(&mut self, w: usize, h: usize, x_off: usize, y_off: usize) {
         self.base.resize(w, h, x_off, y_off);
+        self.layout();
+
+        // The amount of previewed lines depends on the height.
+        self.preview_entry = None;
     }
 
     fn tick(&mut self, key: Option<Key>) -> BufferResult {
         // Only rerender if input was received.
         self.base.rerender |= key.is_some();
 
+        if key.is_some() {
+            self.last_input = Instant::now();
+        } else {
+            self.pending_timeout();
+            self.base.bell_tick();
+        }
+
+        if let res @ BufferResult::Error(_) = self.load_tick() {
+            return res;
+        }
+
         // Intercept inputs if a message is shown.
         if let Some(message) = &mut self.base.message
             && let Some(key) = key
@@ -374,8 +735,13 @@ impl Buffer for FilesBuffer {
                     return BufferResult::Ok;
                 }
                 Key::Char('Y') => {
-                    if let Err(err) = self.base.clipboard.set_text(message.text.clone()) {
-                        return BufferResult::Error(err.to_string());
+                    if let Err(err) = self
+                        .base
+                        .clipboard
+                        .borrow_mut()
+                        .set_text(message.text.clone())
+                    {
+                        return BufferResult::Error(err);
                     }
 
                     return BufferResult::Info("Message yanked to clipboard".to_string());
@@ -402,4 +768,15 @@ impl Buffer for FilesBuffer {
     fn can_quit(&self) -> Result<(), String> {
         Ok(())
     }
+
+    fn write(&mut self) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    fn close(&mut self) {}
+
+    #[cfg(test)]
+    fn doc(&self) -> &Document {
+        &self.base.doc
+    }
 }
@@ -1,7 +1,19 @@
-use crate::{buffer::BufferResult, buffer_impls::files_buffer::FilesBuffer, util::open_file};
+use crate::{
+    buffer::{BufferResult, base::BaseBuffer},
+    buffer_impls::files_buffer::FilesBuffer,
+    trash,
+    util::{PathArg, open_file, path_arg},
+};
+use std::path::Path;
 
 impl FilesBuffer {
     fn create_command(&mut self, args: &str) -> BufferResult {
+        let PathArg { path, warning } = match path_arg(args) {
+            Ok(arg) => arg,
+            Err(res) => return res,
+        };
+        let args = path.as_str();
+
         // Create only directories.
         if args.ends_with('/') {
             if let Err(err) = std::fs::create_dir_all(args) {
@@ -12,10 +24,30 @@ impl FilesBuffer {
             return BufferResult::Error(err.to_string());
         }
 
-        self.refresh()
+        self.refresh().with_warning(warning)
     }
 
     pub(super) fn remove_command(&mut self, args: &str) -> BufferResult {
+        let PathArg { path, warning } = match path_arg(args) {
+            Ok(arg) => arg,
+            Err(res) => return res,
+        };
+        let args = path.as_str();
+
+        // Move files and directories of any content to the trash if enabled.
+        if self.base.options.trash {
+            return match trash::trash(Path::new(args), &self.base.options.trashdir) {
+                Ok(target) => match self.refresh() {
+                    BufferResult::Ok => {
+                        BufferResult::Info(format!("Moved '{args}' to '{}'", target.display()))
+                    }
+                    res => res,
+                },
+                Err(err) => BufferResult::Error(err.to_string()),
+            }
+            .with_warning(warning);
+        }
+
         // Remove only directories.
         if args.ends_with('/') {
             if let Err(err) = std::fs::remove_dir(args) {
@@ -25,20 +57,41 @@ impl FilesBuffer {
             return BufferResult::Error(err.to_string());
         }
 
-        self.refresh()
+        self.refresh().with_warning(warning)
     }
 
     pub(super) fn recursive_remove_command(&mut self, args: &str) -> BufferResult {
-        // Remove only directories.
-        if args.ends_with('/') {
-            if let Err(err) = std::fs::remove_dir_all(args) {
-                return BufferResult::Error(err.to_string());
-            }
+        let PathArg { path, warning } = match path_arg(args) {
+            Ok(arg) => arg,
+            Err(res) => return res,
+        };
+        let args = path.as_str();
 
-            return self.refresh();
+        // Permanently remove files and directories with all of their contents, bypassing the trash.
+        let res = if args.ends_with('/') {
+            std::fs::remove_dir_all(args)
+        } else {
+            std::fs::remove_file(args)
+        };
+        if let Err(err) = res {
+            return BufferResult::Error(err.to_string());
         }
 
-        BufferResult::Info("Recursive removal only works for directories".to_string())
+        self.refresh().with_warning(warning)
+    }
+
+    fn bookmark_command(&self, args: &str) -> BufferResult {
+        // Bookmark the selected entry or the current directory if no path is given.
+        let default = self
+            .base
+            .doc
+            .cur
+            .y
+            .checked_sub(1)
+            .and_then(|idx| self.entries.get(idx))
+            .unwrap_or(&self.path);
+
+        BaseBuffer::bookmark_command(args, Some(default.clone()))
     }
 
     /// Applies the command entered during command mode.
@@ -56,6 +109,7 @@ impl FilesBuffer {
             "mk" => self.create_command(args),
             "rm" => self.remove_command(args),
             "rm!" => self.recursive_remove_command(args),
+            "bm" | "bookmark" => self.bookmark_command(args),
             _ => BufferResult::Error(format!("Unrecognized command: '{cmd}'")),
         }
     }
@@ -1,56 +1,190 @@
 use crate::{
+    bookmarks,
     buffer::BufferResult,
     buffer_impls::{files_buffer::FilesBuffer, text_buffer::TextBuffer},
-    util::{file_name, open_file},
+    cursor::{self, Cursor},
+    util::open_file_or_read_only,
 };
 use std::{
-    fs::read_dir,
-    io::Error,
+    collections::HashSet,
+    ffi::OsStr,
+    fs::{File, read_dir},
+    io::{BufRead, BufReader, Error, Read},
     path::{Path, PathBuf},
 };
 
+/// The maximum amount of bytes read from a file for its preview.
+const PREVIEW_BYTES: u64 = 64 * 1024;
+
 impl FilesBuffer {
-    /// Loads a directory as path buffers and Strings. Does NOT move the cursor to be valid!
-    pub(super) fn load_dir(base: &Path, entries: &mut Vec<PathBuf>) -> Result<String, Error> {
-        let mut base = if base.is_dir() {
-            base.to_path_buf()
+    /// Gets the directory listing a path, the path itself if it is a directory or its parent otherwise. Falls back to
+    /// the current directory if the path does not exist.
+    pub(super) fn listed_dir(path: &Path) -> Result<PathBuf, Error> {
+        let dir = if path.is_dir() {
+            path.to_path_buf()
         } else {
-            PathBuf::from(base.parent().unwrap_or_else(|| Path::new("")))
+            PathBuf::from(path.parent().unwrap_or_else(|| Path::new("")))
         };
-        if !base.exists() {
-            base = std::env::current_dir()?;
+        if !dir.exists() {
+            return std::env::current_dir();
+        }
+
+        Ok(dir)
+    }
+
+    /// Loads a directory as a tree of path buffers and Strings, expanding the given directories in place. Does NOT move
+    /// the cursor to be valid!
+    pub(super) fn load_dir(
+        base: &Path,
+        entries: &mut Vec<PathBuf>,
+        expanded: &HashSet<PathBuf>,
+    ) -> Result<String, Error> {
+        let base = Self::listed_dir(base)?;
+
+        entries.clear();
+        let mut lines = vec![String::from("..")];
+        Self::load_tree(&base, 0, expanded, entries, &mut lines)?;
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Loads the bookmarks as path buffers and Strings. Does NOT move the cursor to be valid!
+    pub(super) fn load_bookmarks(entries: &mut Vec<PathBuf>) -> Result<String, Error> {
+        *entries = bookmarks::load()?;
+
+        let mut lines = vec![String::from("..")];
+        lines.extend(entries.iter().map(|entry| Self::entry_line(entry, None)));
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Recursively appends the entries of a directory and all expanded subdirectories.
+    fn load_tree(
+        dir: &Path,
+        depth: usize,
+        expanded: &HashSet<PathBuf>,
+        entries: &mut Vec<PathBuf>,
+        lines: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        for entry in Self::read_entries(dir)? {
+            let is_expanded = expanded.contains(&entry);
+            let marker = if !entry.is_dir() {
+                ' '
+            } else if is_expanded {
+                '▾'
+            } else {
+                '▸'
+            };
+            lines.push(format!(
+                "{}{marker} {}",
+                "  ".repeat(depth),
+                Self::entry_line(&entry, entry.file_name())
+            ));
+            entries.push(entry.clone());
+
+            // Unreadable subdirectories are shown without children.
+            if is_expanded && entry.is_dir() {
+                let _ = Self::load_tree(&entry, depth + 1, expanded, entries, lines);
+            }
         }
 
-        *entries = read_dir(base)?
+        Ok(())
+    }
+
+    /// Reads the sorted entries of a directory.
+    fn read_entries(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut entries = read_dir(dir)?
             .map(|res| res.map(|e| e.path()))
             .collect::<Result<Vec<_>, Error>>()?;
         entries.sort();
 
-        let mut contents = String::from("..");
-        if !entries.is_empty() {
-            let len = entries.len() - 1;
-            contents.push('\n');
-            contents.extend(entries.iter().enumerate().map(|(idx, entry)| {
-                let nl = if idx == len { "" } else { "\n" };
-                if entry.is_symlink() {
-                    let target = entry.read_link().unwrap_or_else(|_| PathBuf::from("?"));
-                    let suffix = if target.is_dir() { "/" } else { "" };
-                    format!("{} -> {}{suffix}{nl}", entry.display(), target.display())
-                } else if entry.is_dir() {
-                    format!("{}/{nl}", entry.display())
-                } else {
-                    format!("{}{nl}", entry.display())
-                }
-            }));
+        Ok(entries)
+    }
+
+    /// Creates the line of an entry, displaying the given name or the full path.
+    pub(super) fn entry_line(entry: &Path, name: Option<&OsStr>) -> String {
+        let name = name.map_or_else(
+            || entry.display().to_string(),
+            |name| name.display().to_string(),
+        );
+
+        if entry.is_symlink() {
+            let target = entry.read_link().unwrap_or_else(|_| PathBuf::from("?"));
+            let suffix = if target.is_dir() { "/" } else { "" };
+            format!("{name} -> {}{suffix}", target.display())
+        } else if entry.is_dir() {
+            format!("{name}/")
+        } else {
+            name
+        }
+    }
+
+    /// Loads the preview of the selected entry if it changed since the last call. Files preview their first lines
+    /// and directories their listing.
+    pub(super) fn load_preview(&mut self) {
+        // The parent dir is not previewed.
+        let entry = self
+            .base
+            .doc
+            .cur
+            .y
+            .checked_sub(1)
+            .and_then(|idx| self.entries.get(idx).cloned());
+        if entry == self.preview_entry {
+            return;
+        }
+
+        let contents = match &entry {
+            Some(entry) if entry.is_dir() => Self::read_head_dir(entry, self.preview_view.h),
+            Some(entry) if entry.is_file() => Self::read_head(entry, self.preview_view.h),
+            _ => Ok(String::new()),
+        };
+        let contents = contents.unwrap_or_else(|err| err.to_string());
+
+        // Set contents moves the preview.cur to the beginning.
+        self.preview.from(contents.as_str());
+        self.preview_view.scroll_x = 0;
+        self.preview_view.scroll_y = 0;
+        self.preview_entry = entry;
+    }
+
+    /// Lists up to `n` lines of a directory. Only the listed entries are `stat`ed.
+    fn read_head_dir(dir: &Path, n: usize) -> Result<String, Error> {
+        let mut lines = vec![String::from("..")];
+        lines.extend(
+            Self::read_entries(dir)?
+                .iter()
+                .take(n.saturating_sub(1))
+                .map(|entry| Self::entry_line(entry, None)),
+        );
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Reads up to `n` lines from the beginning of a file.
+    fn read_head(path: &Path, n: usize) -> Result<String, Error> {
+        let mut reader = BufReader::new(File::open(path)?.take(PREVIEW_BYTES));
+        let mut buf = Vec::new();
+
+        for _ in 0..n {
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
         }
 
-        Ok(contents)
+        Ok(String::from_utf8_lossy(&buf).to_string())
     }
 
     /// Handles the user selection of an entry in the file buffer.
     pub(super) fn select_item(&mut self) -> Result<BufferResult, Error> {
         let idx = self.base.doc.cur.y;
 
+        // Leave the bookmarks to the listing of the current directory.
+        if idx == 0 && self.bookmarks {
+            self.bookmarks = false;
+            return Ok(self.refresh());
+        }
+
         // Move directory up.
         if idx == 0 {
             if self.path.pop() {
@@ -60,21 +194,41 @@ impl FilesBuffer {
             return Ok(BufferResult::Ok);
         }
 
-        let entry = &self.entries[idx.saturating_sub(1)].clone();
+        let Some(entry) = self.entries.get(idx - 1).cloned() else {
+            return Ok(BufferResult::Ok);
+        };
+        let entry = &entry;
         if entry.is_file() {
+            let (file, read_only) = open_file_or_read_only(entry)?;
             let text_buffer = TextBuffer::new(
                 self.base.w,
                 self.base.h,
                 self.base.x_off,
                 self.base.y_off,
-                Some(open_file(entry)?),
-                file_name(entry),
+                Some(file),
+                Some(entry.clone()),
+                read_only,
+                self.base.clipboard.clone(),
+                self.base.cmd_history.clone(),
             )?;
 
             // Replace this `FilesBuffer` instance with a `TextBuffer` instance containing the file content.
             return Ok(BufferResult::Init(Box::new(text_buffer)));
+        } else if entry.is_dir() && self.base.options.tree && !self.bookmarks {
+            // Toggle the expansion in place, keeping the cursor on the directory.
+            if !self.expanded.remove(entry) {
+                self.expanded.insert(entry.clone());
+            }
+
+            let scroll_y = self.base.doc_view.scroll_y;
+            let res = self.refresh();
+            cursor::move_to(&mut self.base.doc, Cursor::new(0, idx));
+            self.base.doc_view.scroll_y = scroll_y;
+
+            return Ok(res);
         } else if entry.is_dir() {
             self.path.clone_from(entry);
+            self.bookmarks = false;
             return Ok(self.refresh());
         }
 
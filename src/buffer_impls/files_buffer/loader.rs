@@ -0,0 +1,104 @@
+use crate::buffer_impls::files_buffer::FilesBuffer;
+use std::{
+    fs::read_dir,
+    io::Error,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// The amount of entries read before they are sent to the buffer.
+const BATCH_LEN: usize = 512;
+
+pub enum DirLoaderResult {
+    /// A sorted batch of entries and their lines.
+    Entries(Vec<(PathBuf, String)>),
+    Error(String),
+    Done,
+}
+
+/// A helper to read the entries of a directory in the background and stream them to the buffer.
+pub struct DirLoader {
+    /// The entry stream.
+    pub rx: Receiver<DirLoaderResult>,
+
+    /// The lines of the entries received so far, in the order of the entries. Each batch is appended, so they are only
+    /// sorted once loading finished.
+    pub lines: Vec<String>,
+}
+
+impl DirLoader {
+    /// Starts reading the entries of a directory. Only opening the directory happens synchronously, reading and
+    /// `stat`ing the entries happens on a background thread.
+    pub fn new(dir: &Path) -> Result<Self, Error> {
+        use DirLoaderResult::{Done, Entries};
+
+        let read_dir = read_dir(dir)?;
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut batch = Vec::with_capacity(BATCH_LEN);
+
+            for entry in read_dir {
+                match entry {
+                    Ok(entry) => {
+                        let path = entry.path();
+                        let line = FilesBuffer::entry_line(&path, None);
+                        batch.push((path, line));
+                    }
+                    Err(err) => {
+                        let _ = tx.send(DirLoaderResult::Error(err.to_string()));
+                        return;
+                    }
+                }
+
+                if batch.len() == BATCH_LEN {
+                    batch.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                    // The buffer stopped listening, e.g. because it was refreshed.
+                    if tx.send(Entries(std::mem::take(&mut batch))).is_err() {
+                        return;
+                    }
+                }
+            }
+
+            batch.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            let _ = tx.send(Entries(batch));
+            let _ = tx.send(Done);
+        });
+
+        Ok(Self {
+            rx,
+            lines: Vec::new(),
+        })
+    }
+
+    /// Appends a batch to the entries and their lines. Returns the text of the lines to append to the listing.
+    pub fn append(&mut self, entries: &mut Vec<PathBuf>, batch: Vec<(PathBuf, String)>) -> String {
+        let mut text = String::new();
+        for (entry, line) in batch {
+            text.push('\n');
+            text.push_str(&line);
+            entries.push(entry);
+            self.lines.push(line);
+        }
+
+        text
+    }
+
+    /// Sorts the entries and their lines. Returns the sorted lines, or `None` if they already were sorted.
+    pub fn sort(&mut self, entries: &mut Vec<PathBuf>) -> Option<Vec<String>> {
+        if entries.is_sorted() {
+            return None;
+        }
+
+        let mut listing = std::mem::take(entries)
+            .into_iter()
+            .zip(std::mem::take(&mut self.lines))
+            .collect::<Vec<_>>();
+        listing.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let (sorted, lines) = listing.into_iter().unzip();
+        *entries = sorted;
+
+        Some(lines)
+    }
+}
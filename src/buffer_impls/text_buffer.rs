@@ -3,27 +3,41 @@ mod history;
 mod insert;
 
 use crate::{
-    buffer::{Buffer, BufferKind, BufferResult, base::BaseBuffer, delete, edit},
+    buffer::{Buffer, BufferKind, BufferResult, base::BaseBuffer, delete, edit, yank},
     change,
-    cursor::{self, CursorStyle},
+    clipboard::SharedClipboard,
+    command_history::SharedHistory,
+    cursor::{self, Cursor, CursorStyle, FindChar},
     delete,
     display::Display,
     document::Document,
+    editorconfig,
+    filetype::{self, COMMIT_BODY_WIDTH, COMMIT_SUBJECT_WIDTH},
     history::History,
     jump,
     message::{Message, MessageKind},
     movement,
     selection::SelectionKind,
     shell_command::{ShellCommand, ShellCommandResult},
-    shift, yank,
+    shift, swap,
+    util::{TAB_WIDTH, absolute_path, file_name, format_bytes, literal_char, text_width},
+    yank,
 };
+use portable_pty::ExitStatus;
 use std::{
     fs::File,
     io::{Error, Read},
+    path::PathBuf,
     sync::mpsc::TryRecvError,
+    time::{Duration, Instant},
 };
 use termion::event::Key;
 
+/// Idle time in seconds after which modifications are written to the swap file.
+const SWAP_IDLE: u64 = 4;
+/// Amount of keys after which modifications are written to the swap file without idling.
+const SWAP_KEYS: usize = 200;
+
 enum Mode {
     View,
     Command,
@@ -33,9 +47,58 @@ enum Mode {
 enum ViewMode {
     Normal,
     Yank,
+    Scroll,
     Delete,
     Change,
     Replace,
+    Repeat,
+    Go,
+    /// Waits for the character of a find-char motion, which is applied by the operator.
+    Find(Operator, FindChar),
+    /// Waits for the key following a 'g' of an operator, 'n' applying it to the next match.
+    OperatorGo(Operator),
+}
+
+/// The operator applied to a find-char motion.
+#[derive(Clone, Copy)]
+enum Operator {
+    Move,
+    Yank,
+    Delete,
+    Change,
+}
+
+impl Operator {
+    /// Gets the operator of a view mode that accepts a find-char motion.
+    const fn from_view_mode(view_mode: &ViewMode) -> Option<Self> {
+        match view_mode {
+            ViewMode::Normal => Some(Self::Move),
+            ViewMode::Yank => Some(Self::Yank),
+            ViewMode::Delete => Some(Self::Delete),
+            ViewMode::Change => Some(Self::Change),
+            _ => None,
+        }
+    }
+}
+
+/// A key in insert mode that changes how the next key is handled.
+#[derive(PartialEq, Eq)]
+enum InsertPending {
+    None,
+    /// The next key is inserted literally.
+    Literal,
+    /// The next command is a view mode command, after which insert mode continues.
+    ViewCommand,
+}
+
+/// What happens once a shell command finished.
+enum ShellOutput {
+    /// The output is appended to the document.
+    Append,
+    /// The output is opened in a new scratchpad buffer.
+    Scratch,
+    /// The command authenticated with sudo, after which the document is written to its file with elevated privileges.
+    SudoWrite,
 }
 
 /// A text buffer.
@@ -49,24 +112,61 @@ pub struct TextBuffer {
 
     /// The opened file.
     file: Option<File>,
+    /// The path of the opened file.
+    path: Option<PathBuf>,
     /// The name of the opened file.
     file_name: Option<String>,
+    /// Flag if the file was opened read-only.
+    read_only: bool,
 
     /// A runner handling command execution.
     shell_command: Option<ShellCommand>,
+    /// What happens once the shell command finished.
+    shell_output: ShellOutput,
+    /// A temporary file passed to the shell command, removed once it finished.
+    shell_tmp: Option<PathBuf>,
 
     /// A history of edits to undo and redo.
     history: History,
+    /// How the next key in insert mode is handled.
+    insert_pending: InsertPending,
+    /// The last find-char motion and its character, repeated by `;` and `,`.
+    last_find: Option<(FindChar, char)>,
+    /// The digits of the count typed before a view mode command.
+    count: String,
+    /// The count of the keys of a pending command typed so far, like the '3' of '3d2w', multiplied with the count
+    /// typed next.
+    pending_count: usize,
+    /// The keys of the last edit, including the keys typed in insert mode, repeated by `.`.
+    last_edit: Vec<Key>,
+    /// The keys of the command being typed, kept as the last edit once it completed and changed the document.
+    edit_keys: Vec<Key>,
+    /// The time of the last change before the command being typed, to detect if it changed the document.
+    edit_undo_time: Option<Instant>,
+
+    /// The time of the last input, used to detect idle periods for autosaving and pending input timeouts.
+    last_input: Instant,
+    /// Flag if the current file contents were written by autosaving.
+    autosaved: bool,
+    /// Flag if autosaving failed, cleared by the next input to not retry before it.
+    autosave_failed: bool,
+    /// Keys pressed since the swap file was last written, `None` if the swap file is up to date.
+    swap_keys: Option<usize>,
 }
 
 impl TextBuffer {
+    // The file, its path, the clipboard and the command history come on top of the position and size every buffer takes.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         w: usize,
         h: usize,
         x_off: usize,
         y_off: usize,
         mut file: Option<File>,
-        file_name: Option<String>,
+        path: Option<PathBuf>,
+        read_only: bool,
+        clipboard: SharedClipboard,
+        cmd_history: SharedHistory,
     ) -> Result<Self, Error> {
         let contents = if let Some(file) = file.as_mut() {
             let mut buff = String::new();
@@ -77,16 +177,96 @@ impl TextBuffer {
             None
         };
 
-        Ok(Self {
-            base: BaseBuffer::new(w, h, x_off, y_off, contents)?,
+        let mut buffer = Self {
+            base: BaseBuffer::new(w, h, x_off, y_off, contents, clipboard, cmd_history),
             mode: Mode::View,
             view_mode: ViewMode::Normal,
             info: Document::new(0, 0, None),
             file,
-            file_name,
+            file_name: path.as_ref().and_then(file_name),
+            path: path.map(absolute_path),
+            read_only,
             shell_command: None,
+            shell_output: ShellOutput::Append,
+            shell_tmp: None,
             history: History::new(),
-        })
+            insert_pending: InsertPending::None,
+            last_find: None,
+            count: String::new(),
+            pending_count: 1,
+            last_edit: Vec::new(),
+            edit_keys: Vec::new(),
+            edit_undo_time: None,
+            last_input: Instant::now(),
+            autosaved: false,
+            autosave_failed: false,
+            swap_keys: None,
+        };
+        buffer.load_file_options();
+        buffer.check_swap();
+
+        Ok(buffer)
+    }
+
+    /// Creates a scratchpad buffer without a backing file containing the contents.
+    pub fn from_contents(
+        w: usize,
+        h: usize,
+        x_off: usize,
+        y_off: usize,
+        contents: &str,
+        clipboard: SharedClipboard,
+        cmd_history: SharedHistory,
+    ) -> Result<Self, Error> {
+        let mut buff = Self::new(
+            w,
+            h,
+            x_off,
+            y_off,
+            None,
+            None,
+            false,
+            clipboard,
+            cmd_history,
+        )?;
+        buff.base.doc.from(contents);
+
+        Ok(buff)
+    }
+
+    /// Creates a scratchpad buffer like `from_contents` whose lines wrap at the window width.
+    pub fn from_wrapped_contents(
+        w: usize,
+        h: usize,
+        x_off: usize,
+        y_off: usize,
+        contents: &str,
+        clipboard: SharedClipboard,
+        cmd_history: SharedHistory,
+    ) -> Result<Self, Error> {
+        let mut buff = Self::from_contents(w, h, x_off, y_off, contents, clipboard, cmd_history)?;
+        buff.base.options.wrap = true;
+        buff.base.doc_view.wrap = true;
+
+        Ok(buff)
+    }
+
+    /// Detects the filetype from the path and the first line of the document and applies its defaults. The
+    /// `.editorconfig` of the file takes precedence over the defaults of the filetype.
+    fn load_file_options(&mut self) {
+        let first_line = self
+            .base
+            .doc
+            .line(0)
+            .map(|l| l.to_string())
+            .unwrap_or_default();
+        self.base
+            .options
+            .set_filetype(filetype::detect(self.path.as_deref(), &first_line).unwrap_or_default());
+
+        if let Some(path) = &self.path {
+            editorconfig::apply(path, &mut self.base.options);
+        }
     }
 
     /// Changes the mode.
@@ -109,7 +289,7 @@ impl TextBuffer {
         }
 
         match new_mode {
-            Mode::Command => self.base.cmd_history_idx = self.base.cmd_history.len(),
+            Mode::Command => self.base.cmd_history_idx = self.base.cmd_history.borrow().len(),
             Mode::Insert => {
                 // Edits might cause matches and selections to become invalid.
                 self.base.clear_matches();
@@ -133,15 +313,34 @@ impl TextBuffer {
             Mode::Command => unreachable!(),
         };
         let view_mode = match self.view_mode {
-            ViewMode::Normal => "",
+            ViewMode::Normal => match self.insert_pending {
+                InsertPending::None => "",
+                InsertPending::Literal => " [literal]",
+                InsertPending::ViewCommand => " [(insert)]",
+            },
             ViewMode::Yank => " [yank]",
+            ViewMode::Scroll => " [scroll]",
             ViewMode::Delete => " [delete]",
             ViewMode::Change => " [change]",
             ViewMode::Replace => " [replace]",
+            ViewMode::Repeat => " [repeat]",
+            ViewMode::Go | ViewMode::OperatorGo(_) => " [go]",
+            ViewMode::Find(..) => " [find]",
         };
         // Plus 1 since text coordinates are 0 indexed.
         let line = self.base.doc.cur.y + 1;
         let col = self.base.doc.cur.x + 1;
+        // Tabs and wide characters make the displayed column differ from the character column.
+        let visual_col = self
+            .base
+            .doc
+            .line_width(self.base.doc.cur.y, self.base.doc.cur.x)
+            + 1;
+        let col = if visual_col == col {
+            col.to_string()
+        } else {
+            format!("{col}-{visual_col}")
+        };
         let total = self.base.doc.len();
         let percentage = 100 * line / total;
         let size: usize = self.base.doc.lines().map(|l| l.bytes().len()).sum();
@@ -149,60 +348,268 @@ impl TextBuffer {
         if self.file.is_some() {
             write!(&mut info_line, "[{}] ", self.file_name.as_ref().unwrap()).unwrap();
         }
+        if self.read_only {
+            write!(&mut info_line, "[RO] ").unwrap();
+        }
 
         write!(
             &mut info_line,
-            "{mode}[{line}:{col}/{total} {percentage}%] [{size}B]{view_mode}",
+            "{mode}[{line}:{col}/{total} {percentage}%] [{size}B]",
         )
         .unwrap();
+        if !self.base.options.filetype.is_empty() {
+            write!(&mut info_line, " [{}]", self.base.options.filetype).unwrap();
+        }
+        if self.base.options.commit_guide() {
+            let width = self.base.doc.line(self.base.doc.cur.y).map_or(0, |l| {
+                text_width(l.to_string().trim_end_matches(['\n', '\r']), usize::MAX)
+            });
+            match self.base.doc.cur.y {
+                0 => write!(&mut info_line, " [subject {width}/{COMMIT_SUBJECT_WIDTH}]").unwrap(),
+                1 if width > 0 => write!(&mut info_line, " [blank line after subject]").unwrap(),
+                _ => write!(&mut info_line, " [body {width}/{COMMIT_BODY_WIDTH}]").unwrap(),
+            }
+        }
+        write!(&mut info_line, "{view_mode}").unwrap();
+
+        if self.base.doc_view.scroll_x > 0 {
+            write!(&mut info_line, " [offset {}]", self.base.doc_view.scroll_x).unwrap();
+        }
+
+        let synmaxcol = self.base.options.synmaxcol;
+        if synmaxcol > 0
+            && self
+                .base
+                .doc
+                .line_count(self.base.doc.cur.y)
+                .is_some_and(|count| count > synmaxcol)
+        {
+            write!(&mut info_line, " [long line]").unwrap();
+        }
 
-        match self.base.selections.len() {
-            0 => {}
-            1 => write!(&mut info_line, " [1 selection]").unwrap(),
-            n => write!(&mut info_line, " [{n} selections]").unwrap(),
+        if !self.base.selections.is_empty() {
+            // The size of the selected text, counting lines that are only selected up to their beginning as unselected.
+            let (chars, lines) =
+                self.base
+                    .selections
+                    .iter()
+                    .fold((0, 0), |(chars, lines), selection| {
+                        let (start, end) = selection.range();
+                        let len = self
+                            .base
+                            .doc
+                            .get_range(start, end)
+                            .map_or(0, |text| text.len_chars());
+                        let end_y = if end.x == 0 && end.y > start.y {
+                            end.y - 1
+                        } else {
+                            end.y
+                        };
+                        (chars + len, lines + end_y - start.y + 1)
+                    });
+
+            match self.base.selections.len() {
+                1 => write!(&mut info_line, " [1 selection: {chars} chars").unwrap(),
+                n => write!(&mut info_line, " [{n} selections: {chars} chars").unwrap(),
+            }
+            if lines > 1 {
+                write!(&mut info_line, ", {lines} lines").unwrap();
+            }
+            write!(&mut info_line, "]").unwrap();
         }
 
         if let Some(shell_command) = &self.shell_command {
+            if shell_command.scrollback() > 0 {
+                write!(
+                    &mut info_line,
+                    " [scrollback {}]",
+                    shell_command.scrollback()
+                )
+                .unwrap();
+            }
+
+            let spinner = shell_command.spinner();
+            let received = format_bytes(shell_command.received);
             match shell_command.cmd.split_whitespace().next() {
-                Some(cmd) => write!(&mut info_line, " [Command '{cmd}' running]",).unwrap(),
-                None => write!(&mut info_line, " [Command running]",).unwrap(),
+                Some(cmd) => write!(
+                    &mut info_line,
+                    " [Command '{cmd}' running {spinner} {received}]"
+                )
+                .unwrap(),
+                None => write!(&mut info_line, " [Command running {spinner} {received}]").unwrap(),
             }
         }
 
+        if self.autosaved && !self.base.doc.edited {
+            write!(&mut info_line, " [autosaved]").unwrap();
+        }
+
         let edited = if self.base.doc.edited { '*' } else { ' ' };
         write!(&mut info_line, " {edited}").unwrap();
 
         self.info.from(info_line.as_str());
     }
 
+    /// Gets the count and keys of a pending multi-key input.
+    fn pending_keys(&self) -> String {
+        let keys = match self.view_mode {
+            ViewMode::Normal => match self.insert_pending {
+                InsertPending::None => "",
+                InsertPending::Literal => "^V",
+                InsertPending::ViewCommand => "^O",
+            },
+            ViewMode::Yank => "y",
+            ViewMode::Scroll => "z",
+            ViewMode::Delete => "d",
+            ViewMode::Change => "c",
+            ViewMode::Replace => "r",
+            ViewMode::Repeat => "@",
+            ViewMode::Go => "g",
+            ViewMode::Find(operator, find) => match (operator, find) {
+                (Operator::Move, FindChar::Forward) => "f",
+                (Operator::Move, FindChar::Backward) => "F",
+                (Operator::Move, FindChar::TillForward) => "t",
+                (Operator::Move, FindChar::TillBackward) => "T",
+                (Operator::Yank, FindChar::Forward) => "yf",
+                (Operator::Yank, FindChar::Backward) => "yF",
+                (Operator::Yank, FindChar::TillForward) => "yt",
+                (Operator::Yank, FindChar::TillBackward) => "yT",
+                (Operator::Delete, FindChar::Forward) => "df",
+                (Operator::Delete, FindChar::Backward) => "dF",
+                (Operator::Delete, FindChar::TillForward) => "dt",
+                (Operator::Delete, FindChar::TillBackward) => "dT",
+                (Operator::Change, FindChar::Forward) => "cf",
+                (Operator::Change, FindChar::Backward) => "cF",
+                (Operator::Change, FindChar::TillForward) => "ct",
+                (Operator::Change, FindChar::TillBackward) => "cT",
+            },
+            ViewMode::OperatorGo(operator) => match operator {
+                Operator::Move => "g",
+                Operator::Yank => "yg",
+                Operator::Delete => "dg",
+                Operator::Change => "cg",
+            },
+        };
+
+        let pending_count = if self.pending_count > 1 {
+            self.pending_count.to_string()
+        } else {
+            String::new()
+        };
+
+        format!("{pending_count}{keys}{}", self.count)
+    }
+
+    /// Creates a summary of the buffer's file and state.
+    fn file_info(&self) -> String {
+        let path = self.path.as_ref().map_or_else(
+            || "[Scratchpad]".to_string(),
+            |path| {
+                path.canonicalize()
+                    .unwrap_or_else(|_| path.clone())
+                    .display()
+                    .to_string()
+            },
+        );
+        let lines = self.base.doc.len();
+        let lines_label = if lines == 1 { "line" } else { "lines" };
+        let modified = if self.base.doc.edited {
+            "modified"
+        } else {
+            "unmodified"
+        };
+        let read_only = if self.read_only { ", read-only" } else { "" };
+        // Files are always read as UTF-8.
+        let ending = self.base.doc.line_ending();
+
+        format!("\"{path}\"\n{lines} {lines_label}, {modified}{read_only}, utf-8, {ending}")
+    }
+
     /// Handles self defined view actions.
     fn view_tick(&mut self, key: Option<Key>) -> BufferResult {
         let Some(key) = key else {
             return BufferResult::Ok;
         };
 
+        // Digits form the count of the following motion or operator. A leading '0' jumps to the beginning of the line
+        // instead.
+        if let Key::Char(ch @ '0'..='9') = key
+            && (ch != '0' || !self.count.is_empty())
+            && Operator::from_view_mode(&self.view_mode).is_some()
+        {
+            self.count.push(ch);
+            return BufferResult::Ok;
+        }
+
+        // The counts before and after an operator multiply, like in '3d2w' to delete six words. The count only holds
+        // digits, so it fails to parse if it is empty or too large.
+        let count = if self.count.is_empty() {
+            1
+        } else {
+            self.count.parse().unwrap_or(usize::MAX)
+        };
+        let n = self.pending_count.saturating_mul(count);
+        let res = self.view_command(key, n);
+
+        // The count is used up once the command is complete.
+        self.count.clear();
+        self.pending_count = if matches!(self.view_mode, ViewMode::Normal) {
+            1
+        } else {
+            n
+        };
+
+        res
+    }
+
+    /// Handles a view mode command, repeating motions and operators `n` times.
+    fn view_command(&mut self, key: Key, n: usize) -> BufferResult {
+        // Find-char motions wait for their character, which is then applied by the pending operator.
+        if let Key::Char(ch) = key
+            && let Some(find) = FindChar::from_key(ch)
+            && let Some(operator) = Operator::from_view_mode(&self.view_mode)
+        {
+            self.view_mode = ViewMode::Find(operator, find);
+            return BufferResult::Ok;
+        }
+
         match self.view_mode {
             ViewMode::Normal => match key {
-                Key::Char('h') | Key::Left => movement!(self, left),
+                Key::Char('h') | Key::Left => movement!(self, left, n),
                 Key::Char('H') => shift!(self, shift_left),
-                Key::Char('j') | Key::Down => movement!(self, down),
+                Key::Char('j') | Key::Down => self.base.vertical_motion(true, n),
                 Key::Char('J') => shift!(self, shift_down),
-                Key::Char('k') | Key::Up => movement!(self, up),
+                Key::Char('k') | Key::Up => self.base.vertical_motion(false, n),
                 Key::Char('K') => shift!(self, shift_up),
-                Key::Char('l') | Key::Right => movement!(self, right),
+                Key::Char('l') | Key::Right => movement!(self, right, n),
                 Key::Char('L') => shift!(self, shift_right),
-                Key::Char('w') => movement!(self, next_word),
-                Key::Char('W') => movement!(self, next_word_end),
-                Key::Char('b') => movement!(self, prev_word),
-                Key::Char('B') => movement!(self, prev_word_end),
-                Key::Char('s') => movement!(self, next_whitespace),
-                Key::Char('S') => movement!(self, prev_whitespace),
-                Key::Char('}') => movement!(self, next_empty_line),
-                Key::Char('{') => movement!(self, prev_empty_line),
-                Key::Char('<') => jump!(self, jump_to_beginning_of_line),
-                Key::Char('>') => jump!(self, jump_to_end_of_line),
-                Key::Char('.') => jump!(self, jump_to_matching_opposite),
-                Key::Char('g') => jump!(self, jump_to_end_of_file),
+                Key::Ctrl('d') => shift!(self, page_down, self.base.half_page()),
+                Key::Ctrl('u') => shift!(self, page_up, self.base.half_page()),
+                Key::Ctrl('f') | Key::PageDown => shift!(self, page_down, self.base.doc_view.h),
+                Key::Ctrl('b') | Key::PageUp => shift!(self, page_up, self.base.doc_view.h),
+                Key::Char('w') => movement!(self, next_word, n),
+                Key::Char('W') => movement!(self, next_word_end, n),
+                Key::Char('b') => movement!(self, prev_word, n),
+                Key::Char('B') => movement!(self, prev_word_end, n),
+                Key::CtrlRight => movement!(self, next_subword, n),
+                Key::CtrlLeft => movement!(self, prev_subword, n),
+                Key::Char('s') => movement!(self, next_whitespace, n),
+                Key::Char('S') => movement!(self, prev_whitespace, n),
+                Key::Char('}') => movement!(self, next_empty_line, n),
+                Key::Char('{') => movement!(self, prev_empty_line, n),
+                Key::Char('<' | '0') | Key::Home => jump!(self, jump_to_beginning_of_line),
+                Key::Char('>') | Key::End => jump!(self, jump_to_end_of_line),
+                Key::Char('|') => {
+                    cursor::jump_to_column(&mut self.base.doc, n.saturating_sub(1));
+                    self.base.update_selection();
+                }
+                Key::Char('\t') => self.indent(false, n),
+                Key::BackTab => self.indent(true, n),
+                Key::Char('%') => jump!(self, jump_to_matching_opposite),
+                Key::Char('.') => return self.repeat_edit(n),
+                Key::Char(';') => self.repeat_find(false, n),
+                Key::Char(',') => self.repeat_find(true, n),
+                Key::Char('g') => self.view_mode = ViewMode::Go,
                 Key::Char('G') => jump!(self, jump_to_beginning_of_file),
                 Key::Char('v') => {
                     self.base.add_selection(SelectionKind::Normal);
@@ -214,9 +621,11 @@ impl TextBuffer {
                 }
                 Key::Esc => self.base.clear_selections(),
                 Key::Char('y') => self.view_mode = ViewMode::Yank,
+                Key::Char('z') => self.view_mode = ViewMode::Scroll,
                 Key::Char(' ') => self.change_mode(Mode::Command),
-                Key::Char('n') => self.base.next_match(),
-                Key::Char('N') => self.base.prev_match(),
+                Key::Ctrl('g') => return BufferResult::Info(self.file_info()),
+                Key::Char('n') => return self.base.next_match(),
+                Key::Char('N') => return self.base.prev_match(),
                 Key::Char('i') => self.change_mode(Mode::Insert),
                 Key::Char('a') => {
                     cursor::right(&mut self.base.doc, 1);
@@ -235,7 +644,7 @@ impl TextBuffer {
                     self.change_mode(Mode::Insert);
                 }
                 Key::Char('d') => self.view_mode = ViewMode::Delete,
-                Key::Char('x') => delete!(self, right, REPEAT),
+                Key::Char('x') => delete!(self, character, REPEAT, n),
                 Key::Char('c') => self.view_mode = ViewMode::Change,
                 Key::Char('p') => {
                     if let Some(res) = self.paste(false, false) {
@@ -257,57 +666,72 @@ impl TextBuffer {
                     self.base.clear_selections();
                 }
                 Key::Char('r') => self.view_mode = ViewMode::Replace,
-                Key::Char('u') => self.undo(),
-                Key::Char('U') => self.redo(),
+                Key::Char('~') => self.toggle_case(n),
+                Key::Char('@') => self.view_mode = ViewMode::Repeat,
+                Key::Char('u') => {
+                    self.undo();
+                }
+                Key::Char('U') => {
+                    self.redo();
+                }
                 _ => {}
             },
+            ViewMode::Scroll => {
+                match key {
+                    Key::Char('h') => shift!(self, scroll_left),
+                    Key::Char('l') => shift!(self, scroll_right),
+                    _ => {}
+                }
+                self.view_mode = ViewMode::Normal;
+            }
             ViewMode::Yank => {
+                self.view_mode = ViewMode::Normal;
                 match key {
                     Key::Char('v') => yank!(self, selection, SELECTION),
                     Key::Char('y') => yank!(self, line),
-                    Key::Char('h') => yank!(self, left, REPEAT),
-                    Key::Char('l') => yank!(self, right, REPEAT),
-                    Key::Char('w') => yank!(self, next_word, REPEAT),
-                    Key::Char('W') => yank!(self, next_word_end, REPEAT),
-                    Key::Char('b') => yank!(self, prev_word, REPEAT),
-                    Key::Char('B') => yank!(self, prev_word_end, REPEAT),
-                    Key::Char('s') => yank!(self, next_whitespace, REPEAT),
-                    Key::Char('S') => yank!(self, prev_whitespace, REPEAT),
-                    Key::Char('}') => yank!(self, next_empty_line, REPEAT),
-                    Key::Char('{') => yank!(self, prev_empty_line, REPEAT),
-                    Key::Char('<') => yank!(self, beginning_of_line),
+                    Key::Char('h') => yank!(self, left, REPEAT, n),
+                    Key::Char('l') => yank!(self, right, REPEAT, n),
+                    Key::Char('w') => yank!(self, next_word, REPEAT, n),
+                    Key::Char('W') => yank!(self, next_word_end, REPEAT, n),
+                    Key::Char('b') => yank!(self, prev_word, REPEAT, n),
+                    Key::Char('B') => yank!(self, prev_word_end, REPEAT, n),
+                    Key::Char('s') => yank!(self, next_whitespace, REPEAT, n),
+                    Key::Char('S') => yank!(self, prev_whitespace, REPEAT, n),
+                    Key::Char('}') => yank!(self, next_empty_line, REPEAT, n),
+                    Key::Char('{') => yank!(self, prev_empty_line, REPEAT, n),
+                    Key::Char('<' | '0') => yank!(self, beginning_of_line),
                     Key::Char('>') => yank!(self, end_of_line),
-                    Key::Char('.') => yank!(self, matching_opposite),
-                    Key::Char('g') => yank!(self, end_of_file),
+                    Key::Char('%') => yank!(self, matching_opposite),
+                    Key::Char('g') => self.view_mode = ViewMode::OperatorGo(Operator::Yank),
                     Key::Char('G') => yank!(self, beginning_of_file),
                     _ => {}
                 }
-                self.view_mode = ViewMode::Normal;
             }
             ViewMode::Delete => {
+                self.view_mode = ViewMode::Normal;
                 match key {
-                    Key::Char('l') => delete!(self, right, REPEAT),
+                    Key::Char('l') => delete!(self, right, REPEAT, n),
                     Key::Char('v') => delete!(self, selection, SELECTION),
-                    Key::Char('d') => delete!(self, line, REPEAT),
-                    Key::Char('h') => delete!(self, left, REPEAT),
-                    Key::Char('w') => delete!(self, next_word, REPEAT),
-                    Key::Char('b') => delete!(self, prev_word, REPEAT),
-                    Key::Char('W') => delete!(self, next_word_end, REPEAT),
-                    Key::Char('B') => delete!(self, prev_word_end, REPEAT),
-                    Key::Char('s') => delete!(self, next_whitespace, REPEAT),
-                    Key::Char('S') => delete!(self, prev_whitespace, REPEAT),
-                    Key::Char('}') => delete!(self, next_empty_line, REPEAT),
-                    Key::Char('{') => delete!(self, prev_empty_line, REPEAT),
-                    Key::Char('<') => delete!(self, beginning_of_line),
+                    Key::Char('d') => delete!(self, line, REPEAT, n),
+                    Key::Char('h') => delete!(self, left, REPEAT, n),
+                    Key::Char('w') => delete!(self, next_word, REPEAT, n),
+                    Key::Char('b') => delete!(self, prev_word, REPEAT, n),
+                    Key::Char('W') => delete!(self, next_word_end, REPEAT, n),
+                    Key::Char('B') => delete!(self, prev_word_end, REPEAT, n),
+                    Key::Char('s') => delete!(self, next_whitespace, REPEAT, n),
+                    Key::Char('S') => delete!(self, prev_whitespace, REPEAT, n),
+                    Key::Char('}') => delete!(self, next_empty_line, REPEAT, n),
+                    Key::Char('{') => delete!(self, prev_empty_line, REPEAT, n),
+                    Key::Char('<' | '0') => delete!(self, beginning_of_line),
                     Key::Char('>') => delete!(self, end_of_line),
-                    Key::Char('.') => delete!(self, matching_opposite),
-                    Key::Char('g') => delete!(self, end_of_file),
+                    Key::Char('%') => delete!(self, matching_opposite),
+                    Key::Char('g') => self.view_mode = ViewMode::OperatorGo(Operator::Delete),
                     Key::Char('G') => delete!(self, beginning_of_file),
                     _ => {}
                 }
-                self.view_mode = ViewMode::Normal;
             }
             ViewMode::Change => {
+                self.view_mode = ViewMode::Normal;
                 match key {
                     Key::Char('v') => {
                         delete::selection(
@@ -322,24 +746,23 @@ impl TextBuffer {
                         delete::end_of_line(&mut self.base.doc, Some(&mut self.history));
                         self.change_mode(Mode::Insert);
                     }
-                    Key::Char('h') => change!(self, left, REPEAT),
-                    Key::Char('l') => change!(self, right, REPEAT),
-                    Key::Char('w') => change!(self, next_word, REPEAT),
-                    Key::Char('b') => change!(self, prev_word, REPEAT),
-                    Key::Char('W') => change!(self, next_word_end, REPEAT),
-                    Key::Char('B') => change!(self, prev_word_end, REPEAT),
-                    Key::Char('s') => change!(self, next_whitespace, REPEAT),
-                    Key::Char('S') => change!(self, prev_whitespace, REPEAT),
-                    Key::Char('}') => change!(self, next_empty_line, REPEAT),
-                    Key::Char('{') => change!(self, prev_empty_line, REPEAT),
-                    Key::Char('<') => change!(self, beginning_of_line),
+                    Key::Char('h') => change!(self, left, REPEAT, n),
+                    Key::Char('l') => change!(self, right, REPEAT, n),
+                    Key::Char('w') => change!(self, next_word, REPEAT, n),
+                    Key::Char('b') => change!(self, prev_word, REPEAT, n),
+                    Key::Char('W') => change!(self, next_word_end, REPEAT, n),
+                    Key::Char('B') => change!(self, prev_word_end, REPEAT, n),
+                    Key::Char('s') => change!(self, next_whitespace, REPEAT, n),
+                    Key::Char('S') => change!(self, prev_whitespace, REPEAT, n),
+                    Key::Char('}') => change!(self, next_empty_line, REPEAT, n),
+                    Key::Char('{') => change!(self, prev_empty_line, REPEAT, n),
+                    Key::Char('<' | '0') => change!(self, beginning_of_line),
                     Key::Char('>') => change!(self, end_of_line),
-                    Key::Char('.') => change!(self, matching_opposite),
-                    Key::Char('g') => change!(self, end_of_file),
+                    Key::Char('%') => change!(self, matching_opposite),
+                    Key::Char('g') => self.view_mode = ViewMode::OperatorGo(Operator::Change),
                     Key::Char('G') => change!(self, beginning_of_file),
                     _ => {}
                 }
-                self.view_mode = ViewMode::Normal;
             }
             ViewMode::Replace => {
                 if let Key::Char(ch) = key {
@@ -347,28 +770,344 @@ impl TextBuffer {
                 }
                 self.view_mode = ViewMode::Normal;
             }
+            ViewMode::Repeat => {
+                self.view_mode = ViewMode::Normal;
+                if key == Key::Char(':') {
+                    return self.repeat_command(n);
+                }
+            }
+            ViewMode::Find(operator, find) => {
+                self.view_mode = ViewMode::Normal;
+                let Key::Char(ch) = key else {
+                    return BufferResult::Ok;
+                };
+                self.last_find = Some((find, ch));
+
+                match operator {
+                    Operator::Move => {
+                        if find.apply(&mut self.base.doc, ch, n) {
+                            self.base.update_selection();
+                        }
+                    }
+                    Operator::Yank => {
+                        if let Err(err) = yank::find_char(
+                            &mut self.base.doc,
+                            self.base.clipboard.borrow_mut().as_mut(),
+                            find,
+                            ch,
+                            n,
+                        ) {
+                            return err;
+                        }
+                    }
+                    Operator::Delete => {
+                        if delete::find_char(
+                            &mut self.base.doc,
+                            Some(&mut self.history),
+                            find,
+                            ch,
+                            n,
+                        ) {
+                            // Deletions might cause matches and selections to become invalid.
+                            self.base.clear_matches();
+                            self.base.clear_selections();
+                        }
+                    }
+                    Operator::Change => {
+                        if delete::find_char(
+                            &mut self.base.doc,
+                            Some(&mut self.history),
+                            find,
+                            ch,
+                            n,
+                        ) {
+                            self.change_mode(Mode::Insert);
+                        }
+                    }
+                }
+            }
+            ViewMode::OperatorGo(operator) => {
+                self.view_mode = ViewMode::Normal;
+                match key {
+                    Key::Char('n') => {
+                        if self.base.select_next_match_at_cursor() {
+                            return self.apply_to_selection(operator);
+                        }
+                    }
+                    // Escape aborts the operator.
+                    Key::Esc => {}
+                    Key::Char('g') => return self.apply_to_end_of_file(operator),
+                    // Any other key completes the operator to the end of the file and is handled on its own.
+                    _ => {
+                        let res = self.apply_to_end_of_file(operator);
+                        if !matches!(res, BufferResult::Ok) {
+                            return res;
+                        }
+                        return self.view_command(key, n);
+                    }
+                }
+            }
+            ViewMode::Go => {
+                self.view_mode = ViewMode::Normal;
+                match key {
+                    Key::Char('q') => return self.reflow_command(),
+                    Key::Char('a') => return self.ascii_command(),
+                    Key::Char('j') => return self.join_command(true, n),
+                    Key::Char('J') => return self.join_command(false, n),
+                    Key::Char('n') => {
+                        self.base.select_next_match_at_cursor();
+                    }
+                    Key::Char('v') => self.base.reselect(),
+                    Key::Char('-') => return self.earlier_command(""),
+                    Key::Char('+') => return self.later_command(""),
+                    Key::Char('g') => jump!(self, jump_to_end_of_file),
+                    // Any other key completes the jump to the end of the file and is handled on its own.
+                    _ => {
+                        jump!(self, jump_to_end_of_file);
+                        return self.view_command(key, n);
+                    }
+                }
+            }
         }
 
         BufferResult::Ok
     }
 
+    /// Indents or dedents the selected lines or the line of the cursor by `n` levels. A level is what a typed tab
+    /// indents by.
+    fn indent(&mut self, dedent: bool, n: usize) {
+        let ranges = if self.base.selections.is_empty() {
+            vec![(self.base.doc.cur.y, self.base.doc.cur.y)]
+        } else {
+            self.selected_lines()
+        };
+
+        let options = &self.base.options;
+        let changed = if dedent {
+            let width = if options.expandtab {
+                options.shiftwidth
+            } else {
+                TAB_WIDTH
+            };
+            edit::dedent_lines(
+                &mut self.base.doc,
+                Some(&mut self.history),
+                &ranges,
+                width,
+                n,
+            )
+        } else {
+            let unit = if options.expandtab {
+                " ".repeat(options.shiftwidth)
+            } else {
+                "\t".to_string()
+            };
+            edit::indent_lines(
+                &mut self.base.doc,
+                Some(&mut self.history),
+                &ranges,
+                &unit.repeat(n),
+            )
+        };
+        if !changed {
+            return;
+        }
+
+        // Place the cursor at the beginning of the text of the first indented line.
+        self.base.doc.cur = Cursor::new(0, 0);
+        cursor::move_to(&mut self.base.doc, Cursor::new(0, ranges[0].0));
+        cursor::jump_to_first_non_blank(&mut self.base.doc);
+        self.base.clear_matches();
+        self.base.clear_selections();
+    }
+
+    /// Applies an operator to the selections, like to the match selected by 'gn'.
+    fn apply_to_selection(&mut self, operator: Operator) -> BufferResult {
+        match operator {
+            Operator::Move => {}
+            Operator::Yank => yank!(self, selection, SELECTION),
+            Operator::Delete => delete!(self, selection, SELECTION),
+            Operator::Change => {
+                delete::selection(
+                    &mut self.base.doc,
+                    &mut self.base.selections,
+                    Some(&mut self.history),
+                );
+                self.change_mode(Mode::Insert);
+            }
+        }
+
+        BufferResult::Ok
+    }
+
+    /// Applies an operator from the cursor to the end of the file.
+    fn apply_to_end_of_file(&mut self, operator: Operator) -> BufferResult {
+        match operator {
+            Operator::Move => jump!(self, jump_to_end_of_file),
+            Operator::Yank => yank!(self, end_of_file),
+            Operator::Delete => delete!(self, end_of_file),
+            Operator::Change => change!(self, end_of_file),
+        }
+
+        BufferResult::Ok
+    }
+
+    /// Repeats the last find-char motion `n` times, in the opposite direction if `reverse` is set.
+    fn repeat_find(&mut self, reverse: bool, n: usize) {
+        let Some((find, ch)) = self.last_find else {
+            return;
+        };
+
+        let find = if reverse { find.reversed() } else { find };
+        if find.repeat(&mut self.base.doc, ch, n) {
+            self.base.update_selection();
+        }
+    }
+
+    /// Repeats the last edit `n` times at the cursor by replaying its keys.
+    fn repeat_edit(&mut self, n: usize) -> BufferResult {
+        // Repeating from insert mode would type the keys of the edit instead.
+        if !matches!(self.mode, Mode::View) {
+            return BufferResult::Ok;
+        }
+
+        // The count of '.' must not prefix the count of the replayed edit.
+        self.count.clear();
+        let keys = self.last_edit.clone();
+        for _ in 0..n {
+            for &key in &keys {
+                let res = self.mode_tick(Some(key));
+                if !matches!(res, BufferResult::Ok) {
+                    return res;
+                }
+            }
+        }
+
+        BufferResult::Ok
+    }
+
+    /// Records the key of a command. Once the command completed, its keys are kept as the last edit if it is an edit
+    /// that changed the document. Commands entering insert mode complete when leaving it.
+    fn record_edit_key(&mut self, key: Key) {
+        match self.mode {
+            Mode::Command => {
+                self.edit_keys.clear();
+                return;
+            }
+            Mode::Insert => {
+                self.edit_keys.push(key);
+                return;
+            }
+            Mode::View => self.edit_keys.push(key),
+        }
+
+        if !matches!(self.view_mode, ViewMode::Normal) || !self.count.is_empty() {
+            return;
+        }
+
+        let keys = std::mem::take(&mut self.edit_keys);
+        let mut command = keys
+            .iter()
+            .skip_while(|key| matches!(key, Key::Char('0'..='9')));
+        let edits = match command.next() {
+            Some(
+                Key::Char(
+                    'x' | 'd' | 'c' | 'r' | 'p' | 'P' | 'i' | 'a' | 'A' | 'o' | 'O' | '\t' | '~',
+                )
+                | Key::BackTab,
+            ) => true,
+            Some(Key::Char('g')) => matches!(command.next(), Some(Key::Char('q' | 'j' | 'J'))),
+            _ => false,
+        };
+        if edits && self.history.undo_time() != self.edit_undo_time {
+            self.last_edit = keys;
+        }
+    }
+
+    /// Handles a tick of the current mode.
+    fn mode_tick(&mut self, key: Option<Key>) -> BufferResult {
+        match self.mode {
+            Mode::View => self.view_tick(key),
+            Mode::Command => self.command_tick(key),
+            Mode::Insert if self.insert_pending == InsertPending::ViewCommand && key.is_some() => {
+                let res = self.view_tick(key);
+                // Multi-key commands keep running as view mode commands until they completed.
+                if matches!(self.view_mode, ViewMode::Normal) {
+                    self.insert_pending = InsertPending::None;
+                }
+                res
+            }
+            Mode::Insert => self.write_tick(key),
+        }
+    }
+
     /// Handles write mode ticks.
     fn write_tick(&mut self, key: Option<Key>) -> BufferResult {
         let Some(key) = key else {
             return BufferResult::Ok;
         };
 
+        if self.insert_pending == InsertPending::Literal {
+            self.insert_pending = InsertPending::None;
+            if let Some(ch) = literal_char(key) {
+                edit::write_char(&mut self.base.doc, Some(&mut self.history), ch);
+            }
+
+            return BufferResult::Ok;
+        }
+
         match key {
             Key::Esc => self.change_mode(Mode::View),
+            Key::Ctrl('v') => self.insert_pending = InsertPending::Literal,
+            Key::Ctrl('o') => self.insert_pending = InsertPending::ViewCommand,
             Key::Left => cursor::left(&mut self.base.doc, 1),
             Key::Down => cursor::down(&mut self.base.doc, 1),
             Key::Up => cursor::up(&mut self.base.doc, 1),
             Key::Right => cursor::right(&mut self.base.doc, 1),
             Key::AltRight => cursor::next_word(&mut self.base.doc, 1),
             Key::AltLeft => cursor::prev_word(&mut self.base.doc, 1),
-            Key::Char('\t') => edit::write_tab(&mut self.base.doc, Some(&mut self.history), true),
+            Key::CtrlRight => cursor::next_subword(&mut self.base.doc, 1),
+            Key::CtrlLeft => cursor::prev_subword(&mut self.base.doc, 1),
+            Key::Home => cursor::jump_to_beginning_of_line(&mut self.base.doc),
+            Key::End => cursor::jump_to_end_of_line(&mut self.base.doc),
+            Key::PageDown => {
+                let h = self.base.doc_view.h;
+                self.base.doc_view.page_down(&mut self.base.doc, h);
+            }
+            Key::PageUp => {
+                let h = self.base.doc_view.h;
+                self.base.doc_view.page_up(&mut self.base.doc, h);
+            }
+            Key::Char('\t') if self.base.options.expandtab => {
+                edit::write_tab(
+                    &mut self.base.doc,
+                    Some(&mut self.history),
+                    self.base.options.shiftwidth,
+                    true,
+                );
+            }
+            Key::BackTab => {
+                // A level is what a typed tab indents by.
+                let width = if self.base.options.expandtab {
+                    self.base.options.shiftwidth
+                } else {
+                    TAB_WIDTH
+                };
+                edit::dedent(&mut self.base.doc, Some(&mut self.history), width);
+            }
             Key::Backspace => edit::delete_char(&mut self.base.doc, Some(&mut self.history)),
-            Key::Char(ch) => edit::write_char(&mut self.base.doc, Some(&mut self.history), ch),
+            Key::Char(ch) => {
+                edit::write_char(&mut self.base.doc, Some(&mut self.history), ch);
+
+                // Like in Vim, only typing text past the text width breaks the line, not typing whitespace.
+                if self.base.options.textwidth > 0 && !ch.is_whitespace() {
+                    edit::wrap_line(
+                        &mut self.base.doc,
+                        Some(&mut self.history),
+                        self.base.options.textwidth,
+                    );
+                }
+            }
             _ => {}
         }
 
@@ -393,16 +1132,13 @@ impl TextBuffer {
                 // Commands have only one line.
                 let cmd = self.base.cmd.line(0).unwrap().to_string();
                 if !cmd.is_empty() {
-                    self.base.cmd_history.push(cmd.clone());
+                    self.base.push_command_history(&cmd);
                 }
                 self.change_mode(Mode::View);
 
-                match self.base.apply_command(cmd) {
-                    Ok(res) => return res,
-                    Err(cmd) => return self.apply_command(&cmd),
-                }
+                return self.run_command(cmd);
             }
-            Key::Char('\t') => edit::write_tab(&mut self.base.cmd, None, false),
+            Key::Char('\t') => edit::write_tab(&mut self.base.cmd, None, TAB_WIDTH, false),
             Key::Backspace => edit::delete_char(&mut self.base.cmd, None),
             Key::Char(ch) => edit::write_char(&mut self.base.cmd, None, ch),
             _ => {}
@@ -411,6 +1147,137 @@ impl TextBuffer {
         BufferResult::Ok
     }
 
+    /// Runs a command of the base buffer or of the text buffer.
+    fn run_command(&mut self, cmd: String) -> BufferResult {
+        let res = match self.base.apply_command(cmd) {
+            Ok(res) => res,
+            Err(cmd) => self.apply_command(&cmd),
+        };
+
+        // The buffer is closed if it has no unsaved changes or they are discarded.
+        match res {
+            BufferResult::Quit if !self.base.doc.edited => self.remove_swap(),
+            BufferResult::ForceQuit => self.remove_swap(),
+            _ => {}
+        }
+
+        res
+    }
+
+    /// Runs the last command entered in this session `n` times again. Commands loaded from the history of earlier
+    /// sessions are not repeated.
+    fn repeat_command(&mut self, n: usize) -> BufferResult {
+        let Some(cmd) = self.base.last_command.clone() else {
+            return BufferResult::Info("No previous command".to_string());
+        };
+
+        let mut res = BufferResult::Ok;
+        for _ in 0..n {
+            res = self.run_command(cmd.clone());
+
+            // Stop at the first error or once the command leaves this buffer.
+            if !matches!(res, BufferResult::Ok | BufferResult::Info(_)) {
+                break;
+            }
+        }
+
+        res
+    }
+
+    /// Aborts a pending multi-key input if it was not completed in time.
+    fn pending_timeout(&mut self) {
+        if (matches!(self.view_mode, ViewMode::Normal) && self.count.is_empty())
+            || !self.base.pending_timed_out(self.last_input)
+        {
+            return;
+        }
+
+        // A single 'g' jumps to the end of the file once no command follows.
+        if matches!(self.view_mode, ViewMode::Go) {
+            cursor::jump_to_end_of_file(&mut self.base.doc);
+            self.base.update_selection();
+        }
+
+        self.view_mode = ViewMode::Normal;
+        self.insert_pending = InsertPending::None;
+        self.count.clear();
+        self.pending_count = 1;
+        self.base.rerender = true;
+    }
+
+    /// Writes the modifications to the swap file after being idle or after enough keys were pressed.
+    fn write_swap(&mut self) -> Option<BufferResult> {
+        let keys = self.swap_keys?;
+        let path = self.path.as_ref()?;
+        if self.read_only || !self.base.doc.edited {
+            self.swap_keys = None;
+            return None;
+        }
+        if keys < SWAP_KEYS && self.last_input.elapsed() < Duration::from_secs(SWAP_IDLE) {
+            return None;
+        }
+
+        // Don't retry writing until the next input.
+        let res = swap::write(path, &self.base.doc);
+        self.swap_keys = None;
+
+        res.err()
+            .map(|err| BufferResult::Error(format!("Failed to write swap file:\n{err}")))
+    }
+
+    /// Removes the swap file of the buffer's file.
+    fn remove_swap(&mut self) {
+        if let Some(path) = &self.path {
+            swap::remove(path);
+        }
+        self.swap_keys = None;
+    }
+
+    /// Shows a message offering to recover the swap file if it is newer than the file.
+    fn check_swap(&mut self) {
+        if let Some(path) = &self.path
+            && swap::is_newer(path)
+        {
+            self.base.set_message(
+                MessageKind::Info,
+                format!(
+                    "Found a swap file of '{}' that is newer than the file. Use 'recover' to restore its contents or \
+                    'norecover' to delete it",
+                    path.display()
+                ),
+            );
+        }
+    }
+
+    /// Writes the buffer to its file if it was modified and no input was received for the autosave duration.
+    fn autosave(&mut self) -> Option<BufferResult> {
+        let autosave = self.base.options.autosave;
+        if autosave == 0
+            || self.read_only
+            || self.autosave_failed
+            || !self.base.doc.edited
+            || self.last_input.elapsed() < Duration::from_secs(autosave)
+        {
+            return None;
+        }
+
+        // Formatting while the user pauses typing would move the text under their cursor.
+        match self.write_to_file(false) {
+            // Scratchpads without a file are never autosaved.
+            Ok(false) => None,
+            Ok(true) => {
+                self.autosaved = true;
+                self.base.rerender = true;
+                None
+            }
+            Err(err) => {
+                // Don't retry writing until the next input.
+                self.autosave_failed = true;
+                Some(BufferResult::Error(format!("Failed to autosave:\n{err}")))
+            }
+        }
+    }
+
     fn shell_tick(&mut self, key: Option<Key>) -> BufferResult {
         let shell_command = &mut *self.shell_command.as_mut().unwrap();
 
@@ -420,31 +1287,20 @@ impl TextBuffer {
                 Ok(res) => match res {
                     ShellCommandResult::Data(data) => {
                         self.base.rerender = true;
-                        shell_command.parser.process(&data);
+                        shell_command.process(&data);
                     }
                     ShellCommandResult::Error(err) => {
-                        self.base.rerender = true;
-                        self.base.doc.append_str(shell_command.contents().as_str());
-                        jump!(self, jump_to_end_of_file);
-
-                        self.shell_command = None;
-                        return BufferResult::Error(err);
+                        return self.finish_shell_command(BufferResult::Error(err), None);
                     }
-                    ShellCommandResult::Eof => {
-                        self.base.rerender = true;
-                        self.base.doc.append_str(shell_command.contents().as_str());
-                        jump!(self, jump_to_end_of_file);
-
+                    ShellCommandResult::Eof(status) => {
                         let res = BufferResult::Info(format!("'{}' finished", shell_command.cmd));
-                        self.shell_command = None;
-                        return res;
+                        return self.finish_shell_command(res, Some(status));
                     }
                 },
                 // Ignore empty error since we're waiting on data.
                 Err(TryRecvError::Empty) => break,
                 Err(err) => {
-                    self.shell_command = None;
-                    return BufferResult::Error(err.to_string());
+                    return self.finish_shell_command(BufferResult::Error(err.to_string()), None);
                 }
             }
         }
@@ -453,25 +1309,83 @@ impl TextBuffer {
         if let Some(key) = key {
             // Always quit command on 'ctrl+q'.
             if Key::Ctrl('q') == key {
-                self.base.rerender = true;
-                self.base.doc.append_str(shell_command.contents().as_str());
-                jump!(self, jump_to_end_of_file);
-
                 let res = BufferResult::Info(format!("Quit '{}'", shell_command.cmd));
-                self.shell_command = None;
-                return res;
-            } else if let Err(err) = shell_command.write(key) {
-                self.base.rerender = true;
-                self.base.doc.append_str(shell_command.contents().as_str());
-                jump!(self, jump_to_end_of_file);
+                return self.finish_shell_command(res, None);
+            }
 
-                self.shell_command = None;
-                return BufferResult::Error(err.to_string());
+            match key {
+                Key::PageUp => shell_command.scroll_up(self.base.doc_view.h),
+                Key::PageDown => shell_command.scroll_down(self.base.doc_view.h),
+                _ => {
+                    // Return to the live output on input.
+                    shell_command.scroll_down(usize::MAX);
+                    if let Err(err) = shell_command.write(key) {
+                        return self
+                            .finish_shell_command(BufferResult::Error(err.to_string()), None);
+                    }
+                }
             }
+            self.base.rerender = true;
         }
 
         BufferResult::Ok
     }
+
+    /// Handles the output of the finished shell command. The exit status is only available if the command exited.
+    fn finish_shell_command(
+        &mut self,
+        res: BufferResult,
+        status: Option<ExitStatus>,
+    ) -> BufferResult {
+        let mut shell_command = self.shell_command.take().unwrap();
+        self.base.rerender = true;
+
+        if let Some(tmp_path) = self.shell_tmp.take() {
+            let _ = std::fs::remove_file(tmp_path);
+        }
+
+        match std::mem::replace(&mut self.shell_output, ShellOutput::Append) {
+            ShellOutput::Append => {
+                self.base.doc.append_str(shell_command.contents().as_str());
+                jump!(self, jump_to_end_of_file);
+
+                res
+            }
+            ShellOutput::Scratch => {
+                let buff = Self::from_contents(
+                    self.base.w,
+                    self.base.h,
+                    self.base.x_off,
+                    self.base.y_off,
+                    shell_command.contents().as_str(),
+                    self.base.clipboard.clone(),
+                    self.base.cmd_history.clone(),
+                );
+
+                match buff {
+                    Ok(mut buff) => {
+                        // Show the result in the new buffer since it replaces this one on screen.
+                        match res {
+                            BufferResult::Info(text) => buff.set_message(MessageKind::Info, text),
+                            BufferResult::Error(text) => buff.set_message(MessageKind::Error, text),
+                            _ => {}
+                        }
+
+                        BufferResult::Open(Box::new(buff))
+                    }
+                    Err(err) => BufferResult::Error(err.to_string()),
+                }
+            }
+            ShellOutput::SudoWrite => match status {
+                Some(status) if status.success() => self.sudo_tee(),
+                Some(status) => BufferResult::Error(format!(
+                    "Failed to authenticate using sudo (exit code {})",
+                    status.exit_code()
+                )),
+                None => res,
+            },
+        }
+    }
 }
 
 impl Buffer for TextBuffer {
@@ -491,40 +1405,46 @@ impl Buffer for TextBuffer {
 
     fn render(&mut self, display: &mut Display) {
         self.base.rerender = false;
+        display.set_truecolor(self.base.options.termguicolors);
 
         let (cursor_style, cmd) = match self.mode {
-            Mode::View => (CursorStyle::SteadyBlock, false),
-            Mode::Command => (CursorStyle::SteadyBar, true),
-            Mode::Insert => (CursorStyle::SteadyBar, false),
+            Mode::View => (self.base.options.guicursor.view, false),
+            Mode::Command => (self.base.options.guicursor.command, true),
+            Mode::Insert => (self.base.options.guicursor.insert, false),
         };
 
-        self.base.doc_view.recalculate_viewport(&self.base.doc);
+        self.base
+            .doc_view
+            .recalculate_viewport(&self.base.doc, self.base.options.scrolloff);
         if let Some(shell_command) = &self.shell_command {
             self.base
                 .doc_view
                 .render_terminal(display, &shell_command.parser);
         } else {
-            self.base.doc_view.render_gutter(display, &self.base.doc);
             self.base
                 .doc_view
-                .render_document(display, &self.base.doc, &self.base.selections);
+                .render_gutter(display, &self.base.doc, &self.base.options);
+            self.base.update_highlights(false);
+            self.base.doc_view.render_document(
+                display,
+                &mut self.base.doc,
+                &self.base.selections,
+                &self.base.highlights,
+                &self.base.options,
+            );
         }
 
         if cmd {
-            self.base.cmd_view.recalculate_viewport(&self.base.cmd);
-
-            self.base.cmd_view.render_bar(
-                self.base.cmd.line(0).unwrap().to_string().trim_end(),
-                0,
-                display,
-            );
+            self.base.render_command(display);
         } else {
-            self.base.info_view.recalculate_viewport(&self.info);
+            self.base.info_view.recalculate_viewport(&self.info, 0);
             self.info_line();
 
-            self.base.info_view.render_bar(
+            self.base.info_view.render_split_bar(
                 self.info.line(0).unwrap().to_string().trim_end(),
+                &self.pending_keys(),
                 0,
+                self.base.bell(),
                 display,
             );
         }
@@ -539,12 +1459,15 @@ impl Buffer for TextBuffer {
 
         // The shell handles it's own cursor.
         if self.shell_command.is_none() {
-            let (view, doc) = if cmd {
-                (&self.base.cmd_view, &self.base.cmd)
+            if cmd {
+                self.base
+                    .cmd_view
+                    .render_wrapped_cursor(display, &self.base.cmd, cursor_style);
             } else {
-                (&self.base.doc_view, &self.base.doc)
-            };
-            view.render_cursor(display, doc, cursor_style);
+                self.base
+                    .doc_view
+                    .render_cursor(display, &self.base.doc, cursor_style);
+            }
         }
     }
 
@@ -564,6 +1487,21 @@ impl Buffer for TextBuffer {
         // Only rerender if input was received.
         self.base.rerender |= key.is_some();
 
+        if key.is_some() {
+            self.last_input = Instant::now();
+            self.autosave_failed = false;
+            self.swap_keys = Some(self.swap_keys.map_or(1, |keys| keys + 1));
+        } else {
+            self.pending_timeout();
+            self.base.bell_tick();
+            if let Some(res) = self.autosave() {
+                return res;
+            }
+            if let Some(res) = self.write_swap() {
+                return res;
+            }
+        }
+
         // Intercept inputs if a message is shown.
         if let Some(message) = &mut self.base.message
             && let Some(key) = key
@@ -583,8 +1521,13 @@ impl Buffer for TextBuffer {
                     return BufferResult::Ok;
                 }
                 Key::Char('Y') => {
-                    if let Err(err) = self.base.clipboard.set_text(message.text.clone()) {
-                        return BufferResult::Error(err.to_string());
+                    if let Err(err) = self
+                        .base
+                        .clipboard
+                        .borrow_mut()
+                        .set_text(message.text.clone())
+                    {
+                        return BufferResult::Error(err);
                     }
 
                     return BufferResult::Info("Message yanked to clipboard".to_string());
@@ -594,11 +1537,17 @@ impl Buffer for TextBuffer {
             }
         }
 
-        match self.mode {
-            Mode::View => self.view_tick(key),
-            Mode::Command => self.command_tick(key),
-            Mode::Insert => self.write_tick(key),
+        let Some(key) = key else {
+            return self.mode_tick(None);
+        };
+
+        if self.edit_keys.is_empty() {
+            self.edit_undo_time = self.history.undo_time();
         }
+        let res = self.mode_tick(Some(key));
+        self.record_edit_key(key);
+
+        res
     }
 
     fn get_message(&self) -> Option<Message> {
@@ -616,4 +1565,256 @@ impl Buffer for TextBuffer {
 
         Err("There are unsaved changes in the text buffer".to_string())
     }
+
+    fn write(&mut self) -> Result<bool, String> {
+        if !self.base.doc.edited {
+            return Ok(false);
+        }
+
+        match self.write_to_file(true) {
+            Ok(true) => {
+                self.autosaved = false;
+                Ok(true)
+            }
+            Ok(false) => Err("The text buffer has no file to write to".to_string()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn close(&mut self) {
+        self.remove_swap();
+    }
+
+    #[cfg(test)]
+    fn doc(&self) -> &Document {
+        &self.base.doc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clipboard::{self, MemoryClipboard},
+        command_history::{self, CommandHistory},
+    };
+
+    /// Creates a scratchpad buffer with the contents using an in-memory clipboard and command history.
+    fn buffer(contents: &str) -> TextBuffer {
+        let clipboard = clipboard::shared(Box::new(MemoryClipboard::default()));
+        let cmd_history = command_history::shared(CommandHistory::default());
+        TextBuffer::from_contents(80, 20, 0, 0, contents, clipboard, cmd_history).unwrap()
+    }
+
+    /// Feeds the keys of a text to the buffer, one tick per key.
+    fn keys(buff: &mut TextBuffer, keys: &str) {
+        for ch in keys.chars() {
+            buff.tick(Some(Key::Char(ch)));
+        }
+    }
+
+    /// Returns the lines of the document without their line breaks.
+    fn lines(buff: &TextBuffer) -> Vec<String> {
+        buff.base
+            .doc
+            .lines()
+            .map(|line| line.to_string().trim_end_matches('\n').to_string())
+            .collect()
+    }
+
+    #[test]
+    fn clipboard_is_shared_between_buffers() {
+        let mut first = buffer("first\nsecond");
+        let clipboard = first.base.clipboard.clone();
+        let cmd_history = first.base.cmd_history.clone();
+        let mut second =
+            TextBuffer::from_contents(80, 20, 0, 0, "other", clipboard, cmd_history).unwrap();
+
+        keys(&mut first, "yy");
+        keys(&mut second, "p");
+
+        assert_eq!(lines(&second), ["first", "other"]);
+    }
+
+    #[test]
+    fn set_clipboard_switches_the_provider_of_all_buffers() {
+        let mut first = buffer("text");
+        let clipboard = first.base.clipboard.clone();
+        clipboard
+            .borrow_mut()
+            .set_text("before".to_string())
+            .unwrap();
+
+        assert!(matches!(
+            first.base.apply_command("set clipboard=memory".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        assert_eq!(first.base.options.clipboard, "memory");
+        // The new provider starts empty.
+        assert_eq!(clipboard.borrow_mut().get_text().unwrap(), "");
+
+        assert!(matches!(
+            first
+                .base
+                .apply_command("set clipboard=primary".to_string()),
+            Ok(BufferResult::Error(_))
+        ));
+        assert_eq!(first.base.options.clipboard, "memory");
+    }
+
+    #[test]
+    fn set_iskeyword_changes_word_motions() {
+        let mut buff = buffer("kebab-case word");
+        keys(&mut buff, "w");
+        assert_eq!(buff.base.doc.cur.x, 5);
+
+        assert!(matches!(
+            buff.base.apply_command("set iskeyword=_-".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        buff.base.doc.cur = Cursor::new(0, 0);
+        keys(&mut buff, "w");
+        assert_eq!(buff.base.doc.cur.x, 11);
+    }
+
+    #[test]
+    fn write_and_open_paths_with_spaces() {
+        let dir = std::env::temp_dir().join(format!("mini-paths-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let quoted = dir.join("my \"quoted\" file.txt");
+        let spaced = dir.join("my file.txt");
+
+        let mut buff = buffer("text");
+        let result = buff.apply_command(&format!("w '{}'", quoted.display()));
+        assert!(!matches!(result, BufferResult::Error(_)));
+        let result = buff.apply_command(&format!(
+            "w {}",
+            spaced.display().to_string().replace(' ', "\\ ")
+        ));
+        assert!(!matches!(result, BufferResult::Error(_)));
+        assert_eq!(std::fs::read_to_string(&quoted).unwrap(), "text");
+        assert_eq!(std::fs::read_to_string(&spaced).unwrap(), "text");
+
+        std::fs::write(&spaced, "other\n").unwrap();
+        let result = buff.apply_command(&format!("o \"{}\"", spaced.display()));
+        assert!(!matches!(result, BufferResult::Error(_)));
+        assert_eq!(lines(&buff), ["other", ""]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn opened_paths_are_absolute() {
+        let mut buff = buffer("");
+        let result = buff.apply_command("o Cargo.toml");
+        assert!(!matches!(result, BufferResult::Error(_)));
+
+        // Changing the directory must not change the file of the buffer.
+        let path = std::env::current_dir().unwrap().join("Cargo.toml");
+        assert_eq!(buff.path, Some(path));
+    }
+
+    #[test]
+    fn repeat_command_skips_commands_of_earlier_sessions() {
+        let mut buff = buffer("one");
+        buff.base
+            .cmd_history
+            .borrow_mut()
+            .push("set scrolloff=7", 0);
+
+        buff.tick(Some(Key::Char('@')));
+        assert!(matches!(
+            buff.tick(Some(Key::Char(':'))),
+            BufferResult::Info(_)
+        ));
+        assert_ne!(buff.base.options.scrolloff, 7);
+    }
+
+    #[test]
+    fn set_values_containing_spaces() {
+        let mut buff = buffer("");
+        assert!(matches!(
+            buff.base
+                .apply_command("set commentstring='# %s' shiftwidth=2".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        assert_eq!(buff.base.options.commentstring, "# %s");
+        assert_eq!(buff.base.options.shiftwidth, 2);
+
+        assert!(matches!(
+            buff.base
+                .apply_command(r"set commentstring=--\ %s".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        assert_eq!(buff.base.options.commentstring, "-- %s");
+
+        // Shell commands are quoted like any other value and may be followed by more options.
+        assert!(matches!(
+            buff.base
+                .apply_command("set formatprg=\"rustfmt --edition 2024\" noexpandtab".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        assert_eq!(buff.base.options.formatprg, "rustfmt --edition 2024");
+        assert!(!buff.base.options.expandtab);
+    }
+
+    #[test]
+    fn set_changes_nothing_if_an_option_is_invalid() {
+        let mut buff = buffer("a-b");
+        assert!(matches!(
+            buff.base
+                .apply_command("set iskeyword=_- scrolloff=3 bogus".to_string()),
+            Ok(BufferResult::Error(_))
+        ));
+        assert_ne!(buff.base.options.iskeyword, "_-");
+        assert_ne!(buff.base.options.scrolloff, 3);
+
+        // The word characters of the document change together with the option.
+        assert!(matches!(
+            buff.base.apply_command("set iskeyword=_-".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        keys(&mut buff, "w");
+        assert_eq!(buff.base.doc.cur.x, 3);
+    }
+
+    #[test]
+    fn files_buffer_options_are_not_available() {
+        let mut buff = buffer("");
+        assert!(matches!(
+            buff.base.apply_command("set preview".to_string()),
+            Ok(BufferResult::Error(_))
+        ));
+        assert!(!buff.base.options.preview);
+        assert!(!buff.base.options.list().contains("preview"));
+    }
+
+    #[test]
+    fn options_set_before_opening_a_file_override_its_filetype() {
+        let dir = std::env::temp_dir().join(format!("mini-filetype-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.rs");
+        std::fs::write(&path, "fn main() {}\n").unwrap();
+
+        let mut buff = buffer("");
+        assert!(matches!(
+            buff.base.apply_command("set shiftwidth=2".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        assert!(matches!(
+            buff.base.apply_command("set textwidth=60".to_string()),
+            Ok(BufferResult::Ok)
+        ));
+        let result = buff.apply_command(&format!("o {}", path.display()));
+        assert!(!matches!(result, BufferResult::Error(_)));
+
+        let options = &buff.base.options;
+        assert_eq!(options.filetype, "rust");
+        assert_eq!(options.shiftwidth, 2);
+        assert_eq!(options.textwidth, 60);
+        // Options that were not set still get the defaults of the filetype.
+        assert_eq!(options.commentstring, "// %s");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,32 +1,153 @@
 use crate::{
-    buffer::BufferResult,
-    buffer_impls::text_buffer::TextBuffer,
+    buffer::{BufferResult, base::BaseBuffer, delete, edit},
+    buffer_impls::text_buffer::{ShellOutput, TextBuffer},
     cursor::{self, Cursor},
+    document::Document,
     history::Replace,
     selection::{Selection, SelectionKind},
-    shell_command::ShellCommand,
-    util::{file_name, open_file},
+    shell_command::{self, ShellCommand},
+    swap,
+    util::{
+        PathArg, absolute_path, char_info, file_name, match_case, open_file,
+        open_file_or_read_only, path_arg, shell_quote,
+    },
 };
 use regex::Regex;
-use std::io::{Error, Read};
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{Error, ErrorKind, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The amount of substitutions listed by the replace preview.
+const REPLACE_PREVIEW_LEN: usize = 5;
 
 impl TextBuffer {
-    fn write_to_file(&mut self) -> Result<bool, Error> {
-        let Some(file) = self.file.as_mut() else {
+    /// Writes the buffer to its file, formatting it first if `format` is set and enabled by the options. Returns if the
+    /// buffer has a file.
+    pub(super) fn write_to_file(&mut self, format: bool) -> Result<bool, Error> {
+        if self.file.is_none() {
             return Ok(false);
-        };
+        }
+        if self.read_only {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "The file was opened read-only, use 'w!!' to write it using sudo",
+            ));
+        }
+
+        self.prepare_write(format)?;
+        self.base.doc.write_to_file(self.file.as_mut().unwrap())?;
+        self.remove_swap();
 
-        self.base.doc.write_to_file(file)?;
         Ok(true)
     }
 
+    /// Formats the buffer if `format` is set and enabled by the options and applies the fixes of the options before
+    /// writing it. Does nothing if the buffer is unchanged.
+    fn prepare_write(&mut self, format: bool) -> Result<(), Error> {
+        if !self.base.doc.edited {
+            return Ok(());
+        }
+
+        let formatprg = &self.base.options.formatprg;
+        if format && self.base.options.formatonsave && !formatprg.is_empty() {
+            // Writing a file the formatter failed on is left to the user.
+            self.filter(&formatprg.clone()).map_err(|err| {
+                Error::other(format!(
+                    "{err}\nThe file was not written, use 'set noformatonsave' to write it unformatted"
+                ))
+            })?;
+        }
+
+        self.fix_on_write();
+        Ok(())
+    }
+
+    /// Trims trailing whitespace, converts line endings and adds a missing final line break as set by the options.
+    /// All fixes are recorded as one change.
+    fn fix_on_write(&mut self) {
+        let options = &self.base.options;
+        let ending = match options.fileformat.as_str() {
+            "crlf" => Some("\r\n"),
+            "lf" => Some("\n"),
+            _ => None,
+        };
+        let final_ending = ending.unwrap_or_else(|| {
+            if self.base.doc.line_ending() == "crlf" {
+                "\r\n"
+            } else {
+                "\n"
+            }
+        });
+
+        let mut changes = Vec::new();
+        for (y, line) in self.base.doc.lines().enumerate() {
+            let line = line.to_string();
+            let content = line.trim_end_matches(['\n', '\r']);
+            let old_ending = &line[content.len()..];
+
+            let kept = if options.trimwhitespace {
+                content.trim_end_matches([' ', '\t'])
+            } else {
+                content
+            };
+            let new_ending = match ending {
+                _ if old_ending.is_empty() && options.fixeol && !kept.is_empty() => final_ending,
+                Some(ending) if !old_ending.is_empty() => ending,
+                _ => old_ending,
+            };
+
+            if kept.len() != content.len() || new_ending != old_ending {
+                changes.push(Replace {
+                    pos: Cursor::new(kept.chars().count(), y),
+                    delete_data: line[kept.len()..].to_string(),
+                    insert_data: new_ending.to_string(),
+                });
+            }
+        }
+
+        if changes.is_empty() {
+            return;
+        }
+
+        // The fixes keep the amount of lines, so the positions of later changes stay valid.
+        let cur = self.base.doc.cur;
+        for c in &changes {
+            self.base.doc.replace_range(
+                c.pos,
+                cursor::pos_after_text(&c.pos, &c.delete_data),
+                &c.insert_data,
+            );
+        }
+        self.history.add_change(changes);
+
+        // The cursor might be in trimmed whitespace.
+        self.base.doc.cur = Cursor::new(0, 0);
+        cursor::move_to(&mut self.base.doc, cur);
+        self.base.clear_matches();
+        self.base.clear_selections();
+    }
+
     fn open_command(&mut self, args: &str, force: bool) -> BufferResult {
+        let PathArg { path, warning } = match path_arg(args) {
+            Ok(arg) => arg,
+            Err(res) => return res,
+        };
+        let args = path.as_str();
+
         if !force && self.base.doc.edited {
             return BufferResult::Error(
                 "There are unsaved changes, save or oo to force open a new document".to_string(),
             );
         }
 
+        // Changes of the previous file are discarded.
+        self.remove_swap();
+
         // Reset state.
         self.base.doc.from("");
         self.base.cmd.from("");
@@ -36,19 +157,24 @@ impl TextBuffer {
         self.base.clear_matches();
         self.base.clear_selections();
         self.file = None;
+        self.path = None;
         self.file_name = None;
+        self.read_only = false;
+        self.autosaved = false;
+        self.base.options.filetype.clear();
 
         // Open blank buffer if no path is specified.
         if args.is_empty() {
             return BufferResult::Ok;
         }
 
-        self.file = match open_file(args) {
-            Ok(file) => Some(file),
+        (self.file, self.read_only) = match open_file_or_read_only(args) {
+            Ok((file, read_only)) => (Some(file), read_only),
             Err(err) => {
                 return BufferResult::Error(err.to_string());
             }
         };
+        self.path = Some(absolute_path(args));
         self.file_name = file_name(args);
 
         let mut buff = String::new();
@@ -58,11 +184,22 @@ impl TextBuffer {
                 return BufferResult::Error(err.to_string());
             }
         }
+        self.load_file_options();
+        self.check_swap();
+        if self.base.message.is_some() {
+            return BufferResult::Ok;
+        }
 
-        BufferResult::Ok
+        BufferResult::Ok.with_warning(warning)
     }
 
     fn write_command(&mut self, args: &str) -> BufferResult {
+        let PathArg { path, warning } = match path_arg(args) {
+            Ok(arg) => arg,
+            Err(res) => return res,
+        };
+        let args = path.as_str();
+
         if !args.is_empty() {
             self.file = match open_file(args) {
                 Ok(file) => Some(file),
@@ -70,11 +207,23 @@ impl TextBuffer {
                     return BufferResult::Error(err.to_string());
                 }
             };
+            self.path = Some(absolute_path(args));
             self.file_name = file_name(args);
+            self.read_only = false;
+            // The new file gets the contents even if they are unchanged.
+            self.base.doc.edited = true;
+
+            // Writing a scratchpad gives it the options of its file.
+            if self.base.options.filetype.is_empty() {
+                self.load_file_options();
+            }
         }
 
-        let res = match self.write_to_file() {
-            Ok(res) => res,
+        let res = match self.write_to_file(true) {
+            Ok(res) => {
+                self.autosaved = false;
+                res
+            }
             Err(err) => {
                 return BufferResult::Error(err.to_string());
             }
@@ -90,73 +239,182 @@ impl TextBuffer {
             "File has been written to {}",
             self.file_name.as_ref().unwrap()
         ))
+        .with_warning(warning)
     }
 
-    fn replace_command(&mut self, args: &str) -> BufferResult {
-        let err =
-            BufferResult::Error("Invalid format. Expected: r /<regex>/<replace>/".to_string());
-        let Some(args) = args.strip_prefix('/') else {
-            return err;
+    fn recover_command(&mut self) -> BufferResult {
+        let Some(path) = self.path.clone() else {
+            return BufferResult::Error("Buffer has no file to recover".to_string());
         };
-        let Some((regex_str, replace_str)) = args.split_once('/') else {
-            return err;
-        };
-        let Some(replace_str) = replace_str.strip_suffix('/') else {
-            return err;
+        let (contents, pos) = match swap::read(&path) {
+            Ok(swap) => swap,
+            Err(err) => return BufferResult::Error(err.to_string()),
         };
-        if regex_str.is_empty() {
-            return err;
-        }
 
-        let regex = match Regex::new(regex_str) {
-            Ok(regex) => regex,
-            Err(err) => {
-                return BufferResult::Error(format!(
-                    "'{regex_str}' is not a valid regular expression:\n{err}"
+        // Replace the contents as one change so the recovery can be undone.
+        let delete_data = self.base.doc.lines().map(|line| line.to_string()).collect();
+        self.base.doc.from(&contents);
+        self.base.doc.edited = true;
+        cursor::move_to(&mut self.base.doc, pos);
+        self.history.add_change(vec![Replace {
+            pos: Cursor::new(0, 0),
+            delete_data,
+            insert_data: contents,
+        }]);
+
+        // The contents changed entirely.
+        self.base.clear_matches();
+        self.base.clear_selections();
+
+        BufferResult::Info(format!(
+            "Recovered '{}' from its swap file, write the file to keep the changes",
+            path.display()
+        ))
+    }
+
+    fn sudo_write_command(&mut self) -> BufferResult {
+        // Try writing normally first.
+        match self.write_to_file(true) {
+            Ok(true) => {
+                self.autosaved = false;
+                return BufferResult::Info(format!(
+                    "File has been written to {}",
+                    self.file_name.as_ref().unwrap()
                 ));
             }
+            Ok(false) => {
+                return BufferResult::Error(
+                    "Please specify a file location using 'w <path>' to write the file to"
+                        .to_string(),
+                );
+            }
+            Err(err) if err.kind() != ErrorKind::PermissionDenied => {
+                return BufferResult::Error(err.to_string());
+            }
+            Err(_) => {}
+        }
+
+        // Authenticate in the terminal first, the contents are piped to sudo once it finished.
+        let res = self.execute_shell_command("sudo -v");
+        if self.shell_command.is_some() {
+            self.shell_output = ShellOutput::SudoWrite;
+        }
+
+        res
+    }
+
+    /// Pipes the document into `sudo tee` to write it to its file, using the credentials cached by `sudo -v`.
+    pub(super) fn sudo_tee(&mut self) -> BufferResult {
+        if let Err(err) = self.prepare_write(true) {
+            return BufferResult::Error(err.to_string());
+        }
+
+        let path = self.path.as_ref().unwrap().to_string_lossy();
+        let cmd = format!("sudo -n tee {} > /dev/null", shell_quote(&path));
+        let contents = self
+            .base
+            .doc
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<String>();
+
+        if let Err(err) = shell_command::filter(&cmd, contents, false) {
+            return BufferResult::Error(format!("Failed to write the file using sudo:\n{err}"));
+        }
+
+        self.base.doc.edited = false;
+        self.base.doc.clear_changed();
+        self.remove_swap();
+        self.autosaved = false;
+        BufferResult::Info(format!(
+            "File has been written to {} using sudo",
+            self.file_name.as_ref().unwrap()
+        ))
+    }
+
+    fn replace_command(&mut self, args: &str) -> BufferResult {
+        let (regex, replace_str, preserve_case) = match parse_replace(args) {
+            Ok(replace) => replace,
+            Err(err) => return BufferResult::Error(err),
         };
 
         // Use selections or replace in entire buffer.
-        self.base.selections.sort_unstable();
-        let selections = if self.base.selections.is_empty() {
-            let start = Cursor::new(0, 0);
-            let end = {
-                let y = self.base.doc.len().saturating_sub(1);
-                let x = self.base.doc.line_count(y).unwrap_or(0);
-                Cursor::new(x, y)
+        let ranges = self.base.search_ranges();
+        self.substitute(&regex, replace_str, preserve_case, ranges)
+    }
+
+    /// Previews the substitutions of the replace command without applying them, listing the first ones and the total
+    /// count.
+    fn preview_replace_command(&mut self, args: &str) -> BufferResult {
+        let (regex, replace_str, preserve_case) = match parse_replace(args) {
+            Ok(replace) => replace,
+            Err(err) => return BufferResult::Error(err),
+        };
+
+        let mut previews = Vec::new();
+        let mut substitutions = 0;
+        for (start, end) in self.base.search_ranges() {
+            let Some(hay) = self.base.doc.get_range(start, end) else {
+                continue;
             };
+            let hay = hay.to_string();
 
-            &[Selection::new(
-                start,
-                end,
-                SelectionKind::Normal,
-                None,
-                None,
-            )]
+            for (mat, replacement) in replacements(&regex, replace_str, preserve_case, &hay) {
+                substitutions += 1;
+                if previews.len() < REPLACE_PREVIEW_LEN {
+                    let y = cursor::pos_after_text(&start, &hay[..mat.start()]).y;
+                    previews.push(format!("{}: {:?} -> {replacement:?}", y + 1, mat.as_str()));
+                }
+            }
+        }
+
+        if substitutions == 0 {
+            return BufferResult::Info("Pattern not found".to_string());
+        }
+
+        let label = if substitutions == 1 {
+            "substitution"
         } else {
-            &self.base.selections[..]
+            "substitutions"
         };
+        let more = substitutions - previews.len();
+        if more > 0 {
+            previews.push(format!("... and {more} more"));
+        }
 
-        let mut changes = Vec::new();
-        for selection in selections {
-            let (start, end) = selection.range();
+        BufferResult::Info(format!("{substitutions} {label}\n{}", previews.join("\n")))
+    }
 
-            let hay = self.base.doc.get_range(start, end).unwrap().to_string();
+    /// Replaces all matches of the regex inside the ranges.
+    fn substitute(
+        &mut self,
+        regex: &Regex,
+        replace_str: &str,
+        preserve_case: bool,
+        ranges: Vec<(Cursor, Cursor)>,
+    ) -> BufferResult {
+        let mut changes = Vec::new();
+        let mut lines = HashSet::new();
+        for (start, end) in ranges {
+            let Some(hay) = self.base.doc.get_range(start, end) else {
+                continue;
+            };
+            let hay = hay.to_string();
 
             let mut new = String::new();
             let mut last_match = 0;
-            for captures in regex.captures_iter(&hay) {
+            let count = changes.len();
+            for (mat, replacement) in replacements(regex, replace_str, preserve_case, &hay) {
                 // Fetch text between matches.
-                let mat = captures.get(0).unwrap();
                 new.push_str(&hay[last_match..mat.start()]);
 
+                // Count the line of the match in the original text.
+                lines.insert(cursor::pos_after_text(&start, &hay[..mat.start()]).y);
+
                 // Save pos of replacement in new string.
                 let pos = cursor::pos_after_text(&start, &new);
 
                 // Replace match.
-                let mut replacement = String::new();
-                captures.expand(replace_str, &mut replacement);
                 new.push_str(&replacement);
 
                 // Add replace operation to history.
@@ -172,21 +430,313 @@ impl TextBuffer {
             }
             new.push_str(&hay[last_match..]);
 
+            // Leave selections without matches untouched.
+            if changes.len() == count {
+                continue;
+            }
+
             // Replace buffer content.
-            self.base.doc.remove_range(start, end);
-            self.base.doc.write_str_at(start.x, start.y, &new);
+            self.base.doc.replace_range(start, end, &new);
+        }
+
+        if changes.is_empty() {
+            return BufferResult::Info("Pattern not found".to_string());
         }
 
         self.base.clear_matches();
         self.base.clear_selections();
 
-        if !changes.is_empty() {
-            self.history.add_change(changes);
+        let substitutions = changes.len();
+        let label = if substitutions == 1 {
+            "substitution"
+        } else {
+            "substitutions"
+        };
+        let lines = lines.len();
+        let lines_label = if lines == 1 { "line" } else { "lines" };
+        self.history.add_change(changes);
+
+        BufferResult::Info(format!("{substitutions} {label} on {lines} {lines_label}"))
+    }
+
+    fn global_command(&mut self, args: &str) -> BufferResult {
+        let err = BufferResult::Error(
+            "Invalid format. Expected: g /<regex>/ d | g /<regex>/ r /<regex>/<replace>/?c"
+                .to_string(),
+        );
+        let Some(args) = args.strip_prefix('/') else {
+            return err;
+        };
+        let Some((regex_str, cmd)) = args.split_once('/') else {
+            return err;
+        };
+        if regex_str.is_empty() {
+            return err;
+        }
+
+        let regex = match Regex::new(regex_str) {
+            Ok(regex) => regex,
+            Err(err) => {
+                return BufferResult::Error(format!(
+                    "'{regex_str}' is not a valid regular expression:\n{err}"
+                ));
+            }
+        };
+
+        // Collect the matching lines before editing so that edits don't shift them.
+        let lines = (0..self.base.doc.len())
+            .filter(|&y| {
+                let line = self.base.doc.line(y).unwrap().to_string();
+                regex.is_match(line.trim_end_matches(['\n', '\r']))
+            })
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return BufferResult::Info("Pattern not found".to_string());
         }
 
+        let (cmd, args) = match cmd.trim().split_once(char::is_whitespace) {
+            Some((cmd, args)) => (cmd, args.trim()),
+            None => (cmd.trim(), ""),
+        };
+
+        match cmd {
+            "d" if args.is_empty() => self.delete_lines(&lines),
+            "r" => {
+                let (regex, replace_str, preserve_case) = match parse_replace(args) {
+                    Ok(replace) => replace,
+                    Err(err) => return BufferResult::Error(err),
+                };
+
+                // Substitute bottom-up so that multiline replacements don't shift the following ranges.
+                let ranges = lines
+                    .iter()
+                    .rev()
+                    .map(|&y| {
+                        let len = line_len(&self.base.doc, y);
+                        (Cursor::new(0, y), Cursor::new(len, y))
+                    })
+                    .collect();
+                self.substitute(&regex, replace_str, preserve_case, ranges)
+            }
+            _ => err,
+        }
+    }
+
+    /// Gets the sorted line ranges `(first, last)` of the selections, merging overlapping and adjacent ranges so that
+    /// no line is part of two ranges.
+    pub(super) fn selected_lines(&self) -> Vec<(usize, usize)> {
+        let mut ranges = self
+            .base
+            .selections
+            .iter()
+            .map(|selection| {
+                let (start, end) = selection.range();
+                // A normal selection ending at the beginning of a line doesn't select any of it.
+                if selection.kind == SelectionKind::Normal && end.x == 0 && end.y > start.y {
+                    (start.y, end.y - 1)
+                } else {
+                    (start.y, end.y)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        ranges.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (first, last) in ranges {
+            match merged.last_mut() {
+                Some(prev) if first <= prev.1 + 1 => prev.1 = prev.1.max(last),
+                _ => merged.push((first, last)),
+            }
+        }
+
+        merged
+    }
+
+    /// Rewraps the selected lines or the paragraph of the cursor to the text width.
+    pub(super) fn reflow_command(&mut self) -> BufferResult {
+        let doc = &self.base.doc;
+        let blank = |y: usize| {
+            doc.line(y)
+                .is_some_and(|line| line.chars().all(char::is_whitespace))
+        };
+
+        let merged = if self.base.selections.is_empty() {
+            let y = doc.cur.y;
+            if blank(y) {
+                return BufferResult::Ok;
+            }
+
+            let first = (0..y).rev().find(|&y| blank(y)).map_or(0, |y| y + 1);
+            let last = (y + 1..doc.len())
+                .find(|&y| blank(y))
+                .map_or(doc.len() - 1, |y| y - 1);
+            vec![(first, last)]
+        } else {
+            self.selected_lines()
+        };
+
+        let changed = edit::reflow(
+            &mut self.base.doc,
+            Some(&mut self.history),
+            &merged,
+            self.base.options.reflow_width(),
+            &self.base.options.commentstring,
+        );
+        if !changed {
+            return BufferResult::Ok;
+        }
+
+        // Place the cursor at the beginning of the reflowed text.
+        self.base.doc.cur = Cursor::new(0, 0);
+        cursor::move_to(&mut self.base.doc, Cursor::new(0, merged[0].0));
+        self.base.clear_matches();
+        self.base.clear_selections();
+
         BufferResult::Ok
     }
 
+    /// Joins the selected lines or the line of the cursor with the next `n` lines. `trim` replaces the leading whitespace
+    /// of joined lines by a single space, otherwise the lines are concatenated as-is.
+    pub(super) fn join_command(&mut self, trim: bool, n: usize) -> BufferResult {
+        let doc = &self.base.doc;
+        // The empty line after a final line break can't be joined.
+        let max = if doc.len() > 1 && doc.line_count(doc.len() - 1) == Some(0) {
+            doc.len() - 2
+        } else {
+            doc.len() - 1
+        };
+
+        let mut ranges = if self.base.selections.is_empty() {
+            vec![(doc.cur.y, doc.cur.y.saturating_add(n))]
+        } else {
+            self.base
+                .selections
+                .iter()
+                .map(|selection| {
+                    let (start, end) = selection.range();
+                    // Selections within a line join it with the next line.
+                    (start.y, end.y.max(start.y + 1))
+                })
+                .collect()
+        };
+
+        // Merge overlapping ranges so that no line is joined twice.
+        ranges.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (first, last) in ranges {
+            let last = last.min(max);
+            match merged.last_mut() {
+                Some(prev) if first <= prev.1 => prev.1 = prev.1.max(last),
+                _ if first < last => merged.push((first, last)),
+                _ => {}
+            }
+        }
+
+        let Some(pos) = edit::join(&mut self.base.doc, Some(&mut self.history), &merged, trim)
+        else {
+            return BufferResult::Ok;
+        };
+
+        // Place the cursor at the last join of the first joined lines.
+        self.base.doc.cur = Cursor::new(0, 0);
+        cursor::move_to(&mut self.base.doc, pos);
+        self.base.clear_matches();
+        self.base.clear_selections();
+
+        BufferResult::Ok
+    }
+
+    /// Describes the character under the cursor.
+    pub(super) fn ascii_command(&self) -> BufferResult {
+        let doc = &self.base.doc;
+        let Some(ch) = doc
+            .line(doc.cur.y)
+            .and_then(|line| line.get_char(doc.cur.x))
+        else {
+            return BufferResult::Info("No character under the cursor".to_string());
+        };
+
+        BufferResult::Info(char_info(ch))
+    }
+
+    /// Highlights the indentation of lines mixing tabs and spaces and jumps to the next one. The lines are navigable
+    /// like other highlighted matches.
+    fn mixed_command(&mut self) -> BufferResult {
+        let regex = Regex::new(r"(?m)^[ \t]*(?: \t|\t )[ \t]*").unwrap();
+        self.base.set_highlight(Some(regex));
+
+        let count = self.base.highlights.len();
+        if count == 0 {
+            return BufferResult::Info("No lines with mixed indentation".to_string());
+        }
+
+        self.base.next_match();
+        let label = if count == 1 { "line" } else { "lines" };
+        BufferResult::Info(format!("{count} {label} with mixed indentation"))
+    }
+
+    /// Highlights the whitespace at the end of lines and jumps to the next line with it. The lines are navigable like
+    /// other highlighted matches.
+    fn trailing_command(&mut self) -> BufferResult {
+        let regex = Regex::new(r"(?mR)[ \t]+$").unwrap();
+        self.base.set_highlight(Some(regex));
+
+        let count = self.base.highlights.len();
+        if count == 0 {
+            return BufferResult::Info("No lines with trailing whitespace".to_string());
+        }
+
+        self.base.next_match();
+        let label = if count == 1 { "line" } else { "lines" };
+        BufferResult::Info(format!("{count} {label} with trailing whitespace"))
+    }
+
+    /// Deletes the lines as one change.
+    fn delete_lines(&mut self, lines: &[usize]) -> BufferResult {
+        let doc = &self.base.doc;
+        let mut selections = Vec::new();
+
+        // Delete blocks of consecutive lines at once, so that the line break removed with a last line doesn't
+        // overlap the lines above.
+        let mut idx = 0;
+        while idx < lines.len() {
+            let first = lines[idx];
+            while idx + 1 < lines.len() && lines[idx + 1] == lines[idx] + 1 {
+                idx += 1;
+            }
+            let last = lines[idx];
+            idx += 1;
+
+            let (start, end) = if last + 1 < doc.len() {
+                (Cursor::new(0, first), Cursor::new(0, last + 1))
+            } else if first > 0 {
+                // Remove the line break of the previous line instead of the missing one of the last line.
+                (
+                    Cursor::new(line_len(doc, first - 1), first - 1),
+                    Cursor::new(line_len(doc, last), last),
+                )
+            } else {
+                (Cursor::new(0, 0), Cursor::new(line_len(doc, last), last))
+            };
+            selections.push(Selection::new(
+                start,
+                end,
+                SelectionKind::Normal,
+                None,
+                None,
+            ));
+        }
+
+        delete::selection(&mut self.base.doc, &mut selections, Some(&mut self.history));
+
+        // Deletions might cause matches and selections to become invalid.
+        self.base.clear_matches();
+        self.base.clear_selections();
+
+        let label = if lines.len() == 1 { "line" } else { "lines" };
+        BufferResult::Info(format!("{} {label} deleted", lines.len()))
+    }
+
     fn execute_shell_command(&mut self, args: &str) -> BufferResult {
         self.shell_command = match ShellCommand::new(
             self.base.doc_view.buff_w,
@@ -206,19 +756,176 @@ impl TextBuffer {
         BufferResult::Ok
     }
 
+    /// Expands the placeholders of a shell command: '%' to the path of the file, '%s' to the path of a temporary file
+    /// containing the selected text and '%%' to a literal '%'.
+    fn expand_placeholders(&mut self, args: &str) -> Result<String, String> {
+        let mut cmd = String::new();
+        let mut chars = args.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                cmd.push(ch);
+                continue;
+            }
+
+            if chars.next_if_eq(&'%').is_some() {
+                cmd.push('%');
+            } else if chars.next_if_eq(&'s').is_some() {
+                let tmp_path = self.write_selection_tmp()?;
+                cmd.push_str(&shell_quote(&tmp_path.to_string_lossy()));
+            } else {
+                let Some(path) = &self.path else {
+                    return Err("Buffer has no file to substitute for '%'".to_string());
+                };
+                cmd.push_str(&shell_quote(&path.to_string_lossy()));
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    /// Writes the selected text to a temporary file that is removed once the shell command finished.
+    fn write_selection_tmp(&mut self) -> Result<PathBuf, String> {
+        if let Some(tmp_path) = &self.shell_tmp {
+            return Ok(tmp_path.clone());
+        }
+
+        let mut ranges = self
+            .base
+            .selections
+            .iter()
+            .map(Selection::range)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+        let text = ranges
+            .into_iter()
+            .filter_map(|(start, end)| {
+                self.base
+                    .doc
+                    .get_range(start, end)
+                    .map(|text| text.to_string())
+            })
+            .collect::<Vec<_>>();
+        if text.is_empty() {
+            return Err("No text selected to substitute for '%s'".to_string());
+        }
+
+        // Only create a new file readable by the user, so that no other user can provide or read it. The name isn't
+        // predictable, a taken name is retried.
+        let mut attempts = 0;
+        let (tmp_path, mut file) = loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.subsec_nanos());
+            let tmp_path = std::env::temp_dir().join(format!(
+                "mini-{}-{nanos:08x}-selection.tmp",
+                std::process::id()
+            ));
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&tmp_path)
+            {
+                Ok(file) => break (tmp_path, file),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists && attempts < 16 => {
+                    attempts += 1;
+                }
+                Err(err) => return Err(err.to_string()),
+            }
+        };
+        self.shell_tmp = Some(tmp_path.clone());
+        file.write_all(text.join("\n").as_bytes())
+            .map_err(|err| err.to_string())?;
+
+        Ok(tmp_path)
+    }
+
+    /// Runs a shell command entered by the user after expanding its placeholders.
+    fn run_shell_command(&mut self, args: &str) -> BufferResult {
+        let res = match self.expand_placeholders(args) {
+            Ok(cmd) => self.execute_shell_command(&cmd),
+            Err(err) => BufferResult::Error(err),
+        };
+
+        // Remove the temporary file if the command didn't start.
+        if self.shell_command.is_none()
+            && let Some(tmp_path) = self.shell_tmp.take()
+        {
+            let _ = std::fs::remove_file(tmp_path);
+        }
+
+        res
+    }
+
+    /// Pipes the whole document through a shell command and replaces it with the output as one change. The document is
+    /// left unchanged if the command fails. Returns if the document changed.
+    fn filter(&mut self, cmd: &str) -> Result<bool, String> {
+        let delete_data = self
+            .base
+            .doc
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<String>();
+        let contents =
+            shell_command::filter(cmd, delete_data.clone(), self.base.options.stripansi)?;
+
+        // Empty output more likely hints at a misbehaving command than at an intentionally emptied document.
+        if contents.is_empty() && !delete_data.is_empty() {
+            return Err(format!("'{cmd}' wrote no output"));
+        }
+        if contents == delete_data {
+            return Ok(false);
+        }
+
+        // Keep the cursor on its line if the document still has it.
+        let pos = self.base.doc.cur;
+        self.base.doc.from(&contents);
+        self.base.doc.edited = true;
+        cursor::move_to(&mut self.base.doc, pos);
+        self.history.add_change(vec![Replace {
+            pos: Cursor::new(0, 0),
+            delete_data,
+            insert_data: contents,
+        }]);
+
+        // The contents changed entirely.
+        self.base.clear_matches();
+        self.base.clear_selections();
+
+        Ok(true)
+    }
+
+    fn filter_command(&mut self, cmd: &str) -> BufferResult {
+        if cmd.is_empty() {
+            return BufferResult::Error("Expected a shell command like '%!rustfmt'".to_string());
+        }
+
+        match self.filter(cmd) {
+            Ok(true) => BufferResult::Info(format!("Filtered the buffer through '{cmd}'")),
+            Ok(false) => BufferResult::Info(format!("'{cmd}' made no changes")),
+            Err(err) => BufferResult::Error(format!("{err}\nThe buffer is left unchanged")),
+        }
+    }
+
     /// Applies the command entered during command mode.
     pub fn apply_command(&mut self, cmd: &str) -> BufferResult {
         if cmd.is_empty() {
             return BufferResult::Ok;
         }
 
+        // The filter command is usually written without a space, like '%!rustfmt'.
+        if let Some(cmd) = cmd.strip_prefix("%!") {
+            return self.filter_command(cmd.trim());
+        }
+
         let (cmd, args) = match cmd.split_once(char::is_whitespace) {
             Some((cmd, args)) => (cmd.trim(), args.trim()),
             None => (cmd.trim(), ""),
         };
 
         match cmd {
-            "wq" => match self.write_to_file() {
+            "wq" => match self.write_to_file(true) {
                 Ok(res) if !res => BufferResult::Error(
                     "Please specify a file location using 'w <path>' to write the file to"
                         .to_string(),
@@ -227,11 +934,142 @@ impl TextBuffer {
                 _ => BufferResult::Quit,
             },
             "w" => self.write_command(args),
+            "w!!" => self.sudo_write_command(),
+            "bm" | "bookmark" => BaseBuffer::bookmark_command(args, self.path.clone()),
+            "recover" => self.recover_command(),
+            "norecover" => {
+                self.remove_swap();
+                BufferResult::Info("Swap file deleted".to_string())
+            }
             "o" => self.open_command(args, false),
             "oo" => self.open_command(args, true),
             "r" => self.replace_command(args),
-            "c" => self.execute_shell_command(args),
+            "rp" => self.preview_replace_command(args),
+            "g" => self.global_command(args),
+            "reflow" => self.reflow_command(),
+            "ascii" => self.ascii_command(),
+            "join" => self.join_command(true, 1),
+            "join!" => self.join_command(false, 1),
+            "mixed" => self.mixed_command(),
+            "trailing" => self.trailing_command(),
+            "earlier" => self.earlier_command(args),
+            "later" => self.later_command(args),
+            "c" => self.run_shell_command(args),
+            "c!" => {
+                let res = self.run_shell_command(args);
+                if self.shell_command.is_some() {
+                    self.shell_output = ShellOutput::Scratch;
+                }
+
+                res
+            }
             _ => BufferResult::Error(format!("Unrecognized command: '{cmd}'")),
         }
     }
 }
+
+/// Iterates over the matches of the regex in a text together with their expanded replacements.
+fn replacements<'a>(
+    regex: &'a Regex,
+    replace_str: &'a str,
+    preserve_case: bool,
+    hay: &'a str,
+) -> impl Iterator<Item = (regex::Match<'a>, String)> {
+    regex.captures_iter(hay).map(move |captures| {
+        let mat = captures.get(0).unwrap();
+        let mut replacement = String::new();
+        captures.expand(replace_str, &mut replacement);
+        if preserve_case {
+            replacement = match_case(&replacement, mat.as_str());
+        }
+
+        (mat, replacement)
+    })
+}
+
+/// Parses the arguments of a replace command of the form `/<regex>/<replace>/?c`.
+fn parse_replace(args: &str) -> Result<(Regex, &str, bool), String> {
+    let err = "Invalid format. Expected: r /<regex>/<replace>/?c".to_string();
+    let Some(args) = args.strip_prefix('/') else {
+        return Err(err);
+    };
+    let Some((regex_str, replace_str)) = args.split_once('/') else {
+        return Err(err);
+    };
+    // A trailing 'c' flag preserves the case of each match.
+    let (replace_str, preserve_case) = if let Some(replace_str) = replace_str.strip_suffix("/c") {
+        (replace_str, true)
+    } else if let Some(replace_str) = replace_str.strip_suffix('/') {
+        (replace_str, false)
+    } else {
+        return Err(err);
+    };
+    if regex_str.is_empty() {
+        return Err(err);
+    }
+
+    let regex = Regex::new(regex_str)
+        .map_err(|err| format!("'{regex_str}' is not a valid regular expression:\n{err}"))?;
+    validate_replacement(&regex, replace_str)?;
+
+    Ok((regex, replace_str, preserve_case))
+}
+
+/// Returns the length of a line without its line break.
+fn line_len(doc: &Document, y: usize) -> usize {
+    let mut len = doc.line_count(y).unwrap_or(0);
+    if doc.ends_with_newline(y) {
+        len = len.saturating_sub(1);
+    }
+
+    len
+}
+
+/// Validates that all groups referenced by a replacement exist in the regex. References follow the syntax of
+/// `Captures::expand`: `$name`, `${name}` and `$$` as an escaped `$`.
+fn validate_replacement(regex: &Regex, replacement: &str) -> Result<(), String> {
+    let is_name_char = |ch: char| ch == '_' || ch.is_ascii_alphanumeric();
+
+    let mut rest = replacement;
+    while let Some(idx) = rest.find('$') {
+        rest = &rest[idx + 1..];
+
+        let name = if let Some(escaped) = rest.strip_prefix('$') {
+            rest = escaped;
+            continue;
+        } else if let Some(braced) = rest.strip_prefix('{') {
+            // Unterminated braces are inserted literally.
+            let Some(end) = braced.find('}') else {
+                continue;
+            };
+            rest = &braced[end + 1..];
+            &braced[..end]
+        } else {
+            let end = rest.find(|ch| !is_name_char(ch)).unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = &rest[end..];
+            name
+        };
+
+        if name.is_empty() {
+            continue;
+        }
+
+        if let Ok(idx) = name.parse::<usize>() {
+            // The whole match counts as group 0.
+            let groups = regex.captures_len() - 1;
+            if idx > groups {
+                let label = if groups == 1 { "group" } else { "groups" };
+                return Err(format!(
+                    "Replacement refers to group {idx} but pattern has {groups} {label}"
+                ));
+            }
+        } else if !regex.capture_names().flatten().any(|group| group == name) {
+            return Err(format!(
+                "Replacement refers to group '{name}' but pattern has no such named group"
+            ));
+        }
+    }
+
+    Ok(())
+}
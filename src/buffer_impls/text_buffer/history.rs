@@ -1,10 +1,20 @@
-use crate::{buffer_impls::text_buffer::TextBuffer, cursor};
+use crate::{buffer::BufferResult, buffer_impls::text_buffer::TextBuffer, cursor};
+use std::time::{Duration, Instant};
+
+/// How far to move through the history.
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+enum Steps {
+    /// A number of changes.
+    Count(usize),
+    /// A span of time.
+    Time(Duration),
+}
 
 impl TextBuffer {
-    /// Undos the last change if one exists.
-    pub(super) fn undo(&mut self) {
-        let Some(changes) = self.history.undo() else {
-            return;
+    /// Undos the last change if one exists. Returns if a change was undone.
+    pub(super) fn undo(&mut self) -> bool {
+        let Some((time, changes)) = self.history.undo() else {
+            return false;
         };
 
         // Undoing might cause matches and selections to become invalid.
@@ -13,27 +23,23 @@ impl TextBuffer {
 
         // Undo in reverse order to not change indices of later events.
         for c in changes.iter().rev() {
-            // To undo an insert, delete the data that was inserted.
-            self.base
-                .doc
-                .remove_range(c.pos, cursor::pos_after_text(&c.pos, &c.insert_data));
-            cursor::move_to(&mut self.base.doc, c.pos);
-
-            // To undo a delete, insert the data back.
-            self.base.doc.write_str_at(c.pos.x, c.pos.y, &c.delete_data);
-            cursor::move_to(
-                &mut self.base.doc,
-                cursor::pos_after_text(&c.pos, &c.delete_data),
+            // To undo a change, replace the inserted data with the deleted data.
+            let end = self.base.doc.replace_range(
+                c.pos,
+                cursor::pos_after_text(&c.pos, &c.insert_data),
+                &c.delete_data,
             );
+            cursor::move_to(&mut self.base.doc, end);
         }
 
-        self.history.push_redo(changes);
+        self.history.push_redo(time, changes);
+        true
     }
 
-    /// Redos the last undo, if one exists.
-    pub(super) fn redo(&mut self) {
-        let Some(changes) = self.history.redo() else {
-            return;
+    /// Redos the last undo, if one exists. Returns if a change was redone.
+    pub(super) fn redo(&mut self) -> bool {
+        let Some((time, changes)) = self.history.redo() else {
+            return false;
         };
 
         // Redoing might cause matches and selections to become invalid.
@@ -41,20 +47,144 @@ impl TextBuffer {
         self.base.clear_selections();
 
         for c in &changes {
-            // To redo a delete, delete the data.
-            self.base
-                .doc
-                .remove_range(c.pos, cursor::pos_after_text(&c.pos, &c.delete_data));
-            cursor::move_to(&mut self.base.doc, c.pos);
-
-            // To redo an insert, insert the data.
-            self.base.doc.write_str_at(c.pos.x, c.pos.y, &c.insert_data);
-            cursor::move_to(
-                &mut self.base.doc,
-                cursor::pos_after_text(&c.pos, &c.insert_data),
+            // To redo a change, replace the deleted data with the inserted data again.
+            let end = self.base.doc.replace_range(
+                c.pos,
+                cursor::pos_after_text(&c.pos, &c.delete_data),
+                &c.insert_data,
             );
+            cursor::move_to(&mut self.base.doc, end);
+        }
+
+        self.history.push_undo(time, changes);
+        true
+    }
+
+    /// Undos a number of changes or all changes made within a span of time, like `earlier 3` or `earlier 5m`.
+    pub(super) fn earlier_command(&mut self, args: &str) -> BufferResult {
+        let steps = match parse_steps(args) {
+            Ok(steps) => steps,
+            Err(err) => return BufferResult::Error(err),
+        };
+
+        let mut count = 0;
+        match steps {
+            Steps::Count(n) => {
+                while count < n && self.undo() {
+                    count += 1;
+                }
+            }
+            Steps::Time(span) => {
+                let now = Instant::now();
+                while self
+                    .history
+                    .undo_time()
+                    .is_some_and(|time| now.duration_since(time) <= span)
+                {
+                    self.undo();
+                    count += 1;
+                }
+            }
+        }
+
+        if count == 0 {
+            return BufferResult::Info("Already at oldest change".to_string());
+        }
+        BufferResult::Info(format!("Undid {count} {}", changes_label(count)))
+    }
+
+    /// Redos a number of changes or all changes made within a span of time after the current state, like `later 3` or
+    /// `later 5m`.
+    pub(super) fn later_command(&mut self, args: &str) -> BufferResult {
+        let steps = match parse_steps(args) {
+            Ok(steps) => steps,
+            Err(err) => return BufferResult::Error(err),
+        };
+
+        let mut count = 0;
+        match steps {
+            Steps::Count(n) => {
+                while count < n && self.redo() {
+                    count += 1;
+                }
+            }
+            Steps::Time(span) => {
+                // The time of the current state, or of the first undone change at the oldest state.
+                let Some(start) = self
+                    .history
+                    .undo_time()
+                    .or_else(|| self.history.redo_time())
+                else {
+                    return BufferResult::Info("Already at newest change".to_string());
+                };
+                while self
+                    .history
+                    .redo_time()
+                    .is_some_and(|time| time.saturating_duration_since(start) <= span)
+                {
+                    self.redo();
+                    count += 1;
+                }
+            }
         }
 
-        self.history.push_undo(changes);
+        if count == 0 {
+            return BufferResult::Info("Already at newest change".to_string());
+        }
+        BufferResult::Info(format!("Redid {count} {}", changes_label(count)))
+    }
+}
+
+/// Parses the steps of `earlier` and `later`, a count like `3` or a time like `30s`, `5m` or `1h`. Defaults to one
+/// change.
+fn parse_steps(args: &str) -> Result<Steps, String> {
+    if args.is_empty() {
+        return Ok(Steps::Count(1));
+    }
+
+    let (num, unit) = args.split_at(
+        args.find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(args.len()),
+    );
+    let err = || format!("'{args}' is not a valid count or time like '3', '30s', '5m' or '1h'");
+    let num = num.parse::<u64>().map_err(|_| err())?;
+
+    let secs = |factor: u64| {
+        num.checked_mul(factor)
+            .map(|secs| Steps::Time(Duration::from_secs(secs)))
+            .ok_or_else(err)
+    };
+
+    match unit {
+        "" => Ok(Steps::Count(usize::try_from(num).map_err(|_| err())?)),
+        "s" => secs(1),
+        "m" => secs(60),
+        "h" => secs(60 * 60),
+        _ => Err(err()),
+    }
+}
+
+/// Returns the label of an amount of changes.
+const fn changes_label(count: usize) -> &'static str {
+    if count == 1 { "change" } else { "changes" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_steps_of_counts_and_times() {
+        assert_eq!(parse_steps(""), Ok(Steps::Count(1)));
+        assert_eq!(parse_steps("3"), Ok(Steps::Count(3)));
+        assert_eq!(parse_steps("30s"), Ok(Steps::Time(Duration::from_secs(30))));
+        assert_eq!(parse_steps("5m"), Ok(Steps::Time(Duration::from_mins(5))));
+        assert_eq!(parse_steps("1h"), Ok(Steps::Time(Duration::from_hours(1))));
+
+        assert!(parse_steps("3d").is_err());
+        assert!(parse_steps("m").is_err());
+        // Times too long for a duration in seconds are invalid instead of overflowing.
+        assert!(parse_steps(&format!("{}h", u64::MAX / 60)).is_err());
+        assert!(parse_steps(&format!("{}m", u64::MAX)).is_err());
     }
 }
@@ -1,9 +1,9 @@
 use crate::{
-    buffer::BufferResult,
+    buffer::{BufferResult, edit},
     buffer_impls::text_buffer::TextBuffer,
     cursor::{self, Cursor},
     history::Replace,
-    util::TAB_WIDTH,
+    selection::Selection,
 };
 
 impl TextBuffer {
@@ -43,33 +43,79 @@ impl TextBuffer {
             return;
         }
 
+        let cur = self.base.doc.cur;
         let old_ch = self
             .base
             .doc
-            .delete_char(self.base.doc.cur.x, self.base.doc.cur.y);
+            .get_range(cur, Cursor::new(cur.x + 1, cur.y))
+            .map(|ch| ch.to_string())
+            .unwrap_or_default();
         let new_ch = if ch == '\t' {
-            " ".repeat(TAB_WIDTH - (self.base.doc.cur.x % TAB_WIDTH))
+            let width = self.base.options.shiftwidth;
+            " ".repeat(width - (self.base.doc.cur.x % width))
         } else {
             ch.to_string()
         };
 
         self.base
             .doc
-            .write_str_at(self.base.doc.cur.x, self.base.doc.cur.y, new_ch.as_str());
+            .replace_range(cur, Cursor::new(cur.x + 1, cur.y), &new_ch);
 
         self.history.add_change(vec![Replace {
-            pos: self.base.doc.cur,
-            delete_data: old_ch.to_string(),
+            pos: cur,
+            delete_data: old_ch,
             insert_data: new_ch,
         }]);
     }
 
+    /// Toggles the case of the selected characters, or of `n` characters from the cursor within its line and moves the
+    /// cursor past them.
+    pub(super) fn toggle_case(&mut self, n: usize) {
+        let doc = &self.base.doc;
+        let mut ranges = if self.base.selections.is_empty() {
+            let y = doc.cur.y;
+            let bound = doc.line_count(y).unwrap_or(0) - usize::from(doc.ends_with_newline(y));
+            vec![(
+                doc.cur,
+                Cursor::new(doc.cur.x.saturating_add(n).min(bound), y),
+            )]
+        } else {
+            self.base.selections.iter().map(Selection::range).collect()
+        };
+
+        // Merge overlapping ranges so that no character is toggled twice.
+        ranges.sort_unstable();
+        let mut merged: Vec<(Cursor, Cursor)> = Vec::new();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(prev) if start <= prev.1 => prev.1 = prev.1.max(end),
+                _ if start < end => merged.push((start, end)),
+                _ => {}
+            }
+        }
+
+        let Some(end) = edit::toggle_case(&mut self.base.doc, Some(&mut self.history), &merged)
+        else {
+            return;
+        };
+
+        if self.base.selections.is_empty() {
+            cursor::move_to(&mut self.base.doc, end);
+        } else {
+            // Place the cursor at the beginning of the first toggled selection.
+            cursor::move_to(&mut self.base.doc, merged[0].0);
+            self.base.clear_selections();
+        }
+        // The toggled text might not match the search anymore.
+        self.base.clear_matches();
+    }
+
     /// Paste the system clipboard contents after the current cursor.
     pub(super) fn paste(&mut self, trim_newline: bool, move_to: bool) -> Option<BufferResult> {
-        let mut insert_data = match self.base.clipboard.get_text() {
+        let mut insert_data = match self.base.clipboard.borrow_mut().get_text() {
             Ok(content) => content,
             Err(err) => {
-                return Some(BufferResult::Error(err.to_string()));
+                return Some(BufferResult::Error(err));
             }
         };
 
@@ -9,8 +9,11 @@ use termion::event::Key;
 use crate::{
     buffer::{Buffer, BufferKind, BufferResult},
     buffer_impls::{files_buffer::FilesBuffer, text_buffer::TextBuffer},
+    clipboard::SharedClipboard,
+    command_history::{self, CommandHistory, SharedHistory},
     display::Display,
     message::{Message, MessageKind},
+    options::Options,
     util::open_file,
 };
 
@@ -21,7 +24,7 @@ pub struct BufferManager {
     /// Total height of the `Display`.
     h: usize,
 
-    /// The dir where the program was launched at.
+    /// The working directory, where the program was launched at or changed to by `cd`.
     base: PathBuf,
 
     /// Open `Buffer`.
@@ -34,6 +37,11 @@ pub struct BufferManager {
     /// Log of messages to display on demand.
     log: Vec<Message>,
 
+    /// The clipboard shared by all `Buffer`s.
+    clipboard: SharedClipboard,
+    /// The command history shared by all `Buffer`s.
+    cmd_history: SharedHistory,
+
     /// Forces rerender after `Buffer` switching.
     force_rerender: bool,
 }
@@ -41,10 +49,12 @@ pub struct BufferManager {
 impl BufferManager {
     pub fn new(
         path: Option<&String>,
-        file: Option<Result<File, Error>>,
-        file_name: Option<String>,
+        file: Option<Result<(File, bool), Error>>,
+        contents: Option<String>,
         w: usize,
         h: usize,
+        clipboard: SharedClipboard,
+        history_path: Option<PathBuf>,
     ) -> Result<Self, Error> {
         let base = if let Some(path) = &path {
             // Get the absolute path.
@@ -59,22 +69,63 @@ impl BufferManager {
             std::env::current_dir()?
         };
 
+        // The history is read once and kept by all buffers, `None` keeps it only for the session.
+        let cmd_history =
+            command_history::shared(CommandHistory::load(history_path, Options::new().history));
+
         let mut log = Vec::new();
         let buff: Box<dyn Buffer> = if let Some(Err(err)) = &file {
             if err.kind() == ErrorKind::IsADirectory {
                 // Open the `FilesBuffer` if a directory was specified as argument.
-                Box::new(FilesBuffer::new(w, h, 0, 0, base.clone())?)
+                Box::new(FilesBuffer::new(
+                    w,
+                    h,
+                    0,
+                    0,
+                    base.clone(),
+                    clipboard.clone(),
+                    cmd_history.clone(),
+                )?)
             } else {
                 // Show error in files buffer if failed to open.
-                let mut files_buffer = Box::new(FilesBuffer::new(w, h, 0, 0, base.clone())?);
+                let mut files_buffer = Box::new(FilesBuffer::new(
+                    w,
+                    h,
+                    0,
+                    0,
+                    base.clone(),
+                    clipboard.clone(),
+                    cmd_history.clone(),
+                )?);
                 files_buffer.set_message(MessageKind::Error, err.to_string());
                 log.push(files_buffer.get_message().unwrap());
                 files_buffer
             }
+        } else if let Some(contents) = contents {
+            // Open a scratchpad with the piped contents.
+            Box::new(TextBuffer::from_contents(
+                w,
+                h,
+                0,
+                0,
+                &contents,
+                clipboard.clone(),
+                cmd_history.clone(),
+            )?)
         } else {
             // Open the file if no error.
-            let file = file.and_then(Result::ok);
-            Box::new(TextBuffer::new(w, h, 0, 0, file, file_name)?)
+            let (file, read_only) = file.and_then(Result::ok).unzip();
+            Box::new(TextBuffer::new(
+                w,
+                h,
+                0,
+                0,
+                file,
+                path.map(PathBuf::from),
+                read_only.unwrap_or(false),
+                clipboard.clone(),
+                cmd_history.clone(),
+            )?)
         };
 
         Ok(Self {
@@ -85,10 +136,18 @@ impl BufferManager {
             active: 0,
             prev: None,
             log,
+            clipboard,
+            cmd_history,
             force_rerender: true,
         })
     }
 
+    /// Returns the document of the active `Buffer`.
+    #[cfg(test)]
+    pub fn active_doc(&self) -> &crate::document::Document {
+        self.buffs[self.active].doc()
+    }
+
     /// Handles the event, that the terminal was resized.
     pub fn resize(&mut self, w: usize, h: usize) {
         self.w = w;
@@ -129,13 +188,39 @@ impl BufferManager {
 
                 match kind {
                     BufferKind::Text => self.buffs.push(Box::new(
-                        TextBuffer::new(self.w, self.h, 0, 0, None, None).unwrap(),
+                        TextBuffer::new(
+                            self.w,
+                            self.h,
+                            0,
+                            0,
+                            None,
+                            None,
+                            false,
+                            self.clipboard.clone(),
+                            self.cmd_history.clone(),
+                        )
+                        .unwrap(),
                     )),
                     BufferKind::Files => self.buffs.push(Box::new(
-                        FilesBuffer::new(self.w, self.h, 0, 0, self.base.clone()).unwrap(),
+                        FilesBuffer::new(
+                            self.w,
+                            self.h,
+                            0,
+                            0,
+                            self.base.clone(),
+                            self.clipboard.clone(),
+                            self.cmd_history.clone(),
+                        )
+                        .unwrap(),
                     )),
                 }
             }
+            BufferResult::Open(buff) => {
+                self.prev = Some(self.active);
+                self.active = self.buffs.len();
+                self.buffs.push(buff);
+                self.force_rerender = true;
+            }
             BufferResult::Init(buff) => self.buffs[self.active] = buff,
             BufferResult::Log => {
                 // Create log file in the base directory.
@@ -155,6 +240,11 @@ impl BufferManager {
                     format!("Log written to '{}'", log_file_path.to_string_lossy()),
                 );
             }
+            BufferResult::ChangeDir(dir) => {
+                let message = format!("Working directory is now '{}'", dir.to_string_lossy());
+                self.base = dir;
+                self.log(MessageKind::Info, message);
+            }
             BufferResult::Quit => {
                 if let Err(err) = self.buffs[self.active].can_quit() {
                     self.log(MessageKind::Error, err);
@@ -172,6 +262,90 @@ impl BufferManager {
                 self.prev = None;
                 self.force_rerender = true;
             }
+            BufferResult::WriteAll => self.write_all(),
+            BufferResult::QuitAll => {
+                let blocking = self
+                    .buffs
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, buff)| {
+                        buff.can_quit().err().map(|err| {
+                            format!("[{idx}] {}: {err}", Self::buffer_info(buff.as_ref()))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                if !blocking.is_empty() {
+                    let label = if blocking.len() == 1 {
+                        "buffer"
+                    } else {
+                        "buffers"
+                    };
+                    let message = format!(
+                        "{} {label} with unsaved changes, use `wa` to write or `qqa` to discard them:\n{}",
+                        blocking.len(),
+                        blocking.join("\n")
+                    );
+                    self.log(MessageKind::Error, message);
+                    return true;
+                }
+
+                for buff in &mut self.buffs {
+                    buff.close();
+                }
+                return false;
+            }
+            BufferResult::ForceQuitAll => {
+                for buff in &mut self.buffs {
+                    buff.close();
+                }
+                return false;
+            }
+            BufferResult::CloseOthers => {
+                // Close all other buffers without unsaved changes.
+                let active = self.active;
+                let mut kept_before = 0;
+                let mut idx = 0;
+                self.buffs.retain_mut(|buff| {
+                    let keep = idx == active || buff.can_quit().is_err();
+                    if !keep {
+                        buff.close();
+                    } else if idx < active {
+                        kept_before += 1;
+                    }
+
+                    idx += 1;
+                    keep
+                });
+                self.active = kept_before;
+                self.prev = None;
+
+                let blocking = self
+                    .buffs
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| *idx != self.active)
+                    .filter_map(|(idx, buff)| {
+                        buff.can_quit().err().map(|err| {
+                            format!("[{idx}] {}: {err}", Self::buffer_info(buff.as_ref()))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                if !blocking.is_empty() {
+                    let label = if blocking.len() == 1 {
+                        "buffer"
+                    } else {
+                        "buffers"
+                    };
+                    let message = format!(
+                        "Kept {} {label} with unsaved changes, use `wa` to write them or close them with `qq`:\n{}",
+                        blocking.len(),
+                        blocking.join("\n")
+                    );
+                    self.log(MessageKind::Error, message);
+                }
+            }
             BufferResult::ForceQuit => {
                 self.buffs.remove(self.active);
 
@@ -191,6 +365,11 @@ impl BufferManager {
 
     /// Renders the active `Buffer` to the `Display`.
     pub fn render(&mut self, display: &mut Display) {
+        // The display contains contents of a different buffer.
+        if self.force_rerender {
+            display.invalidate();
+        }
+
         if self.force_rerender || self.buffs[self.active].need_rerender() {
             self.buffs[self.active].render(display);
         }
@@ -202,20 +381,75 @@ impl BufferManager {
         use std::fmt::Write;
 
         let mut message = String::new();
+        let mut unsaved = 0;
         for (idx, buff) in self.buffs.iter().enumerate() {
             let marker = if idx == self.active { "*" } else { " " };
-            let info = match buff.kind() {
-                BufferKind::Text => format!("Text ({})", buff.name()),
-                BufferKind::Files => "Files".to_string(),
+            let modified = if buff.can_quit().is_err() {
+                unsaved += 1;
+                " +"
+            } else {
+                ""
             };
 
-            writeln!(message, "[{idx}{marker}] {info}").unwrap();
+            writeln!(
+                message,
+                "[{idx}{marker}] {}{modified}",
+                Self::buffer_info(buff.as_ref())
+            )
+            .unwrap();
+        }
+        if unsaved > 0 {
+            let label = if unsaved == 1 {
+                "buffer has"
+            } else {
+                "buffers have"
+            };
+            writeln!(message, "{unsaved} {label} unsaved changes.").unwrap();
         }
         message.push_str("Use `cb <idx>` to switch to a buffer.");
 
         message
     }
 
+    /// Describes a buffer by its kind and name.
+    fn buffer_info(buff: &dyn Buffer) -> String {
+        match buff.kind() {
+            BufferKind::Text => format!("Text ({})", buff.name()),
+            BufferKind::Files => "Files".to_string(),
+        }
+    }
+
+    /// Writes all buffers with unsaved changes and reports the buffers that failed to write.
+    fn write_all(&mut self) {
+        let mut written = 0;
+        let mut failed = Vec::new();
+        for (idx, buff) in self.buffs.iter_mut().enumerate() {
+            match buff.write() {
+                Ok(true) => written += 1,
+                Ok(false) => {}
+                Err(err) => failed.push(format!(
+                    "[{idx}] {}: {err}",
+                    Self::buffer_info(buff.as_ref())
+                )),
+            }
+        }
+
+        let label = if written == 1 { "buffer" } else { "buffers" };
+        if failed.is_empty() {
+            self.log(MessageKind::Info, format!("Wrote {written} {label}"));
+        } else {
+            let message = format!(
+                "Wrote {written} {label}, failed to write {}:\n{}",
+                failed.len(),
+                failed.join("\n")
+            );
+            self.log(MessageKind::Error, message);
+        }
+
+        // Writing changes the info line of the active buffer.
+        self.force_rerender = true;
+    }
+
     fn log(&mut self, kind: MessageKind, text: String) {
         self.buffs[self.active].set_message(kind, text);
         self.log
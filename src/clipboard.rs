@@ -0,0 +1,183 @@
+use arboard::Clipboard;
+use std::{
+    cell::RefCell,
+    env,
+    io::{Write, stdout},
+    rc::Rc,
+};
+
+/// The characters used by base64 encoding.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A clipboard that text can be yanked to and pasted from.
+pub trait ClipboardProvider {
+    /// Returns the current text contents of the clipboard.
+    fn get_text(&mut self) -> Result<String, String>;
+    /// Replaces the contents of the clipboard with `text`.
+    fn set_text(&mut self, text: String) -> Result<(), String>;
+}
+
+/// A clipboard shared by all buffers, so text yanked in one buffer can be pasted in another.
+pub type SharedClipboard = Rc<RefCell<Box<dyn ClipboardProvider>>>;
+
+/// Wraps a clipboard to be shared by all buffers.
+pub fn shared(clipboard: Box<dyn ClipboardProvider>) -> SharedClipboard {
+    Rc::new(RefCell::new(clipboard))
+}
+
+/// Selects a clipboard by name, `system`, `osc52` or `memory`. Empty selects one for the current environment.
+pub fn by_name(name: &str) -> Result<Box<dyn ClipboardProvider>, String> {
+    match name {
+        "" => Ok(new()),
+        "system" => Clipboard::new()
+            .map(|clipboard| Box::new(SystemClipboard(clipboard)) as Box<dyn ClipboardProvider>)
+            .map_err(|err| format!("The system clipboard is not available: {err}")),
+        "osc52" => Ok(Box::new(Osc52Clipboard::default())),
+        "memory" => Ok(Box::new(MemoryClipboard::default())),
+        _ => Err(format!("'{name}' is not a valid value for 'clipboard'")),
+    }
+}
+
+/// Selects a clipboard for the current environment. Remote sessions use OSC52 to reach the clipboard of the local
+/// terminal, otherwise the system clipboard is used if available and an in-memory clipboard if not.
+pub fn new() -> Box<dyn ClipboardProvider> {
+    if env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some() {
+        return Box::new(Osc52Clipboard::default());
+    }
+
+    match Clipboard::new() {
+        Ok(clipboard) => Box::new(SystemClipboard(clipboard)),
+        Err(_) => Box::new(MemoryClipboard::default()),
+    }
+}
+
+/// The system clipboard.
+pub struct SystemClipboard(Clipboard);
+
+impl ClipboardProvider for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        self.0.get_text().map_err(|err| err.to_string())
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        self.0.set_text(text).map_err(|err| err.to_string())
+    }
+}
+
+/// A clipboard that sets the clipboard of the terminal using OSC52 escape sequences. Reading the terminal clipboard
+/// is not widely supported, so pasting uses the last yanked text.
+#[derive(Default)]
+pub struct Osc52Clipboard {
+    text: String,
+}
+
+impl ClipboardProvider for Osc52Clipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        Ok(self.text.clone())
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        let mut stdout = stdout().lock();
+        write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))
+            .and_then(|()| stdout.flush())
+            .map_err(|err| err.to_string())?;
+
+        self.text = text;
+        Ok(())
+    }
+}
+
+/// A clipboard that only lives as long as the editor.
+#[derive(Default)]
+pub struct MemoryClipboard {
+    text: String,
+}
+
+impl ClipboardProvider for MemoryClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        Ok(self.text.clone())
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        self.text = text;
+        Ok(())
+    }
+}
+
+/// Encodes `data` as padded base64.
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_clipboard_pastes_the_last_yanked_text() {
+        let mut clipboard = MemoryClipboard::default();
+        assert_eq!(clipboard.get_text().unwrap(), "");
+
+        clipboard.set_text("first".to_string()).unwrap();
+        clipboard.set_text("second".to_string()).unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), "second");
+    }
+
+    #[test]
+    fn shared_clipboard_is_the_same_for_all_clones() {
+        let clipboard = shared(Box::new(MemoryClipboard::default()));
+        let other = Rc::clone(&clipboard);
+
+        clipboard
+            .borrow_mut()
+            .set_text("yanked".to_string())
+            .unwrap();
+        assert_eq!(other.borrow_mut().get_text().unwrap(), "yanked");
+    }
+
+    #[test]
+    fn by_name_selects_a_provider() {
+        let mut memory = by_name("memory").unwrap();
+        memory.set_text("text".to_string()).unwrap();
+        assert_eq!(memory.get_text().unwrap(), "text");
+
+        assert!(by_name("osc52").is_ok());
+        assert_eq!(
+            by_name("primary").err().unwrap(),
+            "'primary' is not a valid value for 'clipboard'"
+        );
+    }
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foobar", "Zm9vYmFy"),
+            ("ä\n", "w6QK"),
+        ] {
+            assert_eq!(base64(data.as_bytes()), encoded, "{data:?}");
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    cell::RefCell,
+    fs::{self, OpenOptions, Permissions},
+    io::{ErrorKind, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::PathBuf,
+    rc::Rc,
+};
+
+/// A command history shared by all buffers, so commands entered in one buffer can be recalled in another.
+pub type SharedHistory = Rc<RefCell<CommandHistory>>;
+
+/// Wraps a command history to be shared by all buffers.
+pub fn shared(history: CommandHistory) -> SharedHistory {
+    Rc::new(RefCell::new(history))
+}
+
+/// Gets the path of the command history file inside the state directory.
+pub fn path() -> Option<PathBuf> {
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+
+    Some(state.join("mini").join("history"))
+}
+
+/// The entered commands, oldest first. Searches are commands too and are kept in the same history.
+#[derive(Default)]
+pub struct CommandHistory {
+    /// The file the commands are persisted to, `None` keeps them only for the session.
+    path: Option<PathBuf>,
+    commands: Vec<String>,
+}
+
+impl CommandHistory {
+    /// Loads the last `max` commands of the history file. A missing history file is treated as an empty history, the
+    /// file is not touched at all if `max` is zero. A history file that can't be read is not written to either.
+    pub fn load(path: Option<PathBuf>, max: usize) -> Self {
+        let Some(path) = path.filter(|_| max > 0) else {
+            return Self::default();
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(_) => return Self::default(),
+        };
+
+        // Commands are appended to the file, so only the latest occurrence of repeated commands is kept.
+        let mut commands: Vec<String> = Vec::new();
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            commands.retain(|command| command != line);
+            commands.push(line.to_string());
+        }
+        commands.drain(..commands.len().saturating_sub(max));
+
+        // Compact the file once per session instead of rewriting it on every command.
+        let mut compacted = commands.join("\n");
+        if !compacted.is_empty() {
+            compacted.push('\n');
+        }
+        if compacted != contents {
+            // Failing to compact the history only leaves the file longer than needed.
+            let _ = fs::write(&path, compacted);
+        }
+
+        Self {
+            path: Some(path),
+            commands,
+        }
+    }
+
+    /// Returns the amount of commands in the history.
+    pub const fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns the command at the index.
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.commands.get(idx).map(String::as_str)
+    }
+
+    /// Adds a command to the end of the history, removing earlier occurrences of it and keeping the last `max`
+    /// commands. The command is appended to the history file unless `max` is zero, which keeps all commands of the
+    /// session instead.
+    pub fn push(&mut self, cmd: &str, max: usize) {
+        self.commands.retain(|command| command != cmd);
+        self.commands.push(cmd.to_string());
+
+        if max == 0 {
+            return;
+        }
+        self.commands
+            .drain(..self.commands.len().saturating_sub(max));
+
+        // Failing to persist the history doesn't affect the current session.
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+
+            // Commands might contain secrets, so only the user may read the history. History files left by earlier
+            // versions are restricted as well.
+            if let Ok(mut file) = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .mode(0o600)
+                .open(path)
+            {
+                let _ = file.set_permissions(Permissions::from_mode(0o600));
+                let _ = writeln!(file, "{cmd}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_without_a_path_stays_in_memory() {
+        let mut history = CommandHistory::load(None, 100);
+        history.push("w", 100);
+        history.push("q", 100);
+        history.push("w", 100);
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("q"));
+        assert_eq!(history.get(1), Some("w"));
+    }
+
+    #[test]
+    fn history_is_persisted_and_compacted() {
+        let dir = std::env::temp_dir().join(format!("mini-history-{}", std::process::id()));
+        let path = dir.join("history");
+
+        let mut history = CommandHistory::load(Some(path.clone()), 2);
+        history.push("a", 2);
+        history.push("b", 2);
+        history.push("a", 2);
+        history.push("c", 2);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("a"));
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let history = CommandHistory::load(Some(path.clone()), 2);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("a"));
+        assert_eq!(history.get(1), Some("c"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nc\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn zero_history_does_not_touch_the_file() {
+        let dir = std::env::temp_dir().join(format!("mini-nohistory-{}", std::process::id()));
+        let path = dir.join("history");
+
+        let mut history = CommandHistory::load(Some(path), 0);
+        history.push("w", 0);
+
+        assert_eq!(history.len(), 1);
+        assert!(!dir.exists());
+    }
+}
@@ -1,11 +1,141 @@
-use crate::document::Document;
-
-#[derive(Clone, Copy)]
+use crate::document::{CharClass, Document};
+use std::{
+    fmt::{self, Display},
+    iter::Peekable,
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 /// The displayed cursor style.
 pub enum CursorStyle {
     Hidden,
     SteadyBar,
+    BlinkingBar,
     SteadyBlock,
+    BlinkingBlock,
+    SteadyUnderline,
+    BlinkingUnderline,
+}
+
+impl FromStr for CursorStyle {
+    type Err = ();
+
+    /// Parses a shape of `block`, `bar` or `underline`, followed by `-blink` for a blinking cursor.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bar" => Ok(Self::SteadyBar),
+            "bar-blink" => Ok(Self::BlinkingBar),
+            "block" => Ok(Self::SteadyBlock),
+            "block-blink" => Ok(Self::BlinkingBlock),
+            "underline" => Ok(Self::SteadyUnderline),
+            "underline-blink" => Ok(Self::BlinkingUnderline),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for CursorStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let style = match self {
+            Self::Hidden => "hidden",
+            Self::SteadyBar => "bar",
+            Self::BlinkingBar => "bar-blink",
+            Self::SteadyBlock => "block",
+            Self::BlinkingBlock => "block-blink",
+            Self::SteadyUnderline => "underline",
+            Self::BlinkingUnderline => "underline-blink",
+        };
+        write!(f, "{style}")
+    }
+}
+
+#[derive(Clone, Copy)]
+/// A motion to an occurrence of a character in the line of the cursor.
+pub enum FindChar {
+    /// Onto the next occurrence, like `f`.
+    Forward,
+    /// Onto the previous occurrence, like `F`.
+    Backward,
+    /// Just before the next occurrence, like `t`.
+    TillForward,
+    /// Just after the previous occurrence, like `T`.
+    TillBackward,
+}
+
+impl FindChar {
+    /// Gets the motion of a key, one of `f`, `F`, `t` and `T`.
+    pub const fn from_key(ch: char) -> Option<Self> {
+        match ch {
+            'f' => Some(Self::Forward),
+            'F' => Some(Self::Backward),
+            't' => Some(Self::TillForward),
+            'T' => Some(Self::TillBackward),
+            _ => None,
+        }
+    }
+
+    /// Moves the cursor by the motion to the nth occurrence of the character. Returns if it was found, the cursor
+    /// doesn't move otherwise.
+    pub fn apply(self, doc: &mut Document, ch: char, n: usize) -> bool {
+        let pos = doc.cur;
+        for idx in 0..n {
+            // Later occurrences are searched after the one a till motion stopped in front of.
+            if idx > 0 {
+                self.step_over(doc);
+            }
+
+            let found = match self {
+                Self::Forward => find_char_forward(doc, ch),
+                Self::Backward => find_char_backward(doc, ch),
+                Self::TillForward => till_char_forward(doc, ch),
+                Self::TillBackward => till_char_backward(doc, ch),
+            };
+            if !found {
+                doc.cur = pos;
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Moves the cursor by the motion again, like `;`. Unlike `apply`, a till motion doesn't get stuck on the occurrence
+    /// it stopped at and moves on to the following one. Returns if the character was found.
+    pub fn repeat(self, doc: &mut Document, ch: char, n: usize) -> bool {
+        let pos = doc.cur;
+        self.step_over(doc);
+        if self.apply(doc, ch, n) {
+            return true;
+        }
+
+        doc.cur = pos;
+        false
+    }
+
+    /// Steps onto the occurrence a till motion stopped in front of, so the search starts after it.
+    fn step_over(self, doc: &mut Document) {
+        match self {
+            Self::TillForward => right(doc, 1),
+            Self::TillBackward => left(doc, 1),
+            Self::Forward | Self::Backward => {}
+        }
+    }
+
+    /// Gets the motion in the opposite direction, like `,` does.
+    pub const fn reversed(self) -> Self {
+        match self {
+            Self::Forward => Self::Backward,
+            Self::Backward => Self::Forward,
+            Self::TillForward => Self::TillBackward,
+            Self::TillBackward => Self::TillForward,
+        }
+    }
+
+    /// Checks if the character under the cursor after the motion is affected by an operator. Like in Vim, forward
+    /// motions include it while backward motions exclude the character the cursor started on instead.
+    pub const fn inclusive(self) -> bool {
+        matches!(self, Self::Forward | Self::TillForward)
+    }
 }
 
 #[derive(Clone, Copy, Eq)]
@@ -75,8 +205,11 @@ impl Ord for Cursor {
 #[macro_export]
 /// Convenience macro for calling movement functions. Expects a `BaseBuffer` as member `base`.
 macro_rules! movement {
-    ($self:ident, $func:ident) => {{
-        $crate::cursor::$func(&mut $self.base.doc, 1);
+    ($self:ident, $func:ident) => {
+        $crate::movement!($self, $func, 1)
+    };
+    ($self:ident, $func:ident, $n:expr) => {{
+        $crate::cursor::$func(&mut $self.base.doc, $n);
         $self.base.update_selection();
     }};
 }
@@ -124,6 +257,13 @@ pub fn move_to(doc: &mut Document, pos: Cursor) {
     }
 }
 
+/// Returns the last valid x position of a line. This is the position of the newline, or the position after the last
+/// char when the line does not end with a newline, like the last line of a file without a trailing newline.
+fn line_bound(doc: &Document, y: usize) -> usize {
+    let count = doc.line_count(y).unwrap_or(0);
+    count - usize::from(doc.ends_with_newline(y))
+}
+
 /// Moves the cursors to the left.
 pub fn left(doc: &mut Document, n: usize) {
     doc.cur.left(n, 0);
@@ -131,11 +271,7 @@ pub fn left(doc: &mut Document, n: usize) {
 
 /// Moves the cursors to the right
 pub fn right(doc: &mut Document, n: usize) {
-    let mut line_bound = doc.line_count(doc.cur.y).unwrap();
-    if doc.ends_with_newline(doc.cur.y) {
-        line_bound = line_bound.saturating_sub(1);
-    }
-
+    let line_bound = line_bound(doc, doc.cur.y);
     doc.cur.right(n, line_bound);
 }
 
@@ -144,12 +280,7 @@ pub fn up(doc: &mut Document, n: usize) {
     doc.cur.up(n, 0);
 
     // When moving up, handle case that new line contains less text than previous.
-    let mut line_bound = doc.line_count(doc.cur.y).unwrap();
-    if doc.ends_with_newline(doc.cur.y) {
-        line_bound = line_bound.saturating_sub(1);
-    }
-
-    doc.cur.x = doc.cur.target_x.min(line_bound);
+    doc.cur.x = doc.cur.target_x.min(line_bound(doc, doc.cur.y));
 }
 
 /// Moves the cursors down.
@@ -158,19 +289,23 @@ pub fn down(doc: &mut Document, n: usize) {
     doc.cur.down(n, bound);
 
     // When moving down, handle case that new line contains less text than previous.
-    let mut line_bound = doc.line_count(doc.cur.y).unwrap();
-    if doc.ends_with_newline(doc.cur.y) {
-        line_bound = line_bound.saturating_sub(1);
-    }
+    doc.cur.x = doc.cur.target_x.min(line_bound(doc, doc.cur.y));
+}
 
-    doc.cur.x = doc.cur.target_x.min(line_bound);
+/// Repeats a motion up to `n` times, stopping early once the cursor no longer moves.
+fn repeat_motion(doc: &mut Document, n: usize, motion: fn(&mut Document)) {
+    for _ in 0..n {
+        let cur = doc.cur;
+        motion(doc);
+        if doc.cur == cur {
+            break;
+        }
+    }
 }
 
 /// Jumps the cursors to the next "word".
 pub fn next_word(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __next_word(doc);
-    }
+    repeat_motion(doc, n, __next_word);
 }
 
 fn __next_word(doc: &mut Document) {
@@ -188,28 +323,12 @@ fn __next_word(doc: &mut Document) {
     };
     let mut chars = text.chars().peekable();
     let mut idx = doc.xy_to_idx(doc.cur.x, doc.cur.y);
-    let Some(first) = chars.peek().copied() else {
-        return;
-    };
-
-    if first.is_alphanumeric() {
-        while chars.next_if(|c| c.is_alphanumeric()).is_some() {
-            idx += 1;
-        }
-        while chars.next_if(|c| c.is_whitespace()).is_some() {
-            idx += 1;
-        }
-    } else if first.is_whitespace() {
-        while chars.next_if(|c| c.is_whitespace()).is_some() {
-            idx += 1;
-        }
-    } else {
-        chars.next();
-        idx += 1;
-        while chars.next_if(|c| c.is_whitespace()).is_some() {
-            idx += 1;
-        }
+    if let Some(first) = chars.peek().copied()
+        && doc.char_class(first) != CharClass::Whitespace
+    {
+        idx += skip_class(doc, &mut chars, doc.char_class(first));
     }
+    idx += skip_whitespace_until_empty_line(doc, &mut chars);
 
     let (x, y) = doc.idx_to_xy(idx);
     doc.cur = Cursor::new(x, y);
@@ -217,9 +336,7 @@ fn __next_word(doc: &mut Document) {
 
 /// Jumps the cursors to the end of the next "word".
 pub fn next_word_end(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __next_word_end(doc);
-    }
+    repeat_motion(doc, n, __next_word_end);
 }
 
 fn __next_word_end(doc: &mut Document) {
@@ -237,30 +354,9 @@ fn __next_word_end(doc: &mut Document) {
     };
     let mut chars = text.chars().peekable();
     let mut idx = doc.xy_to_idx(doc.cur.x, doc.cur.y);
-    let Some(first) = chars.peek().copied() else {
-        return;
-    };
-
-    if first.is_alphanumeric() {
-        while chars.next_if(|c| c.is_alphanumeric()).is_some() {
-            idx += 1;
-        }
-    } else if first.is_whitespace() {
-        while chars.next_if(|c| c.is_whitespace()).is_some() {
-            idx += 1;
-        }
-        if let Some(c) = chars.peek()
-            && !c.is_whitespace()
-            && !c.is_alphanumeric()
-        {
-            idx += 1;
-        } else {
-            while chars.next_if(|c| c.is_alphanumeric()).is_some() {
-                idx += 1;
-            }
-        }
-    } else {
-        idx += 1;
+    idx += skip_class(doc, &mut chars, CharClass::Whitespace);
+    if let Some(next) = chars.peek().copied() {
+        idx += skip_class(doc, &mut chars, doc.char_class(next));
     }
 
     let (x, y) = doc.idx_to_xy(idx);
@@ -269,9 +365,7 @@ fn __next_word_end(doc: &mut Document) {
 
 /// Jumps the cursors to the previous "word".
 pub fn prev_word(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __prev_word(doc);
-    }
+    repeat_motion(doc, n, __prev_word);
 }
 
 fn __prev_word(doc: &mut Document) {
@@ -284,44 +378,137 @@ fn __prev_word(doc: &mut Document) {
     };
     let mut chars = text.chars_at(text.len_chars()).reversed().peekable();
     let mut idx = doc.xy_to_idx(doc.cur.x, doc.cur.y);
-    let Some(first) = chars.peek().copied() else {
+    idx -= skip_whitespace_until_empty_line(doc, &mut chars);
+    // Stopped on an empty line, which is a word of its own.
+    if let Some(next) = chars.peek().copied()
+        && next != '\n'
+    {
+        idx -= skip_class(doc, &mut chars, doc.char_class(next));
+    }
+
+    let (x, y) = doc.idx_to_xy(idx);
+    doc.cur = Cursor::new(x, y);
+}
+
+/// Jumps the cursors to the end of the previous "word".
+pub fn prev_word_end(doc: &mut Document, n: usize) {
+    repeat_motion(doc, n, __prev_word_end);
+}
+
+fn __prev_word_end(doc: &mut Document) {
+    if doc.cur == Cursor::new(0, 0) {
+        return;
+    }
+
+    let Some(text) = doc.get_range(Cursor::new(0, 0), doc.cur) else {
         return;
     };
+    let mut chars = text.chars_at(text.len_chars()).reversed().peekable();
+    let mut idx = doc.xy_to_idx(doc.cur.x, doc.cur.y);
+    if let Some(first) = chars.peek().copied()
+        && doc.char_class(first) != CharClass::Whitespace
+    {
+        idx -= skip_class(doc, &mut chars, doc.char_class(first));
+    }
+    idx -= skip_class(doc, &mut chars, CharClass::Whitespace);
 
-    if first.is_alphanumeric() {
-        while chars.next_if(|c| c.is_alphanumeric()).is_some() {
-            idx -= 1;
-        }
-    } else if first.is_whitespace() {
-        while chars.next_if(|c| c.is_whitespace()).is_some() {
-            idx -= 1;
+    let (x, y) = doc.idx_to_xy(idx);
+    doc.cur = Cursor::new(x, y);
+}
+
+/// Skips the run of characters of a class, returning the amount of skipped characters.
+fn skip_class<I: Iterator<Item = char>>(
+    doc: &Document,
+    chars: &mut Peekable<I>,
+    class: CharClass,
+) -> usize {
+    let mut count = 0;
+    while chars.next_if(|c| doc.char_class(*c) == class).is_some() {
+        count += 1;
+    }
+
+    count
+}
+
+/// Skips a run of whitespace like `skip_class`, but stops after a newline that is followed by an empty line. Empty
+/// lines count as words, so word motions pause on them.
+fn skip_whitespace_until_empty_line<I: Iterator<Item = char>>(
+    doc: &Document,
+    chars: &mut Peekable<I>,
+) -> usize {
+    let mut count = 0;
+    while let Some(ch) = chars.next_if(|c| doc.char_class(*c) == CharClass::Whitespace) {
+        count += 1;
+
+        if ch == '\n' && chars.peek().is_none_or(|c| *c == '\n') {
+            break;
         }
-        if let Some(c) = chars.peek()
-            && !c.is_whitespace()
-            && !c.is_alphanumeric()
-        {
-            idx -= 1;
-        } else {
-            while chars.next_if(|c| c.is_alphanumeric()).is_some() {
-                idx -= 1;
+    }
+
+    count
+}
+
+/// Jumps the cursors to the next "subword". Subwords are the camelCase humps and underscore separated parts of a word.
+pub fn next_subword(doc: &mut Document, n: usize) {
+    repeat_motion(doc, n, __next_subword);
+}
+
+fn __next_subword(doc: &mut Document) {
+    let end = {
+        let y = doc.len().saturating_sub(1);
+        let x = doc.line_count(y).unwrap_or(0);
+        Cursor::new(x, y)
+    };
+    if doc.cur == end {
+        return;
+    }
+
+    let Some(text) = doc.get_range(doc.cur, end) else {
+        return;
+    };
+    let mut chars = text.chars().peekable();
+    let mut idx = doc.xy_to_idx(doc.cur.x, doc.cur.y);
+    let Some(mut prev) = chars.next() else {
+        return;
+    };
+    idx += 1;
+
+    if prev.is_alphanumeric() {
+        while let Some(&ch) = chars.peek() {
+            if !ch.is_alphanumeric() {
+                break;
+            }
+
+            // Stop at a hump like in "fooBar" or at the last capital of an acronym like in "HTTPServer".
+            let after = chars.clone().nth(1);
+            if ((prev.is_lowercase() || prev.is_numeric()) && ch.is_uppercase())
+                || (prev.is_uppercase()
+                    && ch.is_uppercase()
+                    && after.is_some_and(char::is_lowercase))
+            {
+                break;
             }
+
+            prev = ch;
+            chars.next();
+            idx += 1;
         }
-    } else {
-        idx -= 1;
+    }
+    while chars.next_if(|c| *c == '_' || c.is_whitespace()).is_some() {
+        idx += 1;
     }
 
     let (x, y) = doc.idx_to_xy(idx);
     doc.cur = Cursor::new(x, y);
 }
 
-/// Jumps the cursors to the end of the previous "word".
-pub fn prev_word_end(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __prev_word_end(doc);
-    }
+/// Jumps the cursors to the previous "subword". Subwords are the camelCase humps and underscore separated parts of a
+/// word.
+pub fn prev_subword(doc: &mut Document, n: usize) {
+    repeat_motion(doc, n, __prev_subword);
 }
 
-fn __prev_word_end(doc: &mut Document) {
+fn __prev_subword(doc: &mut Document) {
     if doc.cur == Cursor::new(0, 0) {
         return;
     }
@@ -331,23 +518,38 @@ fn __prev_word_end(doc: &mut Document) {
     };
     let mut chars = text.chars_at(text.len_chars()).reversed().peekable();
     let mut idx = doc.xy_to_idx(doc.cur.x, doc.cur.y);
-    let Some(first) = chars.peek().copied() else {
+
+    while chars.next_if(|c| *c == '_' || c.is_whitespace()).is_some() {
+        idx -= 1;
+    }
+
+    let Some(mut last) = chars.next() else {
+        let (x, y) = doc.idx_to_xy(idx);
+        doc.cur = Cursor::new(x, y);
         return;
     };
+    idx -= 1;
 
-    if first.is_alphanumeric() {
-        while chars.next_if(|c| c.is_alphanumeric()).is_some() {
-            idx -= 1;
-        }
-        while chars.next_if(|c| c.is_whitespace()).is_some() {
-            idx -= 1;
-        }
-    } else if first.is_whitespace() {
-        while chars.next_if(|c| c.is_whitespace()).is_some() {
+    if last.is_alphanumeric() {
+        let mut after = None;
+        while let Some(&ch) = chars.peek() {
+            if !ch.is_alphanumeric() {
+                break;
+            }
+
+            // Stop at the start of a hump like in "fooBar" or of the word after an acronym like in "HTTPServer".
+            if last.is_uppercase()
+                && ((ch.is_lowercase() || ch.is_numeric())
+                    || (ch.is_uppercase() && after.is_some_and(char::is_lowercase)))
+            {
+                break;
+            }
+
+            after = Some(last);
+            last = ch;
+            chars.next();
             idx -= 1;
         }
-    } else {
-        idx -= 1;
     }
 
     let (x, y) = doc.idx_to_xy(idx);
@@ -356,9 +558,7 @@ fn __prev_word_end(doc: &mut Document) {
 
 /// Jumps to the next whitespace.
 pub fn next_whitespace(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __next_whitespace(doc);
-    }
+    repeat_motion(doc, n, __next_whitespace);
 }
 
 fn __next_whitespace(doc: &mut Document) {
@@ -390,9 +590,7 @@ fn __next_whitespace(doc: &mut Document) {
 
 /// Jumps to the previous whitespace.
 pub fn prev_whitespace(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __prev_whitespace(doc);
-    }
+    repeat_motion(doc, n, __prev_whitespace);
 }
 
 fn __prev_whitespace(doc: &mut Document) {
@@ -422,9 +620,7 @@ fn __prev_whitespace(doc: &mut Document) {
 
 /// Jumps to the next empty line.
 pub fn next_empty_line(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __next_empty_line(doc);
-    }
+    repeat_motion(doc, n, __next_empty_line);
 }
 
 fn __next_empty_line(doc: &mut Document) {
@@ -442,9 +638,7 @@ fn __next_empty_line(doc: &mut Document) {
 
 /// Jumps to the previous empty line.
 pub fn prev_empty_line(doc: &mut Document, n: usize) {
-    for _ in 0..n {
-        __prev_empty_line(doc);
-    }
+    repeat_motion(doc, n, __prev_empty_line);
 }
 
 pub fn __prev_empty_line(doc: &mut Document) {
@@ -466,16 +660,39 @@ pub fn jump_to_beginning_of_line(doc: &mut Document) {
     left(doc, doc.cur.x);
 }
 
+/// Jumps the cursors to the first character of a line that is not a space or tab.
+pub fn jump_to_first_non_blank(doc: &mut Document) {
+    let indent = doc.line(doc.cur.y).map_or(0, |line| {
+        line.chars()
+            .take_while(|ch| matches!(ch, ' ' | '\t'))
+            .count()
+    });
+
+    jump_to_beginning_of_line(doc);
+    right(doc, indent);
+}
+
 /// Jumps the cursors to the end of a line.
 pub fn jump_to_end_of_line(doc: &mut Document) {
-    let mut line_bound = doc.line_count(doc.cur.y).unwrap();
-    if doc.ends_with_newline(doc.cur.y) {
-        line_bound = line_bound.saturating_sub(1);
-    }
-
+    let line_bound = line_bound(doc, doc.cur.y);
     right(doc, line_bound.saturating_sub(doc.cur.x));
 }
 
+/// Jumps the cursors to a visual column of the current line. Tabs and wide characters count with their displayed
+/// width. Columns past the end of the line jump to the end of the line.
+pub fn jump_to_column(doc: &mut Document, col: usize) {
+    // Measured like the renderer and the visual column of the info line.
+    let x = doc
+        .visual_cells(doc.cur.y)
+        .and_then(|mut cells| cells.find(|cell| cell.x + cell.width > col))
+        .map(|cell| cell.idx);
+
+    match x {
+        Some(x) => move_to(doc, Cursor::new(x, doc.cur.y)),
+        None => jump_to_end_of_line(doc),
+    }
+}
+
 /// Jumps the cursors to the matching opposite bracket (if exists).
 pub fn jump_to_matching_opposite(doc: &mut Document) {
     if let Some((x, y)) = find_matching_bracket(doc) {
@@ -483,6 +700,58 @@ pub fn jump_to_matching_opposite(doc: &mut Document) {
     }
 }
 
+/// Finds the index of the next occurrence of a character after the cursor in its line.
+fn next_char_in_line(doc: &Document, ch: char) -> Option<usize> {
+    let start = doc.cur.x + 1;
+    doc.line(doc.cur.y)?
+        .chars()
+        .take(line_bound(doc, doc.cur.y))
+        .skip(start)
+        .position(|c| c == ch)
+        .map(|idx| start + idx)
+}
+
+/// Finds the index of the previous occurrence of a character before the cursor in its line.
+fn prev_char_in_line(doc: &Document, ch: char) -> Option<usize> {
+    doc.line(doc.cur.y)?
+        .chars_at(doc.cur.x)
+        .reversed()
+        .position(|c| c == ch)
+        .map(|idx| doc.cur.x - 1 - idx)
+}
+
+/// Moves the cursor onto the next occurrence of a character in its line. Returns if it was found.
+pub fn find_char_forward(doc: &mut Document, ch: char) -> bool {
+    next_char_in_line(doc, ch).is_some_and(|x| {
+        right(doc, x - doc.cur.x);
+        true
+    })
+}
+
+/// Moves the cursor onto the previous occurrence of a character in its line. Returns if it was found.
+pub fn find_char_backward(doc: &mut Document, ch: char) -> bool {
+    prev_char_in_line(doc, ch).is_some_and(|x| {
+        left(doc, doc.cur.x - x);
+        true
+    })
+}
+
+/// Moves the cursor just before the next occurrence of a character in its line. Returns if it was found.
+pub fn till_char_forward(doc: &mut Document, ch: char) -> bool {
+    next_char_in_line(doc, ch).is_some_and(|x| {
+        right(doc, x - 1 - doc.cur.x);
+        true
+    })
+}
+
+/// Moves the cursor just after the previous occurrence of a character in its line. Returns if it was found.
+pub fn till_char_backward(doc: &mut Document, ch: char) -> bool {
+    prev_char_in_line(doc, ch).is_some_and(|x| {
+        left(doc, doc.cur.x - (x + 1));
+        true
+    })
+}
+
 fn find_matching_bracket(doc: &Document) -> Option<(usize, usize)> {
     let Some(current_char) = doc.line(doc.cur.y).unwrap().chars().nth(doc.cur.x) else {
         return None; // Cursor is at the end of line.
@@ -546,3 +815,169 @@ pub fn jump_to_end_of_file(doc: &mut Document) {
 pub fn jump_to_beginning_of_file(doc: &mut Document) {
     move_to(doc, Cursor::new(0, 0));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Repeats a motion on a document of a single line, returning the columns it stops at until it stops moving.
+    fn stops(text: &str, start: usize, motion: fn(&mut Document, usize)) -> Vec<usize> {
+        let mut doc = Document::new(start, 0, Some(text.to_string()));
+        let mut stops = Vec::new();
+
+        loop {
+            let prev = doc.cur;
+            motion(&mut doc, 1);
+            if doc.cur == prev {
+                return stops;
+            }
+            assert_eq!(doc.cur.y, 0);
+            stops.push(doc.cur.x);
+        }
+    }
+
+    #[test]
+    fn word_motions_stop_at_class_transitions() {
+        // The stops of 'w', 'W', 'b' and 'B' from the start or end of the text. The ends of words are the column after
+        // their last character.
+        #[allow(clippy::type_complexity)]
+        let table: [(&str, &[usize], &[usize], &[usize], &[usize]); 6] = [
+            (
+                "foo::bar->baz",
+                &[3, 5, 8, 10, 13],
+                &[3, 5, 8, 10, 13],
+                &[10, 8, 5, 3, 0],
+                &[10, 8, 5, 3, 0],
+            ),
+            (
+                "a.b.c",
+                &[1, 2, 3, 4, 5],
+                &[1, 2, 3, 4, 5],
+                &[4, 3, 2, 1, 0],
+                &[4, 3, 2, 1, 0],
+            ),
+            ("a::b", &[1, 3, 4], &[1, 3, 4], &[3, 1, 0], &[3, 1, 0]),
+            (
+                "foo  bar.baz",
+                &[5, 8, 9, 12],
+                &[3, 8, 9, 12],
+                &[9, 8, 5, 0],
+                &[9, 8, 3, 0],
+            ),
+            (
+                "x = (y, z);",
+                &[2, 4, 5, 6, 8, 9, 11],
+                &[1, 3, 5, 6, 7, 9, 11],
+                &[9, 8, 6, 5, 4, 2, 0],
+                &[9, 7, 6, 5, 3, 1, 0],
+            ),
+            (
+                "snake_case.call()",
+                &[10, 11, 15, 17],
+                &[10, 11, 15, 17],
+                &[15, 11, 10, 0],
+                &[15, 11, 10, 0],
+            ),
+        ];
+
+        for (text, word, word_end, prev, prev_end) in table {
+            let end = text.chars().count();
+            assert_eq!(stops(text, 0, next_word), word, "w in {text:?}");
+            assert_eq!(stops(text, 0, next_word_end), word_end, "W in {text:?}");
+            assert_eq!(stops(text, end, prev_word), prev, "b in {text:?}");
+            assert_eq!(stops(text, end, prev_word_end), prev_end, "B in {text:?}");
+        }
+    }
+
+    #[test]
+    fn word_chars_join_snake_and_kebab_case() {
+        let text = "snake_case kebab-case";
+        let end = text.chars().count();
+
+        // Only '_' is part of a word by default.
+        let mut doc = Document::new(0, 0, Some(text.to_string()));
+        let mut stops = Vec::new();
+        for _ in 0..4 {
+            next_word(&mut doc, 1);
+            stops.push(doc.cur.x);
+        }
+        assert_eq!(stops, [11, 16, 17, 21]);
+
+        doc.set_word_chars("_-");
+        doc.cur = Cursor::new(0, 0);
+        next_word(&mut doc, 1);
+        assert_eq!(doc.cur.x, 11);
+        next_word_end(&mut doc, 1);
+        assert_eq!(doc.cur.x, end);
+        prev_word(&mut doc, 1);
+        assert_eq!(doc.cur.x, 11);
+
+        // Without additional word chars '_' splits words as well.
+        doc.set_word_chars("");
+        prev_word_end(&mut doc, 1);
+        assert_eq!(doc.cur.x, 10);
+        prev_word(&mut doc, 1);
+        assert_eq!(doc.cur.x, 6);
+    }
+
+    #[test]
+    fn word_motions_stop_on_empty_lines() {
+        /// Repeats a motion, returning the positions it stops at until it stops moving.
+        fn positions(doc: &mut Document, motion: fn(&mut Document, usize)) -> Vec<(usize, usize)> {
+            let mut positions = Vec::new();
+            loop {
+                let prev = doc.cur;
+                motion(doc, 1);
+                if doc.cur == prev {
+                    return positions;
+                }
+                positions.push((doc.cur.x, doc.cur.y));
+            }
+        }
+
+        let mut doc = Document::new(0, 0, Some(String::from("foo\n\n\nbar")));
+        assert_eq!(
+            positions(&mut doc, next_word),
+            [(0, 1), (0, 2), (0, 3), (3, 3)]
+        );
+        assert_eq!(
+            positions(&mut doc, prev_word),
+            [(0, 3), (0, 2), (0, 1), (0, 0)]
+        );
+
+        // Lines of whitespace are not empty and skipped like any whitespace.
+        let mut doc = Document::new(0, 0, Some(String::from("foo\n  \n\tbar baz\n")));
+        assert_eq!(positions(&mut doc, next_word), [(1, 2), (5, 2), (0, 3)]);
+
+        // A count stops on every empty line in between.
+        let mut doc = Document::new(0, 0, Some(String::from("a\n\nb\n\nc")));
+        next_word(&mut doc, 3);
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 3));
+    }
+
+    #[test]
+    fn jump_to_column_lands_on_the_reported_visual_column() {
+        let mut doc = Document::new(0, 0, Some("\tab漢字cd\nnext".to_string()));
+
+        // The columns inside a tab or a wide character land on it.
+        for (col, x) in [
+            (0, 0),
+            (3, 0),
+            (4, 1),
+            (6, 3),
+            (7, 3),
+            (8, 4),
+            (10, 5),
+            (40, 7),
+        ] {
+            jump_to_column(&mut doc, col);
+            assert_eq!((doc.cur.x, doc.cur.y), (x, 0), "column {col}");
+        }
+
+        // Landing on the start of a character reports that column again.
+        for col in [0, 4, 5, 6, 8, 10] {
+            jump_to_column(&mut doc, col);
+            assert_eq!(doc.line_width(0, doc.cur.x), col);
+        }
+    }
+}
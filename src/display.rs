@@ -1,11 +1,17 @@
-use std::io::{BufWriter, Error, Stdout, Write};
+use std::io::{Error, Write};
 use termion::{
     color::{self, Bg, Fg, Reset},
-    cursor::{Goto, Hide, Show, SteadyBar, SteadyBlock},
-    raw::RawTerminal,
+    cursor::{
+        BlinkingBar, BlinkingBlock, BlinkingUnderline, Goto, Hide, Show, SteadyBar, SteadyBlock,
+        SteadyUnderline,
+    },
 };
 
-use crate::cursor::{Cursor, CursorStyle};
+use crate::{
+    cursor::{Cursor, CursorStyle},
+    viewport::{BG, HIGHLIGHT},
+};
+use unicode_width::UnicodeWidthChar;
 
 /// Use the placeholder U+FFFF value to indicate a cell is taken by wide characters.
 pub const PLACEHOLDER: char = '\u{FFFF}';
@@ -14,6 +20,23 @@ pub const PLACEHOLDER: char = '\u{FFFF}';
 const NO_TXT: Fg<Reset> = Fg(Reset);
 /// Reset background color.
 const NO_BG: Bg<Reset> = Bg(Reset);
+/// Begins a synchronized update, the terminal holds back rendering until the update ends.
+const BEGIN_SYNC: &str = "\x1b[?2026h";
+/// Ends a synchronized update.
+const END_SYNC: &str = "\x1b[?2026l";
+/// Turns off all text attributes.
+const NO_ATTRS: &str = "\x1b[22;23;24;27m";
+
+/// How the display draws colors.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Colors are drawn as RGB.
+    TrueColor,
+    /// Colors are approximated using the 256-color palette.
+    Palette,
+    /// No colors are drawn, highlighted cells are drawn in reverse video instead.
+    Monochrome,
+}
 
 /// A display buffer.
 pub struct Display {
@@ -31,10 +54,16 @@ pub struct Display {
 
     /// Issuing a full redraw on resize.
     full_redraw: bool,
+    /// If the terminal supports synchronized updates.
+    sync: bool,
+    /// How colors are drawn.
+    colors: ColorMode,
+    /// Counter that changes whenever the contents of the display can't be trusted to be up to date by renderers.
+    epoch: usize,
 }
 
 impl Display {
-    pub fn new(w: usize, h: usize) -> Self {
+    pub fn new(w: usize, h: usize, sync: bool, colors: ColorMode) -> Self {
         Self {
             buff: vec![vec![Cell::default(); w]; h],
             redraw: Vec::new(),
@@ -42,9 +71,22 @@ impl Display {
             w,
             h,
             full_redraw: false,
+            sync,
+            colors,
+            epoch: 0,
         }
     }
 
+    /// Returns the current epoch of the display.
+    pub const fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Invalidates the contents of the display, forcing renderers to update every cell.
+    pub const fn invalidate(&mut self) {
+        self.epoch = self.epoch.wrapping_add(1);
+    }
+
     /// Resizes the display.
     pub fn resize(&mut self, w: usize, h: usize) {
         let mut redraw = false;
@@ -68,6 +110,27 @@ impl Display {
 
         // Redraw everything on resize.
         self.full_redraw = redraw;
+        if redraw {
+            self.invalidate();
+        }
+    }
+
+    /// Sets if colors are drawn as RGB or approximated using the 256-color palette. Has no effect if colors are
+    /// disabled. Redraws everything on changes.
+    pub fn set_truecolor(&mut self, truecolor: bool) {
+        if self.colors == ColorMode::Monochrome {
+            return;
+        }
+
+        let colors = if truecolor {
+            ColorMode::TrueColor
+        } else {
+            ColorMode::Palette
+        };
+        if self.colors != colors {
+            self.colors = colors;
+            self.full_redraw = true;
+        }
     }
 
     /// Updates a cell in the display.
@@ -83,30 +146,35 @@ impl Display {
         self.cursor = (cursor, style);
     }
 
-    /// Draws the display to the terminal.
-    pub fn draw(&mut self, stdout: &mut BufWriter<RawTerminal<Stdout>>) -> Result<(), Error> {
+    /// Draws the display to the terminal, or any other writer taking the escape sequences.
+    pub fn draw(&mut self, stdout: &mut impl Write) -> Result<(), Error> {
+        // Wrap all writes in a synchronized update to avoid tearing.
+        if self.sync {
+            write!(stdout, "{BEGIN_SYNC}")?;
+        }
+
         // Hide the cursor to avoid it flickering over the screen.
         write!(stdout, "{Hide}")?;
 
+        // Store the last used colors and the cursor position to not write them for every character.
+        let mut state = DrawState::default();
         if self.full_redraw {
             write!(stdout, "{NO_TXT}{NO_BG}")?;
             write!(stdout, "{}", termion::clear::All)?;
 
-            // Store last used colors to not write the color for ever character.
-            let mut last_fg: Option<Fg<color::Rgb>> = None;
-            let mut last_bg: Option<Bg<color::Rgb>> = None;
             for y in 0..self.h {
                 for x in 0..self.w {
-                    self.draw_cell(x, y, &mut last_fg, &mut last_bg, stdout)?;
+                    self.draw_cell(x, y, &mut state, stdout)?;
                 }
             }
             self.full_redraw = false;
         } else if !self.redraw.is_empty() {
-            // Store last used colors to not write the color for ever character.
-            let mut last_fg: Option<Fg<color::Rgb>> = None;
-            let mut last_bg: Option<Bg<color::Rgb>> = None;
+            // Sort the cells by row so that runs of adjacent cells can be written without moving the cursor.
+            self.redraw.sort_unstable_by_key(|&(x, y)| (y, x));
+            self.redraw.dedup();
+
             for (x, y) in &self.redraw {
-                self.draw_cell(*x, *y, &mut last_fg, &mut last_bg, stdout)?;
+                self.draw_cell(*x, *y, &mut state, stdout)?;
             }
         }
         // Clear the redraw buffer in any case since it contains stale data on full redraw.
@@ -120,10 +188,17 @@ impl Display {
         match self.cursor.1 {
             CursorStyle::Hidden => {}
             CursorStyle::SteadyBar => write!(stdout, "{cur}{SteadyBar}{Show}")?,
+            CursorStyle::BlinkingBar => write!(stdout, "{cur}{BlinkingBar}{Show}")?,
             CursorStyle::SteadyBlock => write!(stdout, "{cur}{SteadyBlock}{Show}")?,
+            CursorStyle::BlinkingBlock => write!(stdout, "{cur}{BlinkingBlock}{Show}")?,
+            CursorStyle::SteadyUnderline => write!(stdout, "{cur}{SteadyUnderline}{Show}")?,
+            CursorStyle::BlinkingUnderline => write!(stdout, "{cur}{BlinkingUnderline}{Show}")?,
         }
 
-        write!(stdout, "{NO_TXT}{NO_BG}")?;
+        write!(stdout, "{NO_TXT}{NO_BG}{NO_ATTRS}")?;
+        if self.sync {
+            write!(stdout, "{END_SYNC}")?;
+        }
         stdout.flush()
     }
 
@@ -131,37 +206,179 @@ impl Display {
         &self,
         x: usize,
         y: usize,
-        last_fg: &mut Option<Fg<color::Rgb>>,
-        last_bg: &mut Option<Bg<color::Rgb>>,
-        stdout: &mut BufWriter<RawTerminal<Stdout>>,
+        state: &mut DrawState,
+        stdout: &mut impl Write,
     ) -> Result<(), Error> {
-        let Cell { ch, fg, bg, .. } = self.buff[y][x];
+        let Cell { ch, fg, bg, attrs } = self.buff[y][x];
 
         if ch == PLACEHOLDER {
             return Ok(());
         }
 
-        // The indices are bound by terminal dimensions.
-        #[allow(clippy::cast_possible_truncation)]
-        write!(stdout, "{}", Goto(x as u16 + 1, y as u16 + 1))?;
+        // Only move the cursor if the cell doesn't directly follow the previously written one.
+        if state.pos != Some((x, y)) {
+            // The indices are bound by terminal dimensions.
+            #[allow(clippy::cast_possible_truncation)]
+            write!(stdout, "{}", Goto(x as u16 + 1, y as u16 + 1))?;
+        }
 
-        // Write colors if necessary.
-        match last_fg {
+        if self.colors == ColorMode::Monochrome {
+            // Without colors only the cursor line keeps the plain look, any other background marks a highlight.
+            let invert = bg.0 != BG.0 && bg.0 != HIGHLIGHT.0;
+            let reverse = attrs.contains(Attrs::REVERSE) != invert;
+            draw_attrs(attrs.with(Attrs::REVERSE, reverse), state, stdout)?;
+        } else {
+            self.draw_colors(fg, bg, state, stdout)?;
+            draw_attrs(attrs, state, stdout)?;
+        }
+
+        // Characters without a known width leave the cursor at an unknown position.
+        state.pos = match ch.width() {
+            Some(width) if width > 0 => Some((x + width, y)),
+            _ => None,
+        };
+
+        write!(stdout, "{ch}")
+    }
+
+    /// Writes the colors of a cell if they differ from the last written ones.
+    fn draw_colors(
+        &self,
+        fg: Fg<color::Rgb>,
+        bg: Bg<color::Rgb>,
+        state: &mut DrawState,
+        stdout: &mut impl Write,
+    ) -> Result<(), Error> {
+        match state.fg {
             Some(last_fg) if last_fg.0 == fg.0 => {}
-            _ => {
+            _ if self.colors == ColorMode::TrueColor => {
                 write!(stdout, "{fg}")?;
-                *last_fg = Some(fg);
+                state.fg = Some(fg);
+            }
+            _ => {
+                write!(stdout, "{}", Fg(ansi256(fg.0)))?;
+                state.fg = Some(fg);
             }
         }
-        match last_bg {
+        match state.bg {
             Some(last_bg) if last_bg.0 == bg.0 => {}
-            _ => {
+            _ if self.colors == ColorMode::TrueColor => {
                 write!(stdout, "{bg}")?;
-                *last_bg = Some(bg);
+                state.bg = Some(bg);
+            }
+            _ => {
+                write!(stdout, "{}", Bg(ansi256(bg.0)))?;
+                state.bg = Some(bg);
             }
         }
 
-        write!(stdout, "{ch}")
+        Ok(())
+    }
+}
+
+/// Writes the text attributes of a cell if they differ from the last written ones. Only the changed attributes are
+/// turned on or off.
+fn draw_attrs(attrs: Attrs, state: &mut DrawState, stdout: &mut impl Write) -> Result<(), Error> {
+    let codes = Attrs::CODES
+        .iter()
+        .filter(|(attr, ..)| {
+            state
+                .attrs
+                .is_none_or(|last| last.contains(*attr) != attrs.contains(*attr))
+        })
+        .map(|&(attr, on, off)| if attrs.contains(attr) { on } else { off })
+        .map(|code| code.to_string())
+        .collect::<Vec<_>>();
+    if !codes.is_empty() {
+        write!(stdout, "\x1b[{}m", codes.join(";"))?;
+    }
+    state.attrs = Some(attrs);
+
+    Ok(())
+}
+
+/// Approximates an RGB color with the closest color of the 6x6x6 color cube or the grayscale ramp of the 256-color
+/// palette.
+fn ansi256(rgb: color::Rgb) -> color::AnsiValue {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    let color::Rgb(r, g, b) = rgb;
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(r, cr) + d(g, cg) + d(b, cb)
+    };
+
+    // The index of the closest level of the color cube for each channel.
+    let level = |c: u8| match c {
+        0..48 => 0,
+        48..115 => 1,
+        _ => (c - 35) / 40,
+    };
+    let (lr, lg, lb) = (level(r), level(g), level(b));
+    let cube = (
+        LEVELS[lr as usize],
+        LEVELS[lg as usize],
+        LEVELS[lb as usize],
+    );
+
+    // The grayscale ramp goes from 8 to 238 in steps of 10.
+    // The average of three channels fits into a channel.
+    #[allow(clippy::cast_possible_truncation)]
+    let avg = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let gray_idx = (avg.saturating_sub(3) / 10).min(23);
+    let gray = 8 + 10 * gray_idx;
+
+    if distance((gray, gray, gray)) < distance(cube) {
+        color::AnsiValue(232 + gray_idx)
+    } else {
+        color::AnsiValue(16 + 36 * lr + 6 * lg + lb)
+    }
+}
+
+/// The state of the terminal while drawing, used to avoid writing redundant escape sequences.
+#[derive(Default)]
+struct DrawState {
+    /// The last written text color.
+    fg: Option<Fg<color::Rgb>>,
+    /// The last written background color.
+    bg: Option<Bg<color::Rgb>>,
+    /// The last written text attributes.
+    attrs: Option<Attrs>,
+    /// The position of the terminal cursor after the last written character.
+    pos: Option<(usize, usize)>,
+}
+
+/// Text attributes of a cell, a set of flags combined with `with`.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Attrs(u8);
+
+impl Attrs {
+    pub const NONE: Self = Self(0);
+    pub const BOLD: Self = Self(1);
+    pub const ITALIC: Self = Self(1 << 1);
+    pub const UNDERLINE: Self = Self(1 << 2);
+    pub const REVERSE: Self = Self(1 << 3);
+
+    /// The attributes with the SGR codes turning them on and off.
+    const CODES: [(Self, u8, u8); 4] = [
+        (Self::BOLD, 1, 22),
+        (Self::ITALIC, 3, 23),
+        (Self::UNDERLINE, 4, 24),
+        (Self::REVERSE, 7, 27),
+    ];
+
+    /// Checks if all attributes of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the attributes with the attributes of `other` set or unset.
+    pub const fn with(self, other: Self, set: bool) -> Self {
+        if set {
+            Self(self.0 | other.0)
+        } else {
+            Self(self.0 & !other.0)
+        }
     }
 }
 
@@ -174,11 +391,24 @@ pub struct Cell {
     pub fg: Fg<color::Rgb>,
     /// The background color at that cell.
     pub bg: Bg<color::Rgb>,
+    /// The text attributes at that cell.
+    pub attrs: Attrs,
 }
 
 impl Cell {
     pub const fn new(ch: char, fg: Fg<color::Rgb>, bg: Bg<color::Rgb>) -> Self {
-        Self { ch, fg, bg }
+        Self {
+            ch,
+            fg,
+            bg,
+            attrs: Attrs::NONE,
+        }
+    }
+
+    /// Returns the cell with text attributes.
+    pub const fn with_attrs(mut self, attrs: Attrs) -> Self {
+        self.attrs = attrs;
+        self
     }
 }
 
@@ -188,12 +418,184 @@ impl Default for Cell {
             ch: '\0',
             fg: Fg(color::Rgb(0, 0, 0)),
             bg: Bg(color::Rgb(0, 0, 0)),
+            attrs: Attrs::NONE,
         }
     }
 }
 
 impl PartialEq for Cell {
     fn eq(&self, other: &Self) -> bool {
-        self.ch == other.ch && self.fg.0 == other.fg.0 && self.bg.0 == other.bg.0
+        self.ch == other.ch
+            && self.fg.0 == other.fg.0
+            && self.bg.0 == other.bg.0
+            && self.attrs == other.attrs
+    }
+}
+
+/// The screen of a terminal the output of a `Display` was drawn to, for tests to assert on the resulting cells.
+#[cfg(test)]
+pub struct Screen(vt100::Parser);
+
+#[cfg(test)]
+impl Screen {
+    /// Interprets the escape sequences drawn to `out` like a terminal of the size.
+    pub fn new(out: &[u8], w: usize, h: usize) -> Self {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut parser = vt100::Parser::new(h as u16, w as u16, 0);
+        parser.process(out);
+
+        Self(parser)
+    }
+
+    /// Returns the rows of the screen without trailing whitespace.
+    pub fn rows(&self) -> Vec<String> {
+        let screen = self.0.screen();
+        let (_, w) = screen.size();
+
+        screen
+            .rows(0, w)
+            .map(|row| row.trim_end().to_string())
+            .collect()
+    }
+
+    /// Returns the row and column of the cursor.
+    pub fn cursor(&self) -> (u16, u16) {
+        self.0.screen().cursor_position()
+    }
+
+    /// Returns the cell at a row and column.
+    pub fn cell(&self, row: u16, col: u16) -> &vt100::Cell {
+        self.0.screen().cell(row, col).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::viewport::{SEL, TXT};
+
+    /// Draws the display to memory.
+    fn draw(display: &mut Display) -> Vec<u8> {
+        let mut out = Vec::new();
+        display.draw(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn draws_runs_of_cells_without_moving_the_cursor() {
+        let mut display = Display::new(5, 2, false, ColorMode::Monochrome);
+        display.update(Cell::new('a', TXT, BG), 1, 0);
+        display.update(Cell::new('b', TXT, BG), 2, 0);
+        display.update(Cell::new('c', TXT, BG), 0, 1);
+
+        let out = String::from_utf8(draw(&mut display)).unwrap();
+        assert!(out.contains("ab\x1b[2;1Hc"), "{out:?}");
+        assert_eq!(Screen::new(out.as_bytes(), 5, 2).rows(), [" ab", "c"]);
+    }
+
+    #[test]
+    fn only_changed_cells_are_redrawn() {
+        let mut display = Display::new(5, 1, false, ColorMode::Monochrome);
+        display.update(Cell::new('a', TXT, BG), 0, 0);
+        display.update(Cell::new('b', TXT, BG), 1, 0);
+        let mut out = draw(&mut display);
+
+        // Updating a cell to its current contents is not a change.
+        display.update(Cell::new('a', TXT, BG), 0, 0);
+        display.update(Cell::new('c', TXT, BG), 1, 0);
+        let redraw = String::from_utf8(draw(&mut display)).unwrap();
+        assert!(redraw.contains('c') && !redraw.contains('a'), "{redraw:?}");
+
+        out.extend(redraw.bytes());
+        assert_eq!(Screen::new(&out, 5, 1).rows(), ["ac"]);
+    }
+
+    #[test]
+    fn wide_characters_cover_their_placeholder() {
+        let mut display = Display::new(5, 1, false, ColorMode::Monochrome);
+        display.update(Cell::new('漢', TXT, BG), 0, 0);
+        display.update(Cell::new(PLACEHOLDER, TXT, BG), 1, 0);
+        display.update(Cell::new('x', TXT, BG), 2, 0);
+
+        let out = String::from_utf8(draw(&mut display)).unwrap();
+        assert!(!out.contains(PLACEHOLDER), "{out:?}");
+        assert_eq!(Screen::new(out.as_bytes(), 5, 1).rows(), ["漢x"]);
+    }
+
+    #[test]
+    fn colors_follow_the_color_mode() {
+        let cell = Cell::new('a', TXT, SEL);
+
+        let mut display = Display::new(1, 1, false, ColorMode::TrueColor);
+        display.update(cell.clone(), 0, 0);
+        let screen = Screen::new(&draw(&mut display), 1, 1);
+        assert_eq!(
+            screen.cell(0, 0).fgcolor(),
+            vt100::Color::Rgb(172, 178, 190)
+        );
+        assert_eq!(screen.cell(0, 0).bgcolor(), vt100::Color::Rgb(75, 78, 87));
+
+        let mut display = Display::new(1, 1, false, ColorMode::Palette);
+        display.update(cell.clone(), 0, 0);
+        let screen = Screen::new(&draw(&mut display), 1, 1);
+        assert_eq!(screen.cell(0, 0).fgcolor(), vt100::Color::Idx(249));
+        assert_eq!(screen.cell(0, 0).bgcolor(), vt100::Color::Idx(239));
+
+        // Without colors highlights are drawn in reverse video, the plain background is not.
+        let mut display = Display::new(2, 1, false, ColorMode::Monochrome);
+        display.update(cell, 0, 0);
+        display.update(Cell::new('b', TXT, BG), 1, 0);
+        let screen = Screen::new(&draw(&mut display), 2, 1);
+        assert_eq!(screen.cell(0, 0).fgcolor(), vt100::Color::Default);
+        assert!(screen.cell(0, 0).inverse());
+        assert!(!screen.cell(0, 1).inverse());
+    }
+
+    #[test]
+    fn attributes_are_turned_off_again() {
+        let mut display = Display::new(2, 1, false, ColorMode::Monochrome);
+        display.update(Cell::new('a', TXT, BG).with_attrs(Attrs::BOLD), 0, 0);
+        display.update(Cell::new('b', TXT, BG), 1, 0);
+
+        let screen = Screen::new(&draw(&mut display), 2, 1);
+        assert!(screen.cell(0, 0).bold());
+        assert!(!screen.cell(0, 1).bold());
+    }
+
+    #[test]
+    fn cursor_is_drawn_last_with_its_style() {
+        let mut display = Display::new(5, 2, true, ColorMode::Monochrome);
+        display.update(Cell::new('a', TXT, BG), 0, 0);
+        display.set_cursor(Cursor::new(3, 1), CursorStyle::SteadyBar);
+
+        let out = draw(&mut display);
+        assert_eq!(Screen::new(&out, 5, 2).cursor(), (1, 3));
+
+        // The update is synchronized and ends with the cursor shown.
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with(BEGIN_SYNC) && out.ends_with(END_SYNC),
+            "{out:?}"
+        );
+        assert!(
+            out.contains(&format!("{}{SteadyBar}{Show}", Goto(4, 2))),
+            "{out:?}"
+        );
+    }
+
+    #[test]
+    fn resize_clears_and_redraws_everything() {
+        let mut display = Display::new(2, 1, false, ColorMode::Monochrome);
+        display.update(Cell::new('a', TXT, BG), 0, 0);
+        display.update(Cell::new('b', TXT, BG), 1, 0);
+        draw(&mut display);
+
+        let epoch = display.epoch();
+        display.resize(3, 1);
+        assert_ne!(display.epoch(), epoch);
+
+        let out = String::from_utf8(draw(&mut display)).unwrap();
+        assert!(out.contains(&termion::clear::All.to_string()), "{out:?}");
+        assert_eq!(Screen::new(out.as_bytes(), 3, 1).rows(), ["ab"]);
     }
 }
@@ -1,10 +1,25 @@
-use crate::cursor::Cursor;
-use ropey::{Rope, RopeSlice, iter::Lines};
+use crate::{
+    cursor::Cursor,
+    util::{VisualCells, visual_cells},
+};
+use ropey::{
+    Rope, RopeSlice,
+    iter::{Chars, Lines},
+};
 use std::{
+    collections::BTreeSet,
     fs::File,
     io::{BufWriter, Error, Seek, SeekFrom, Write},
 };
 
+/// The class of a character used by word motions. Words are runs of characters of the same class.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
 // The document of a buffer containing its contents.
 pub struct Document {
     // The buffer contents.
@@ -13,14 +28,50 @@ pub struct Document {
     pub cur: Cursor,
     // Flag if the buffer was modified.
     pub edited: bool,
+    // Lines that were modified since the last render.
+    dirty: BTreeSet<usize>,
+    // All lines starting at this line were modified since the last render.
+    dirty_from: Option<usize>,
+    // Flags for every line if it was modified since the document was last written.
+    changed: Vec<bool>,
+    // Characters besides alphanumeric ones that are part of a word.
+    word_chars: String,
 }
 
 impl Document {
     pub fn new(x: usize, y: usize, contents: Option<String>) -> Self {
+        let rope = Rope::from_str(contents.unwrap_or_default().as_str());
+        let changed = vec![false; rope.len_lines()];
+
         Self {
-            rope: Rope::from_str(contents.unwrap_or_default().as_str()),
+            rope,
             cur: Cursor::new(x, y),
             edited: false,
+            dirty: BTreeSet::new(),
+            dirty_from: Some(0),
+            changed,
+            word_chars: String::from("_"),
+        }
+    }
+
+    /// Sets the characters besides alphanumeric ones that are part of a word.
+    pub fn set_word_chars(&mut self, chars: &str) {
+        chars.clone_into(&mut self.word_chars);
+    }
+
+    /// Checks if a character is part of a word.
+    pub fn is_word_char(&self, ch: char) -> bool {
+        ch.is_alphanumeric() || self.word_chars.contains(ch)
+    }
+
+    /// Returns the class of a character.
+    pub fn char_class(&self, ch: char) -> CharClass {
+        if ch.is_whitespace() {
+            CharClass::Whitespace
+        } else if self.is_word_char(ch) {
+            CharClass::Word
+        } else {
+            CharClass::Punctuation
         }
     }
 
@@ -29,9 +80,49 @@ impl Document {
         self.rope = Rope::from_str(buff);
         self.cur = Cursor::new(0, 0);
         self.edited = false;
+        self.dirty.clear();
+        self.dirty_from = Some(0);
+        self.changed = vec![false; self.rope.len_lines()];
     }
 
-    /// Returns the number of lines.
+    /// Gets the line ending format of the document, detected from its first line.
+    pub fn line_ending(&self) -> &'static str {
+        match self.rope.get_line(0) {
+            Some(line) if line.len_chars() >= 2 && line.char(line.len_chars() - 2) == '\r' => {
+                "crlf"
+            }
+            _ => "lf",
+        }
+    }
+
+    /// Checks if a line was modified since the last render.
+    pub fn is_dirty(&self, y: usize) -> bool {
+        self.dirty_from.is_some_and(|from| y >= from) || self.dirty.contains(&y)
+    }
+
+    /// Checks if any line was modified since the last render.
+    pub fn has_dirty(&self) -> bool {
+        self.dirty_from.is_some() || !self.dirty.is_empty()
+    }
+
+    /// Marks all lines as rendered.
+    pub fn clear_dirty(&mut self) {
+        self.dirty.clear();
+        self.dirty_from = None;
+    }
+
+    /// Marks the lines affected by an edit in line y as dirty. If the amount of lines changed, all following lines
+    /// are shifted and thus dirty as well.
+    fn mark_dirty(&mut self, y: usize, prev_len: usize) {
+        if self.rope.len_lines() == prev_len {
+            self.dirty.insert(y);
+        } else {
+            self.dirty_from = Some(self.dirty_from.map_or(y, |from| from.min(y)));
+        }
+    }
+
+    /// Returns the number of lines. This is never zero, the rope always has at least one possibly empty line, so an
+    /// empty document consists of a single empty line.
     pub fn len(&self) -> usize {
         self.rope.len_lines()
     }
@@ -59,6 +150,47 @@ impl Document {
         self.rope.get_line(y)
     }
 
+    /// Calculates the visual width of a line up to a character index without copying the line.
+    pub fn line_width(&self, y: usize, char_idx: usize) -> usize {
+        self.visual_cells(y)
+            .map_or(0, |cells| cells.take(char_idx).map(|cell| cell.width).sum())
+    }
+
+    /// Returns an iterator over the visual cells of a line.
+    pub fn visual_cells(&self, y: usize) -> Option<VisualCells<Chars<'_>>> {
+        self.rope.get_line(y).map(|line| visual_cells(line.chars()))
+    }
+
+    /// Checks if a line was modified since the document was last written.
+    pub fn is_changed(&self, y: usize) -> bool {
+        self.changed.get(y).copied().unwrap_or(false)
+    }
+
+    /// Marks all lines as written.
+    pub fn clear_changed(&mut self) {
+        self.changed.fill(false);
+    }
+
+    /// Marks the lines affected by an edit in line y as changed since the last write. Added lines are inserted after
+    /// line y, or before it if the edit only prepended whole lines to it. Removed lines were merged into line y.
+    fn mark_changed(&mut self, y: usize, prev_len: usize, prepended: bool) {
+        let len = self.rope.len_lines();
+        if len >= prev_len {
+            let at = if prepended { y } else { y + 1 }.min(self.changed.len());
+            self.changed
+                .splice(at..at, std::iter::repeat_n(true, len - prev_len));
+            if !prepended && let Some(changed) = self.changed.get_mut(y) {
+                *changed = true;
+            }
+        } else {
+            let end = (y + 1 + prev_len - len).min(self.changed.len());
+            self.changed.drain((y + 1).min(end)..end);
+            if let Some(changed) = self.changed.get_mut(y) {
+                *changed = true;
+            }
+        }
+    }
+
     /// Returns an iterator over the lines of the document.
     pub fn lines(&self) -> Lines<'_> {
         self.rope.lines()
@@ -77,18 +209,30 @@ impl Document {
         file.flush()?;
 
         self.edited = false;
+        self.clear_changed();
         Ok(())
     }
 
+    /// Writes the document contents to a writer.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), Error> {
+        self.rope.write_to(writer)
+    }
+
     /// Inserts a new line at a specified y position.
     pub fn insert_line(&mut self, y: usize) {
+        let len = self.rope.len_lines();
         self.rope.insert(self.rope.line_to_char(y), "\n");
+        self.mark_dirty(y, len);
+        self.mark_changed(y, len, true);
         self.edited = true;
     }
 
     /// Writes a char at a specified position.
     pub fn write_char(&mut self, ch: char, x: usize, y: usize) {
+        let len = self.rope.len_lines();
         self.rope.insert_char(self.xy_to_idx(x, y), ch);
+        self.mark_dirty(y, len);
+        self.mark_changed(y, len, x == 0 && ch == '\n');
         self.edited = true;
     }
 
@@ -97,7 +241,10 @@ impl Document {
         let idx = self.xy_to_idx(x, y);
         let ch = self.rope.char(idx);
 
+        let len = self.rope.len_lines();
         self.rope.remove(idx..=idx);
+        self.mark_dirty(y, len);
+        self.mark_changed(y, len, false);
         self.edited = true;
 
         ch
@@ -110,37 +257,96 @@ impl Document {
 
     /// Writes a str at a specified position.
     pub fn write_str_at(&mut self, x: usize, y: usize, str: &str) {
+        let len = self.rope.len_lines();
         self.rope.insert(self.xy_to_idx(x, y), str);
+        self.mark_dirty(y, len);
+        self.mark_changed(y, len, x == 0 && str.ends_with('\n'));
         self.edited = true;
     }
 
     /// Appends a str at the end of the document.
     pub fn append_str(&mut self, str: &str) {
+        let len = self.rope.len_lines();
         self.rope.insert(self.rope.len_chars(), str);
+        self.mark_dirty(len - 1, len);
+        self.mark_changed(len - 1, len, false);
         self.edited = true;
     }
 
-    /// Gets a range of text from the document.
+    /// Gets a range of text from the document. Positions outside of the document are clamped to its bounds. Returns
+    /// `None` only if the document is empty.
     pub fn get_range(&self, pos1: Cursor, pos2: Cursor) -> Option<RopeSlice<'_>> {
+        if self.rope.len_chars() == 0 {
+            return None;
+        }
+
         let start = pos1.min(pos2);
         let end = pos1.max(pos2);
 
-        let start_idx = self.xy_to_idx(start.x, start.y);
-        let end_idx = self.xy_to_idx(end.x, end.y);
+        let start_idx = self.clamped_xy_to_idx(start.x, start.y);
+        let end_idx = self.clamped_xy_to_idx(end.x, end.y);
 
         self.rope.get_slice(start_idx..end_idx)
     }
 
-    /// Removes a range of text from the document.
+    /// Converts (x, y) coordinates to a rope index, clamping them to the bounds of the document. Columns past the end
+    /// of a line are clamped to the end of the line including its line break.
+    fn clamped_xy_to_idx(&self, x: usize, y: usize) -> usize {
+        if y >= self.rope.len_lines() {
+            return self.rope.len_chars();
+        }
+
+        self.rope.line_to_char(y) + x.min(self.rope.line(y).len_chars())
+    }
+
+    /// Removes a range of text from the document. Positions are clamped like in `get_range`, so the removed text is
+    /// always the text `get_range` returns.
     pub fn remove_range(&mut self, pos1: Cursor, pos2: Cursor) {
         let start = pos1.min(pos2);
         let end = pos1.max(pos2);
 
-        let start_idx = self.xy_to_idx(start.x, start.y);
-        let end_idx = self.xy_to_idx(end.x, end.y);
+        let start_idx = self.clamped_xy_to_idx(start.x, start.y);
+        let end_idx = self.clamped_xy_to_idx(end.x, end.y);
 
+        let len = self.rope.len_lines();
         self.rope.remove(start_idx..end_idx);
+        self.mark_dirty(start.y, len);
+        self.mark_changed(start.y, len, false);
         self.edited = true;
+
+        // At least one line always remains, keep the cursor on an existing one.
+        if self.cur.y >= self.len() {
+            let y = self.len() - 1;
+            self.cur = Cursor::new(self.cur.x.min(self.rope.line(y).len_chars()), y);
+        }
+    }
+
+    /// Replaces a range of text with a str in one step. Positions are clamped like in `remove_range`. Returns the position
+    /// after the inserted str.
+    pub fn replace_range(&mut self, pos1: Cursor, pos2: Cursor, str: &str) -> Cursor {
+        let start = pos1.min(pos2);
+        let end = pos1.max(pos2);
+
+        let start_idx = self.clamped_xy_to_idx(start.x, start.y);
+        let end_idx = self.clamped_xy_to_idx(end.x, end.y);
+
+        let len = self.rope.len_lines();
+        self.rope.remove(start_idx..end_idx);
+        self.rope.insert(start_idx, str);
+        self.mark_dirty(start.y, len);
+        self.mark_changed(start.y, len, false);
+        self.edited = true;
+
+        let (x, y) = self.idx_to_xy(start_idx + str.chars().count());
+        // The lines after the first one that were replaced in place changed as well.
+        for y in start.y + 1..=y {
+            self.dirty.insert(y);
+            if let Some(changed) = self.changed.get_mut(y) {
+                *changed = true;
+            }
+        }
+
+        Cursor::new(x, y)
     }
 
     /// Converts (x, y) coordinates to a rope index.
@@ -155,3 +361,60 @@ impl Document {
         (x, y)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removing_everything_leaves_one_empty_line() {
+        let mut doc = Document::new(0, 0, Some(String::from("abc\ndef\n")));
+        doc.cur = Cursor::new(1, 2);
+
+        doc.remove_range(Cursor::new(0, 0), Cursor::new(usize::MAX, usize::MAX));
+
+        assert_eq!(doc.len(), 1);
+        assert_eq!(doc.line(0).map(|line| line.len_chars()), Some(0));
+        assert_eq!((doc.cur.x, doc.cur.y), (0, 0));
+        assert!(doc.edited);
+    }
+
+    #[test]
+    fn empty_document_has_one_line() {
+        let mut doc = Document::new(0, 0, None);
+        assert_eq!(doc.len(), 1);
+        assert!(doc.line(0).is_some());
+
+        doc.from("");
+        assert_eq!(doc.len(), 1);
+        assert_eq!(doc.line_count(0), Some(0));
+        assert_eq!(doc.line_count(1), None);
+    }
+
+    #[test]
+    fn get_range_clamps_to_the_document() {
+        let doc = Document::new(0, 0, Some(String::from("abc\nde")));
+        let range = |x1, y1, x2, y2| {
+            doc.get_range(Cursor::new(x1, y1), Cursor::new(x2, y2))
+                .map(|slice| slice.to_string())
+        };
+
+        // Columns past the end of a line include its line break.
+        assert_eq!(range(1, 0, 10, 0).as_deref(), Some("bc\n"));
+        // Positions past the last line reach until the end of the document.
+        assert_eq!(range(1, 1, 0, 5).as_deref(), Some("e"));
+        assert_eq!(
+            range(0, 0, usize::MAX, usize::MAX).as_deref(),
+            Some("abc\nde")
+        );
+        // The order of the positions doesn't matter, a range fully outside of the document is empty.
+        assert_eq!(range(1, 1, 1, 0).as_deref(), Some("bc\nd"));
+        assert_eq!(range(0, 7, 3, 9).as_deref(), Some(""));
+
+        assert!(
+            Document::new(0, 0, None)
+                .get_range(Cursor::new(0, 0), Cursor::new(1, 1))
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::options::Options;
+use regex::Regex;
+use std::{collections::HashMap, fs, path::Path};
+
+/// A section of an `.editorconfig` file.
+struct Section {
+    /// The glob pattern of the files the section applies to.
+    glob: String,
+    /// The properties of the section.
+    properties: Vec<(String, String)>,
+}
+
+/// A parsed `.editorconfig` file.
+struct Config {
+    /// If the search for further `.editorconfig` files stops at this one.
+    root: bool,
+    /// The sections in order of appearance.
+    sections: Vec<Section>,
+}
+
+/// Applies the `.editorconfig` files of the directories containing a file to the options of its buffer. Files closer
+/// to the file take precedence, the search stops at a file marked with `root = true`.
+pub fn apply(path: &Path, options: &mut Options) {
+    let Ok(path) = std::path::absolute(path) else {
+        return;
+    };
+
+    let mut configs = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        let Ok(contents) = fs::read_to_string(dir.join(".editorconfig")) else {
+            continue;
+        };
+
+        let config = parse(&contents);
+        let root = config.root;
+        configs.push((dir, config));
+        if root {
+            break;
+        }
+    }
+
+    // Later matching sections override the properties of earlier ones.
+    let file = path.to_string_lossy();
+    let mut properties = HashMap::new();
+    for (dir, config) in configs.iter().rev() {
+        for section in &config.sections {
+            if matches(dir, &section.glob, &file) {
+                properties.extend(section.properties.iter().cloned());
+            }
+        }
+    }
+
+    let get = |key: &str| {
+        properties
+            .get(key)
+            .map(String::as_str)
+            .filter(|value| *value != "unset")
+    };
+
+    match get("indent_style") {
+        Some("space") => options.expandtab = true,
+        Some("tab") => options.expandtab = false,
+        _ => {}
+    }
+
+    // The indent size defaults to the tab width and vice versa.
+    let tab_width = get("tab_width").and_then(|value| value.parse().ok());
+    let indent_size = match get("indent_size") {
+        Some(value) if value != "tab" => value.parse().ok(),
+        _ => tab_width,
+    };
+    if let Some(size) = indent_size.filter(|size| *size > 0) {
+        options.shiftwidth = size;
+    }
+
+    match get("trim_trailing_whitespace") {
+        Some("true") => options.trimwhitespace = true,
+        Some("false") => options.trimwhitespace = false,
+        _ => {}
+    }
+    match get("insert_final_newline") {
+        Some("true") => options.fixeol = true,
+        Some("false") => options.fixeol = false,
+        _ => {}
+    }
+    if let Some(ending @ ("lf" | "crlf")) = get("end_of_line") {
+        options.fileformat = ending.to_string();
+    }
+}
+
+/// Parses the contents of an `.editorconfig` file. Keys and values are lowercased.
+fn parse(contents: &str) -> Config {
+    let mut config = Config {
+        root: false,
+        sections: Vec::new(),
+    };
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            config.sections.push(Section {
+                glob: glob.to_string(),
+                properties: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(['=', ':']) else {
+            continue;
+        };
+        let (key, value) = (key.trim().to_lowercase(), value.trim().to_lowercase());
+
+        match config.sections.last_mut() {
+            Some(section) => section.properties.push((key, value)),
+            // Properties before the first section belong to the preamble.
+            None if key == "root" => config.root = value == "true",
+            None => {}
+        }
+    }
+
+    config
+}
+
+/// Checks if a file matches the glob of a section of the `.editorconfig` file in `dir`. Globs without a slash match
+/// files in any subdirectory.
+fn matches(dir: &Path, glob: &str, file: &str) -> bool {
+    let dir = regex::escape(dir.to_string_lossy().trim_end_matches('/'));
+    let pattern = if glob.contains('/') {
+        format!("^{dir}/{}$", glob_to_regex(glob.trim_start_matches('/')))
+    } else {
+        format!("^{dir}/(?:.*/)?{}$", glob_to_regex(glob))
+    };
+
+    Regex::new(&pattern).is_ok_and(|re| re.is_match(file))
+}
+
+/// Converts a glob of an `.editorconfig` file to a regular expression. Supports `*`, `**`, `?`, `[...]`, `[!...]`,
+/// `{a,b}` and `{n1..n2}`.
+fn glob_to_regex(glob: &str) -> String {
+    let chars = glob.chars().collect::<Vec<_>>();
+    let mut re = String::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        match chars[idx] {
+            '*' if chars.get(idx + 1) == Some(&'*') => {
+                re.push_str(".*");
+                idx += 1;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '\\' if idx + 1 < chars.len() => {
+                idx += 1;
+                re.push_str(&regex::escape(&chars[idx].to_string()));
+            }
+            '[' => match chars[idx..].iter().position(|ch| *ch == ']') {
+                Some(len) => {
+                    let class = chars[idx + 1..idx + len].iter().collect::<String>();
+                    let class = class.strip_prefix('!').map_or_else(
+                        || class.replace('[', "\\["),
+                        |class| format!("^{}", class.replace('[', "\\[")),
+                    );
+                    re.push('[');
+                    re.push_str(&class);
+                    re.push(']');
+                    idx += len;
+                }
+                None => re.push_str("\\["),
+            },
+            '{' => match closing_brace(&chars[idx..]) {
+                Some(len) => {
+                    let inner = chars[idx + 1..idx + len].iter().collect::<String>();
+                    re.push_str(&braces_to_regex(&inner));
+                    idx += len;
+                }
+                None => re.push_str("\\{"),
+            },
+            ch => re.push_str(&regex::escape(&ch.to_string())),
+        }
+        idx += 1;
+    }
+
+    re
+}
+
+/// Finds the offset of the brace closing the brace at the start of `chars`.
+fn closing_brace(chars: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, ch) in chars.iter().enumerate() {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Converts the contents of braces to a regular expression, either a numeric range `n1..n2` or a comma separated list
+/// of alternatives.
+fn braces_to_regex(inner: &str) -> String {
+    if let Some((start, end)) = inner.split_once("..")
+        && let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>())
+    {
+        // Large ranges match any number instead of listing every value.
+        if (end - start).abs() > 1000 {
+            return "-?\\d+".to_string();
+        }
+
+        let numbers = (start.min(end)..=start.max(end))
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        return format!("(?:{})", numbers.join("|"));
+    }
+
+    // Split at commas outside of nested braces.
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for ch in inner.chars() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(glob_to_regex(&current));
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    alternatives.push(glob_to_regex(&current));
+
+    format!("(?:{})", alternatives.join("|"))
+}
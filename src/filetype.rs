@@ -0,0 +1,189 @@
+use crate::util::TAB_WIDTH;
+use std::path::Path;
+
+/// File names that determine the filetype on their own.
+const FILE_NAMES: [(&str, &str); 9] = [
+    ("Makefile", "make"),
+    ("makefile", "make"),
+    ("Dockerfile", "dockerfile"),
+    ("Cargo.lock", "toml"),
+    (".bashrc", "sh"),
+    (".zshrc", "sh"),
+    (".gitignore", "gitignore"),
+    ("COMMIT_EDITMSG", "gitcommit"),
+    ("MERGE_MSG", "gitcommit"),
+];
+
+/// File extensions and their filetype.
+const EXTENSIONS: [(&str, &str); 29] = [
+    ("rs", "rust"),
+    ("py", "python"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cpp", "cpp"),
+    ("cc", "cpp"),
+    ("hpp", "cpp"),
+    ("go", "go"),
+    ("java", "java"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("ts", "typescript"),
+    ("lua", "lua"),
+    ("rb", "ruby"),
+    ("pl", "perl"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("zsh", "sh"),
+    ("toml", "toml"),
+    ("json", "json"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("xml", "xml"),
+    ("html", "html"),
+    ("css", "css"),
+    ("md", "markdown"),
+    ("mk", "make"),
+    ("diff", "diff"),
+    ("patch", "diff"),
+];
+
+/// Interpreters of shebang lines and their filetype.
+const INTERPRETERS: [(&str, &str); 9] = [
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("zsh", "sh"),
+    ("dash", "sh"),
+    ("python", "python"),
+    ("node", "javascript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+    ("lua", "lua"),
+];
+
+/// Detects the filetype of a file from its name, its extension or a shebang in its first line. Returns `None` if the
+/// filetype is unknown.
+pub fn detect(path: Option<&Path>, first_line: &str) -> Option<&'static str> {
+    let by_path = path.and_then(|path| {
+        let name = path.file_name()?.to_str()?;
+        if let Some((_, filetype)) = FILE_NAMES.iter().find(|(file_name, _)| *file_name == name) {
+            return Some(*filetype);
+        }
+
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        EXTENSIONS
+            .iter()
+            .find(|(extension, _)| *extension == ext)
+            .map(|(_, filetype)| *filetype)
+    });
+
+    by_path.or_else(|| shebang(first_line))
+}
+
+/// Detects the filetype from the interpreter of a shebang line like `#!/bin/sh` or `#!/usr/bin/env python3`.
+fn shebang(line: &str) -> Option<&'static str> {
+    let mut args = line.strip_prefix("#!")?.split_whitespace();
+
+    let mut program = args.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // Skip options of env like `-S`.
+        program = args.find(|arg| !arg.starts_with('-'))?;
+    }
+
+    // Versioned interpreters like `python3.12` use the filetype of the interpreter.
+    let program = program.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(interpreter, _)| *interpreter == program)
+        .map(|(_, filetype)| *filetype)
+}
+
+/// Indentation, comment and formatting defaults of a filetype.
+pub struct Defaults {
+    /// If typed tabs are expanded to spaces.
+    pub expandtab: bool,
+    /// The amount of spaces an expanded tab indents by.
+    pub shiftwidth: usize,
+    /// The template of a line comment, with `%s` standing for the commented text. Empty if unknown.
+    pub commentstring: &'static str,
+    /// The shell command formatting the text of the filetype from its standard input. Empty if unknown.
+    pub formatprg: &'static str,
+    /// The width text is wrapped at. Zero if the filetype has no text width and keeps the current one.
+    pub textwidth: usize,
+}
+
+/// The defaults of filetypes without an entry.
+const GENERIC: Defaults = Defaults {
+    expandtab: true,
+    shiftwidth: TAB_WIDTH,
+    commentstring: "",
+    formatprg: "",
+    textwidth: 0,
+};
+
+/// Filetypes and their defaults, as `(filetype, expandtab, shiftwidth, commentstring, formatprg, textwidth)`.
+const DEFAULTS: [(&str, bool, usize, &str, &str, usize); 24] = [
+    ("rust", true, 4, "// %s", "rustfmt", 0),
+    ("python", true, 4, "# %s", "black -q -", 0),
+    ("c", true, 4, "// %s", "clang-format", 0),
+    ("cpp", true, 4, "// %s", "clang-format", 0),
+    ("go", false, 4, "// %s", "gofmt", 0),
+    (
+        "java",
+        true,
+        4,
+        "// %s",
+        "clang-format --assume-filename=.java",
+        0,
+    ),
+    ("javascript", true, 2, "// %s", "prettier --parser babel", 0),
+    (
+        "typescript",
+        true,
+        2,
+        "// %s",
+        "prettier --parser typescript",
+        0,
+    ),
+    ("lua", true, 2, "-- %s", "stylua -", 0),
+    ("ruby", true, 2, "# %s", "", 0),
+    ("perl", true, 4, "# %s", "", 0),
+    ("sh", true, 4, "# %s", "shfmt", 0),
+    ("make", false, 4, "# %s", "", 0),
+    ("dockerfile", true, 4, "# %s", "", 0),
+    ("gitignore", true, 4, "# %s", "", 0),
+    ("toml", true, 4, "# %s", "taplo fmt -", 0),
+    ("yaml", true, 2, "# %s", "prettier --parser yaml", 0),
+    ("json", true, 2, "", "jq .", 0),
+    ("xml", true, 2, "<!-- %s -->", "", 0),
+    ("html", true, 2, "<!-- %s -->", "prettier --parser html", 0),
+    ("css", true, 2, "/* %s */", "prettier --parser css", 0),
+    (
+        "markdown",
+        true,
+        4,
+        "<!-- %s -->",
+        "prettier --parser markdown",
+        0,
+    ),
+    ("diff", true, 4, "", "", 0),
+    ("gitcommit", true, 4, "# %s", "", COMMIT_BODY_WIDTH),
+];
+
+/// The maximum length of the subject line of a commit message.
+pub const COMMIT_SUBJECT_WIDTH: usize = 50;
+/// The maximum length of the body lines of a commit message.
+pub const COMMIT_BODY_WIDTH: usize = 72;
+
+/// Gets the indentation, comment and formatting defaults of a filetype.
+pub fn defaults(filetype: &str) -> Defaults {
+    DEFAULTS.iter().find(|(name, ..)| *name == filetype).map_or(
+        GENERIC,
+        |&(_, expandtab, shiftwidth, commentstring, formatprg, textwidth)| Defaults {
+            expandtab,
+            shiftwidth,
+            commentstring,
+            formatprg,
+            textwidth,
+        },
+    )
+}
@@ -0,0 +1,402 @@
+use crate::{
+    buffer_manager::BufferManager,
+    clipboard::{self, MemoryClipboard},
+    display::{ColorMode, Display, Screen},
+    input::{KeyEvent, KeySource},
+    run,
+};
+use std::{collections::VecDeque, io::Error};
+use termion::event::Key;
+
+/// Keys fed to the editor in place of the terminal. The input is closed once all keys were read.
+struct FakeKeys {
+    keys: VecDeque<Key>,
+    w: usize,
+    h: usize,
+}
+
+impl KeySource for FakeKeys {
+    fn size(&self) -> Result<(usize, usize), Error> {
+        Ok((self.w, self.h))
+    }
+
+    fn next_key(&mut self) -> Result<KeyEvent, Error> {
+        Ok(self
+            .keys
+            .pop_front()
+            .map_or(KeyEvent::Closed, KeyEvent::Key))
+    }
+}
+
+/// Drives the editor without a terminal. Keys are fed through the same loop as in the terminal and the display is
+/// drawn into memory.
+pub struct Headless {
+    buffer_manager: BufferManager,
+    display: Display,
+    /// Everything drawn to the display so far.
+    out: Vec<u8>,
+    w: usize,
+    h: usize,
+}
+
+impl Headless {
+    /// Opens a scratchpad with the contents, using an in-memory clipboard and a history that is not persisted.
+    pub fn new(contents: &str, w: usize, h: usize) -> Self {
+        let clipboard = clipboard::shared(Box::new(MemoryClipboard::default()));
+        let buffer_manager = BufferManager::new(
+            None,
+            None,
+            Some(contents.to_string()),
+            w,
+            h,
+            clipboard,
+            None,
+        )
+        .unwrap();
+
+        Self {
+            buffer_manager,
+            display: Display::new(w, h, false, ColorMode::Monochrome),
+            out: Vec::new(),
+            w,
+            h,
+        }
+    }
+
+    /// Types the characters of the text as keys.
+    pub fn keys(&mut self, keys: &str) -> &mut Self {
+        self.press(keys.chars().map(Key::Char))
+    }
+
+    /// Presses the keys one after another.
+    pub fn press(&mut self, keys: impl IntoIterator<Item = Key>) -> &mut Self {
+        let mut keys = FakeKeys {
+            keys: keys.into_iter().collect(),
+            w: self.w,
+            h: self.h,
+        };
+        run(
+            &mut self.buffer_manager,
+            &mut self.display,
+            &mut keys,
+            &mut self.out,
+        )
+        .unwrap();
+
+        self
+    }
+
+    /// Returns the lines of the active buffer without their line breaks.
+    pub fn lines(&self) -> Vec<String> {
+        self.buffer_manager
+            .active_doc()
+            .lines()
+            .map(|line| line.to_string().trim_end_matches('\n').to_string())
+            .collect()
+    }
+
+    /// Returns the x and y position of the cursor of the active buffer.
+    pub fn cursor(&self) -> (usize, usize) {
+        let cur = self.buffer_manager.active_doc().cur;
+        (cur.x, cur.y)
+    }
+
+    /// Returns the rows of the screen drawn so far, without trailing whitespace.
+    pub fn screen(&self) -> Vec<String> {
+        Screen::new(&self.out, self.w, self.h).rows()
+    }
+
+    /// Returns the row and column of the cursor on the screen.
+    pub fn screen_cursor(&self) -> (u16, u16) {
+        Screen::new(&self.out, self.w, self.h).cursor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delete_word_and_repeat() {
+        let mut editor = Headless::new("one two three four", 40, 6);
+
+        editor.keys("dw");
+        assert_eq!(editor.lines(), ["two three four"]);
+        assert_eq!(editor.cursor(), (0, 0));
+
+        editor.keys(".");
+        assert_eq!(editor.lines(), ["three four"]);
+    }
+
+    #[test]
+    fn counts_of_operator_and_motion_multiply() {
+        let mut editor = Headless::new("a b c d e f g h", 40, 6);
+
+        editor.keys("3d2w");
+        assert_eq!(editor.lines(), ["g h"]);
+    }
+
+    #[test]
+    fn undo_and_redo_line_delete() {
+        let mut editor = Headless::new("first\nsecond", 40, 6);
+
+        editor.keys("dd");
+        assert_eq!(editor.lines(), ["second"]);
+
+        editor.keys("u");
+        assert_eq!(editor.lines(), ["first", "second"]);
+
+        editor.keys("U");
+        assert_eq!(editor.lines(), ["second"]);
+    }
+
+    #[test]
+    fn change_word_inserts_text() {
+        let mut editor = Headless::new("one two", 40, 6);
+
+        editor.keys("cwnew ").press([Key::Esc]);
+        assert_eq!(editor.lines(), ["new two"]);
+    }
+
+    #[test]
+    fn append_at_end_of_line() {
+        let mut editor = Headless::new("one\ntwo", 40, 6);
+
+        editor.keys("jA!").press([Key::Esc]);
+        assert_eq!(editor.lines(), ["one", "two!"]);
+        assert_eq!(editor.cursor(), (4, 1));
+    }
+
+    #[test]
+    fn yank_line_and_paste_above() {
+        let mut editor = Headless::new("first\nsecond", 40, 6);
+
+        editor.keys("jyyP");
+        assert_eq!(editor.lines(), ["first", "second", "second"]);
+        assert_eq!(editor.cursor().1, 1);
+    }
+
+    #[test]
+    fn repeat_command_with_a_count() {
+        let mut editor = Headless::new("a\nb\nc\nd\ne", 40, 6);
+
+        editor.keys(" join\n");
+        assert_eq!(editor.lines(), ["a b", "c", "d", "e"]);
+
+        editor.keys("2@:");
+        assert_eq!(editor.lines(), ["a b c d", "e"]);
+    }
+
+    #[test]
+    fn word_and_line_motions() {
+        let mut editor = Headless::new("one two three\nfour five", 40, 6);
+
+        editor.keys("w");
+        assert_eq!(editor.cursor(), (4, 0));
+
+        editor.keys("2w");
+        assert_eq!(editor.cursor(), (0, 1));
+
+        editor.keys("b");
+        assert_eq!(editor.cursor(), (8, 0));
+
+        editor.keys(">");
+        assert_eq!(editor.cursor(), (13, 0));
+
+        editor.keys("0j");
+        assert_eq!(editor.cursor(), (0, 1));
+    }
+
+    #[test]
+    fn screen_shows_the_buffer() {
+        let mut editor = Headless::new("one two", 20, 3);
+
+        editor.keys("x");
+        assert_eq!(editor.screen()[1..], [" 1 ┃ ne·two", "   ┃"]);
+    }
+
+    #[test]
+    fn switching_buffers_keeps_their_scroll_position() {
+        let contents = (1..=200)
+            .map(|n| format!("{n} {}", "x".repeat(n)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut editor = Headless::new(&contents, 40, 10);
+
+        // Scroll both buffers vertically and horizontally.
+        editor.keys(" j 150:120\n");
+        let first = (editor.screen(), editor.cursor());
+        assert_eq!(first.1, (119, 149));
+        assert!(first.0.iter().any(|row| row.contains("150 ┃ xxx")));
+
+        editor.keys(" ??\n j 80\n");
+        let second = (editor.screen(), editor.cursor());
+        assert_ne!(first.0, second.0);
+
+        editor.keys(" cb 0\n");
+        assert_eq!((editor.screen(), editor.cursor()), first);
+        editor.keys(" cb 1\n");
+        assert_eq!((editor.screen(), editor.cursor()), second);
+    }
+
+    #[test]
+    fn search_in_the_help_buffer() {
+        let mut editor = Headless::new("text", 80, 24);
+
+        editor.keys(" ??\n");
+        let lines = editor.lines();
+        assert!(lines[0].starts_with("Mini - A terminal text-editor"));
+        let found: Vec<_> = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                // Searches ignore the case of lowercase patterns.
+                line.to_lowercase()
+                    .match_indices("scratchpad")
+                    .map(|(idx, _)| (line[..idx].chars().count(), y))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert!(found.len() > 2);
+
+        editor.keys(" s /scratchpad/\n");
+        assert_eq!(editor.cursor(), found[0]);
+
+        editor.keys("nn");
+        assert_eq!(editor.cursor(), found[2]);
+        editor.keys("N");
+        assert_eq!(editor.cursor(), found[1]);
+
+        // Searching wraps around at the start of the help.
+        editor.keys("NN");
+        assert_eq!(editor.cursor(), *found.last().unwrap());
+    }
+
+    #[test]
+    fn yank_and_delete_to_the_end_of_the_file() {
+        // Selecting to the end of the file includes the last character once.
+        let mut editor = Headless::new("abc\ndef\nghi", 40, 6);
+        editor.keys(" j 2:2\nvggyvGp");
+        assert_eq!(editor.lines(), ["ef", "ghiabc", "def", "ghi"]);
+
+        let mut editor = Headless::new("abc\ndef\nghi", 40, 6);
+        editor.keys(" j 2:2\nvggdv");
+        assert_eq!(editor.lines(), ["abc", "d"]);
+        editor.keys("u");
+        assert_eq!(editor.lines(), ["abc", "def", "ghi"]);
+
+        // The motion of an operator agrees with the selection.
+        let mut editor = Headless::new("abc\ndef\nghi", 40, 6);
+        editor.keys(" j 2:2\nyggGp");
+        assert_eq!(editor.lines(), ["ef", "ghiabc", "def", "ghi"]);
+
+        let mut editor = Headless::new("abc\ndef\nghi\n", 40, 6);
+        editor.keys(" j 2:2\ndgg");
+        assert_eq!(editor.lines(), ["abc", "d"]);
+    }
+
+    #[test]
+    fn typing_newlines_keeps_scrolloff_lines_below_the_cursor() {
+        let contents = (1..=100)
+            .map(|n| format!("line {n}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut editor = Headless::new(&contents, 40, 12);
+        editor.keys(" set scrolloff=3\n j 5\ni");
+
+        for _ in 0..20 {
+            editor.keys("\n");
+            let (row, _) = editor.screen_cursor();
+            let (_, y) = editor.cursor();
+            let screen = editor.screen();
+
+            // The three following lines are visible below the cursor.
+            for below in 1..=3 {
+                let number = format!("{} ┃", y + 1 + below);
+                let row = &screen[usize::from(row) + below];
+                assert!(
+                    row.trim_start().starts_with(&number),
+                    "{row:?} after line {}",
+                    y + 1
+                );
+            }
+        }
+        editor.press([Key::Esc]);
+        assert_eq!(editor.cursor(), (0, 24));
+    }
+
+    #[test]
+    fn join_with_and_without_spaces() {
+        for (keys, lines, cursor) in [
+            ("gj", vec!["foo bar", "  baz"], (3, 0)),
+            ("gJ", vec!["foo    bar", "  baz"], (3, 0)),
+            ("3gj", vec!["foo bar baz"], (7, 0)),
+            ("3gJ", vec!["foo    bar  baz"], (10, 0)),
+        ] {
+            let mut editor = Headless::new("foo\n    bar\n  baz", 40, 6);
+            editor.keys(keys);
+            assert_eq!(editor.lines(), lines, "{keys}");
+            assert_eq!(editor.cursor(), cursor, "{keys}");
+        }
+
+        // Lines ending in whitespace or joined with a closing parenthesis don't get another space.
+        let mut editor = Headless::new("call(  \n  )", 40, 6);
+        editor.keys("gj");
+        assert_eq!(editor.lines(), ["call(  )"]);
+        editor.keys("u");
+        assert_eq!(editor.lines(), ["call(  ", "  )"]);
+    }
+
+    #[test]
+    fn append_at_the_end_of_files_with_and_without_trailing_newline() {
+        // Without a trailing newline the end of the file is after the last character.
+        let mut editor = Headless::new("ab\ncd", 40, 6);
+        editor.keys("gg");
+        assert_eq!(editor.cursor(), (2, 1));
+        editor.keys("<>");
+        assert_eq!(editor.cursor(), (2, 1));
+        editor.keys("G>");
+        assert_eq!(editor.cursor(), (2, 0));
+        editor.keys("ggAx");
+        assert_eq!(editor.lines(), ["ab", "cdx"]);
+        assert_eq!(editor.cursor(), (3, 1));
+
+        // With a trailing newline it is the empty last line.
+        let mut editor = Headless::new("ab\ncd\n", 40, 6);
+        editor.keys("gg");
+        assert_eq!(editor.cursor(), (0, 2));
+        editor.keys(">");
+        assert_eq!(editor.cursor(), (0, 2));
+        editor.keys("kAx");
+        assert_eq!(editor.lines(), ["ab", "cdx", ""]);
+        editor.press([Key::Esc]);
+        editor.keys("ggAy");
+        assert_eq!(editor.lines(), ["ab", "cdx", "y"]);
+    }
+
+    #[test]
+    fn repeat_change_of_the_next_highlight() {
+        let mut editor = Headless::new("foo bar foo baz foo", 40, 6);
+
+        // The replacement is shorter than the matches, the highlights must follow the changes.
+        editor.keys(" match /foo/\ncgnX").press([Key::Esc]);
+        editor.keys(".");
+        assert_eq!(editor.lines(), ["X bar X baz foo"]);
+        editor.keys(".");
+        assert_eq!(editor.lines(), ["X bar X baz X"]);
+    }
+
+    #[test]
+    fn huge_counts_stop_at_the_end_of_the_document() {
+        let mut editor = Headless::new("one two\nthree", 40, 6);
+
+        editor.keys("99999999w");
+        assert_eq!(editor.cursor(), (5, 1));
+        editor.keys("99999999b");
+        assert_eq!(editor.cursor(), (0, 0));
+
+        // A count too large for a number moves as far as possible instead of once.
+        editor.keys("99999999999999999999999W");
+        assert_eq!(editor.cursor(), (5, 1));
+    }
+}
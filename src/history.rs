@@ -1,4 +1,5 @@
 use crate::cursor::Cursor;
+use std::time::Instant;
 
 /// A change replacing data.
 pub struct Replace {
@@ -11,10 +12,10 @@ type Change = Vec<Replace>;
 
 /// A history of changes to a document.
 pub struct History {
-    /// The undo stack of changes.
-    undo: Vec<Change>,
-    /// The redo stack of changes.
-    redo: Vec<Change>,
+    /// The undo stack of changes and the times they were made.
+    undo: Vec<(Instant, Change)>,
+    /// The redo stack of changes and the times they were made.
+    redo: Vec<(Instant, Change)>,
 }
 
 impl History {
@@ -27,27 +28,37 @@ impl History {
 
     /// Adds a new change to the history.
     pub fn add_change(&mut self, change: Change) {
-        self.undo.push(change);
+        self.undo.push((Instant::now(), change));
         self.redo.clear();
     }
 
-    /// Pops the last change for undoing.
-    pub fn undo(&mut self) -> Option<Change> {
+    /// Pops the last change and the time it was made for undoing.
+    pub fn undo(&mut self) -> Option<(Instant, Change)> {
         self.undo.pop()
     }
 
-    /// Pops the last undone change for redoing.
-    pub fn redo(&mut self) -> Option<Change> {
+    /// Pops the last undone change and the time it was made for redoing.
+    pub fn redo(&mut self) -> Option<(Instant, Change)> {
         self.redo.pop()
     }
 
     /// Pushes a change to the redo stack.
-    pub fn push_redo(&mut self, change: Change) {
-        self.redo.push(change);
+    pub fn push_redo(&mut self, time: Instant, change: Change) {
+        self.redo.push((time, change));
     }
 
     /// Pushes a change to the undo stack.
-    pub fn push_undo(&mut self, change: Change) {
-        self.undo.push(change);
+    pub fn push_undo(&mut self, time: Instant, change: Change) {
+        self.undo.push((time, change));
+    }
+
+    /// Returns the time the change that would be undone next was made.
+    pub fn undo_time(&self) -> Option<Instant> {
+        self.undo.last().map(|(time, _)| *time)
+    }
+
+    /// Returns the time the change that would be redone next was made.
+    pub fn redo_time(&self) -> Option<Instant> {
+        self.redo.last().map(|(time, _)| *time)
     }
 }
@@ -0,0 +1,175 @@
+use polling::{Events, Poller};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{Error, Read},
+    os::fd::AsFd,
+    time::Duration,
+};
+use termion::event::{Event, Key, parse_event};
+
+const ESC: u8 = 0x1b;
+// Random value chosen by dev-rng.
+const STDIN_EVENT_KEY: usize = 25663;
+/// Time to wait for a key before sending an empty tick.
+const TICK_TIMEOUT: Duration = Duration::from_millis(20);
+/// Time to wait for the rest of an escape sequence before treating an escape as a key press.
+const ESC_TIMEOUT: Duration = Duration::from_millis(25);
+
+/// What waiting for a key yielded.
+pub enum KeyEvent {
+    /// A key was pressed.
+    Key(Key),
+    /// No key arrived in time, the buffers get an empty tick.
+    Timeout,
+    /// The input was closed.
+    Closed,
+}
+
+/// A source of keys driving the editor, like the terminal.
+pub trait KeySource {
+    /// Returns the width and height of the display.
+    fn size(&self) -> Result<(usize, usize), Error>;
+
+    /// Waits for the next key.
+    fn next_key(&mut self) -> Result<KeyEvent, Error>;
+}
+
+/// The keys typed into the terminal.
+pub struct TerminalKeys {
+    /// The terminal, which is stdin unless stdin was piped into the buffer.
+    tty: File,
+    /// The keys read from the terminal.
+    input: Input,
+    /// Polls the terminal for input.
+    poller: Poller,
+    /// The events of the last poll.
+    events: Events,
+}
+
+impl TerminalKeys {
+    pub fn new(tty: File) -> Result<Self, Error> {
+        let input = Input::new(tty.try_clone()?);
+
+        // Use polling to periodically read the terminal.
+        let poller = Poller::new()?;
+        unsafe { poller.add(&tty.as_fd(), polling::Event::readable(STDIN_EVENT_KEY))? };
+
+        Ok(Self {
+            tty,
+            input,
+            poller,
+            events: Events::new(),
+        })
+    }
+}
+
+impl KeySource for TerminalKeys {
+    fn size(&self) -> Result<(usize, usize), Error> {
+        let (w, h) = termion::terminal_size()?;
+
+        Ok((w as usize, h as usize))
+    }
+
+    fn next_key(&mut self) -> Result<KeyEvent, Error> {
+        // Handle keys that were already read first.
+        if let Some(key) = self.input.next_key(false) {
+            return Ok(KeyEvent::Key(key));
+        }
+
+        // Clear previous iterations events and fetch new ones. Pending bytes are the beginning of an escape sequence,
+        // so only wait a short time for the rest.
+        self.events.clear();
+        let timeout = if self.input.is_empty() {
+            TICK_TIMEOUT
+        } else {
+            ESC_TIMEOUT
+        };
+        self.poller.wait(&mut self.events, Some(timeout))?;
+
+        let key = if self.events.iter().any(|e| e.key == STDIN_EVENT_KEY) {
+            // Re-enable polling.
+            self.poller
+                .modify(self.tty.as_fd(), polling::Event::readable(STDIN_EVENT_KEY))?;
+
+            // If a new event exists, send a tick with the key immediately.
+            if !self.input.read()? {
+                return Ok(KeyEvent::Closed);
+            }
+            self.input.next_key(false)
+        } else {
+            // Otherwise send a tick with the pending escape or an empty tick after the timeout.
+            self.input.next_key(true)
+        };
+
+        Ok(key.map_or(KeyEvent::Timeout, KeyEvent::Key))
+    }
+}
+
+/// Buffered keyboard input. Bytes are parsed into keys once it is known whether an escape starts an escape sequence.
+pub struct Input {
+    /// The terminal, which is stdin unless stdin was piped into the buffer.
+    tty: File,
+    /// The bytes that were read but not yet parsed into keys.
+    bytes: VecDeque<u8>,
+}
+
+impl Input {
+    pub const fn new(tty: File) -> Self {
+        Self {
+            tty,
+            bytes: VecDeque::new(),
+        }
+    }
+
+    /// Checks if no unparsed bytes are pending.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Reads the available bytes. Returns false if the input was closed.
+    pub fn read(&mut self) -> Result<bool, Error> {
+        let mut buff = [0; 1024];
+        let len = self.tty.read(&mut buff)?;
+        self.bytes.extend(&buff[..len]);
+
+        Ok(len > 0)
+    }
+
+    /// Parses the next key. An escape is only returned on its own if it is followed by a byte that does not continue
+    /// an escape sequence, or if no more bytes arrived and `complete` is set. Otherwise the following bytes might
+    /// still be on their way and `None` is returned to wait for them.
+    pub fn next_key(&mut self, complete: bool) -> Option<Key> {
+        while let Some(&first) = self.bytes.front() {
+            // An escape followed by another key is not an alt-combination, but a quickly pressed escape. Forwarding
+            // both keys separately sends the same bytes to shell commands.
+            if first == ESC && !matches!(self.bytes.get(1), Some(b'[' | b'O') | None) {
+                self.bytes.pop_front();
+                return Some(Key::Esc);
+            }
+
+            let mut rest = self.bytes.iter().skip(1).copied().map(Ok);
+            let res = parse_event(first, &mut rest);
+            let len = self.bytes.len() - rest.len();
+
+            match res {
+                Ok(event) => {
+                    self.bytes.drain(..len);
+                    if let Event::Key(key) = event {
+                        return Some(key);
+                    }
+                }
+                // The bytes might be an incomplete sequence.
+                Err(_) if !complete => return None,
+                Err(_) => {
+                    self.bytes.pop_front();
+                    if first == ESC {
+                        return Some(Key::Esc);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -1,49 +1,113 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 #![allow(clippy::too_many_lines, clippy::similar_names)]
 
+mod bookmarks;
 mod buffer;
 mod buffer_impls;
 mod buffer_manager;
+mod clipboard;
+mod command_history;
 mod cursor;
 mod display;
 mod document;
+mod editorconfig;
+mod filetype;
+#[cfg(test)]
+mod headless;
 mod history;
+mod input;
 mod message;
+mod options;
 mod selection;
 mod shell_command;
+mod swap;
+mod trash;
 mod util;
 mod viewport;
 
 use crate::{
     buffer_manager::BufferManager,
-    display::Display,
-    util::{file_name, open_file},
+    display::{ColorMode, Display},
+    input::{KeyEvent, KeySource, TerminalKeys},
+    util::open_file_or_read_only,
     viewport::{BG, CHAR_WARN, HIGHLIGHT, INFO, SEL, TXT},
 };
-use polling::{Events, Poller};
-use std::{io::BufWriter, os::fd::AsFd, time::Duration};
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Stdout, Write},
+    os::fd::AsFd,
+    sync::Mutex,
+};
 use termion::{
-    input::TermRead,
-    raw::IntoRawMode,
+    cursor::Show,
+    raw::{IntoRawMode, RawTerminal},
     screen::{ToAlternateScreen, ToMainScreen},
 };
 
-// Random value chosen by dev-rng.
-const STDIN_EVENT_KEY: usize = 25663;
 const INFO_MSG: &str = include_str!("../info.txt");
 
+/// The raw mode handle of the terminal. Kept globally to be able to restore the terminal on panics.
+static RAW_TERMINAL: Mutex<Option<RawTerminal<Stdout>>> = Mutex::new(None);
+
 /// Checks if the current running terminal is kitty.
 fn is_kitty() -> bool {
-    let term = std::env::var("TERM")
-        .map(|s| s.contains("kitty"))
-        .unwrap_or(false);
-    let prog = std::env::var("TERM_PROGRAM")
-        .map(|s| s.contains("kitty"))
-        .unwrap_or(false);
+    let term = std::env::var("TERM").is_ok_and(|s| s.contains("kitty"));
+    let prog = std::env::var("TERM_PROGRAM").is_ok_and(|s| s.contains("kitty"));
 
     term || prog
 }
 
+/// Checks if the current running terminal is known to support synchronized output.
+fn supports_synchronized_output() -> bool {
+    const TERMINALS: [&str; 6] = [
+        "kitty",
+        "wezterm",
+        "foot",
+        "alacritty",
+        "contour",
+        "ghostty",
+    ];
+
+    let matches = |var: &str| {
+        std::env::var(var).is_ok_and(|s| {
+            let s = s.to_lowercase();
+            TERMINALS.iter().any(|term| s.contains(term))
+        })
+    };
+
+    matches("TERM") || matches("TERM_PROGRAM")
+}
+
+/// Checks if colors should be used. Respects `NO_COLOR` and falls back to monochrome on terminals without colors.
+fn supports_color() -> bool {
+    if std::env::var_os("NO_COLOR").is_some_and(|s| !s.is_empty()) {
+        return false;
+    }
+
+    std::env::var("TERM").is_ok_and(|s| s != "dumb" && !s.starts_with("vt"))
+}
+
+/// Checks if the terminal supports RGB colors.
+fn supports_truecolor() -> bool {
+    if std::env::var("COLORTERM").is_ok_and(|s| s == "truecolor" || s == "24bit") {
+        return true;
+    }
+
+    std::env::var("TERM").is_ok_and(|s| {
+        s.ends_with("-direct")
+            || [
+                "kitty",
+                "wezterm",
+                "foot",
+                "alacritty",
+                "contour",
+                "ghostty",
+            ]
+            .iter()
+            .any(|term| s.contains(term))
+    })
+}
+
 /// Pushes to the kitty color stack.
 fn kitty_push_colors() {
     print!("\x1b]30001\x1b\\");
@@ -75,6 +139,35 @@ fn kitty_transparency() {
     );
 }
 
+/// Disables raw mode if it was enabled.
+fn disable_raw_mode() {
+    // Ignore poisoning, the terminal has to be restored in any case.
+    let mut raw_terminal = RAW_TERMINAL
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    // Dropping the handle restores the previous terminal mode.
+    if let Some(raw_terminal) = raw_terminal.take() {
+        let _ = raw_terminal.suspend_raw_mode();
+    }
+}
+
+/// Installs a panic hook restoring the terminal before printing the panic message.
+fn set_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // Every step is a no-op if the terminal wasn't modified yet.
+        disable_raw_mode();
+        if is_kitty() && supports_color() {
+            kitty_pop_colors();
+        }
+        print!("{ToMainScreen}{Show}");
+        let _ = std::io::stdout().flush();
+
+        default_hook(info);
+    }));
+}
+
 fn main() {
     let mut args = std::env::args();
     args.next();
@@ -88,13 +181,29 @@ fn main() {
         return;
     }
 
+    // Read a piped buffer before the terminal is set up, since keys are read from the terminal afterwards.
+    let contents = if path.as_deref() == Some("-") {
+        let mut bytes = Vec::new();
+        if let Err(err) = std::io::stdin().read_to_end(&mut bytes) {
+            eprintln!("{err}");
+            return;
+        }
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    } else {
+        None
+    };
+    let path = path.filter(|_| contents.is_none());
+
+    set_panic_hook();
+
     print!("{ToAlternateScreen}");
-    if is_kitty() {
+    if is_kitty() && supports_color() {
         kitty_push_colors();
         kitty_transparency();
     }
-    let res = mini(path.as_ref());
-    if is_kitty() {
+    let res = mini(path.as_ref(), contents);
+    disable_raw_mode();
+    if is_kitty() && supports_color() {
         kitty_pop_colors();
     }
     print!("{ToMainScreen}");
@@ -104,58 +213,78 @@ fn main() {
     }
 }
 
-fn mini(path: Option<&String>) -> Result<(), std::io::Error> {
-    let (file, file_name) = path.as_ref().map_or((None, None), |path| {
-        (Some(open_file(path)), file_name(path))
-    });
+fn mini(path: Option<&String>, contents: Option<String>) -> Result<(), std::io::Error> {
+    let file = path.as_ref().map(open_file_or_read_only);
+
+    // Stdin is not the terminal anymore if it was piped into the buffer.
+    let tty = if contents.is_some() {
+        File::open("/dev/tty")?
+    } else {
+        File::from(std::io::stdin().as_fd().try_clone_to_owned()?)
+    };
 
     // Setup stdin and stdout.
-    let mut stdout = BufWriter::new(std::io::stdout().into_raw_mode()?);
-    let stdin = std::io::stdin();
-    let mut stdin_keys = std::io::stdin().keys();
+    *RAW_TERMINAL
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) =
+        Some(std::io::stdout().into_raw_mode()?);
+    let mut stdout = BufWriter::new(std::io::stdout());
+    let mut keys = TerminalKeys::new(tty)?;
 
-    // Use polling to periodically read stdin.
-    let poller = Poller::new()?;
-    unsafe { poller.add(&stdin.as_fd(), polling::Event::readable(STDIN_EVENT_KEY))? };
+    let (w, h) = keys.size()?;
 
-    let (w, h) = termion::terminal_size()?;
+    let clipboard = clipboard::shared(clipboard::new());
+    let mut buffer_manager = BufferManager::new(
+        path,
+        file,
+        contents,
+        w,
+        h,
+        clipboard,
+        command_history::path(),
+    )?;
+    let mut display = Display::new(
+        w,
+        h,
+        supports_synchronized_output(),
+        if !supports_color() {
+            ColorMode::Monochrome
+        } else if supports_truecolor() {
+            ColorMode::TrueColor
+        } else {
+            ColorMode::Palette
+        },
+    );
 
-    let mut buffer_manager = BufferManager::new(path, file, file_name, w as usize, h as usize)?;
-    let mut display = Display::new(w as usize, h as usize);
+    run(&mut buffer_manager, &mut display, &mut keys, &mut stdout)
+}
 
-    buffer_manager.render(&mut display);
-    display.draw(&mut stdout)?;
+/// Runs the editor until all buffers are closed or the input is closed. Every key, or an empty tick if none arrived in
+/// time, is forwarded to the buffers and the result is drawn to `out`.
+fn run(
+    buffer_manager: &mut BufferManager,
+    display: &mut Display,
+    keys: &mut impl KeySource,
+    out: &mut impl Write,
+) -> Result<(), std::io::Error> {
+    buffer_manager.render(display);
+    display.draw(out)?;
 
-    let mut events = Events::new();
     loop {
-        let (w, h) = termion::terminal_size()?;
-        buffer_manager.resize(w as usize, h as usize);
-        display.resize(w as usize, h as usize);
-
-        // Clear previous iterations events and fetch new ones.
-        events.clear();
-        poller.wait(&mut events, Some(Duration::from_millis(20)))?;
-
-        let key = if events.iter().any(|e| e.key == STDIN_EVENT_KEY) {
-            // If a new event exists, send a tick with the key immediately.
-            match stdin_keys.next() {
-                Some(Ok(key)) => Some(key),
-                Some(Err(_)) | None => return Ok(()),
-            }
-        } else {
-            // Otherwise send an empty tick after the timeout.
-            None
+        let (w, h) = keys.size()?;
+        buffer_manager.resize(w, h);
+        display.resize(w, h);
+
+        let key = match keys.next_key()? {
+            KeyEvent::Key(key) => Some(key),
+            KeyEvent::Timeout => None,
+            KeyEvent::Closed => return Ok(()),
         };
 
         if !buffer_manager.tick(key) {
-            break;
+            return Ok(());
         }
-        buffer_manager.render(&mut display);
-        display.draw(&mut stdout)?;
-
-        // Re-enable polling.
-        poller.modify(stdin.as_fd(), polling::Event::readable(STDIN_EVENT_KEY))?;
+        buffer_manager.render(display);
+        display.draw(out)?;
     }
-
-    Ok(())
 }
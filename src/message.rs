@@ -1,12 +1,13 @@
-use crate::util::TAB_WIDTH;
+use crate::util::visual_cells;
 use std::{fmt::Display, str::Lines};
-use unicode_width::UnicodeWidthChar;
 
 /// Kind of the message.
 #[derive(Clone)]
 pub enum MessageKind {
     Info,
     Error,
+    /// The help message, drawn with its headings and notes styled.
+    Help,
 }
 
 /// A message to be displayed to the user to convey information or show errors.
@@ -49,7 +50,7 @@ impl Message {
 impl Display for Message {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.kind {
-            MessageKind::Info => writeln!(f, "Info:")?,
+            MessageKind::Info | MessageKind::Help => writeln!(f, "Info:")?,
             MessageKind::Error => writeln!(f, "Error:")?,
         }
         write!(f, "{}", self.text)
@@ -85,13 +86,8 @@ impl<'a> Iterator for MessageIter<'a> {
         let mut width = 0;
         let mut split_idx = text.len();
 
-        for (idx, ch) in text.char_indices() {
-            let ch_width = if ch == '\t' {
-                TAB_WIDTH - (width % TAB_WIDTH)
-            } else {
-                ch.width().unwrap_or(0)
-            };
-
+        for ((idx, _), cell) in text.char_indices().zip(visual_cells(text.chars())) {
+            let ch_width = cell.width;
             if ch_width == 0 {
                 continue;
             }
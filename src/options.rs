@@ -0,0 +1,511 @@
+use crate::{cursor::CursorStyle, filetype, util::TAB_WIDTH};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Write},
+    str::FromStr,
+};
+
+/// Default idle time in seconds before autosaving if enabled without a value.
+const AUTOSAVE_DEFAULT: u64 = 5;
+
+/// Default time in milliseconds to wait for the completion of a multi-key input.
+const TIMEOUTLEN_DEFAULT: u64 = 1000;
+
+/// Default amount of lines kept visible around the cursor if enabled without a value.
+const SCROLLOFF_DEFAULT: usize = 5;
+
+/// Default width in columns text is reflowed to if no text width is set.
+const REFLOW_WIDTH_DEFAULT: usize = 79;
+
+/// Default column after which lines are drawn without decorations.
+const SYNMAXCOL_DEFAULT: usize = 3000;
+
+/// Default amount of entered commands kept across sessions.
+const HISTORY_DEFAULT: usize = 100;
+
+/// Default characters besides alphanumeric ones that are part of a word.
+const ISKEYWORD_DEFAULT: &str = "_";
+
+/// Options that only affect files buffers.
+const FILES_OPTIONS: &[&str] = &["preview"];
+
+/// Options of a buffer that can be changed using the `set` command.
+// Options are independent toggles.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone)]
+pub struct Options {
+    /// Idle time in seconds after which a modified buffer is written to its file. Zero disables autosaving.
+    pub autosave: u64,
+    /// If the files buffer shows a preview of the selected entry.
+    pub preview: bool,
+    /// If the files buffer lists directories as an expandable tree.
+    pub tree: bool,
+    /// Characters besides alphanumeric ones that word motions treat as part of a word.
+    pub iskeyword: String,
+    /// If tabs are expanded to spaces when typed. Tab characters are rendered as warnings if enabled.
+    pub expandtab: bool,
+    /// The amount of spaces a typed tab indents by if `expandtab` is enabled.
+    pub shiftwidth: usize,
+    /// The width in columns typed text is wrapped and text is reflowed to, 0 to not wrap typed text.
+    pub textwidth: usize,
+    /// If lines longer than the window continue on the following rows instead of scrolling horizontally.
+    pub wrap: bool,
+    /// The glyphs used to render whitespace characters.
+    pub listchars: ListChars,
+    /// The cursor styles of the modes.
+    pub guicursor: GuiCursor,
+    /// If indent guides are drawn at each indentation level.
+    pub indentguides: bool,
+    /// If indentation mixing tabs and spaces is rendered as a warning.
+    pub mixedindent: bool,
+    /// If whitespace at the end of lines is rendered as a warning.
+    pub trailingspace: bool,
+    /// If the gutter marks lines changed since the buffer was last written.
+    pub changesigns: bool,
+    /// The character column after which highlighted matches are not drawn, keeping very long lines fast to render. Zero
+    /// draws them in the whole line.
+    pub synmaxcol: usize,
+    /// If the line of the cursor is highlighted.
+    pub cursorline: bool,
+    /// If the column of the cursor is highlighted.
+    pub cursorcolumn: bool,
+    /// The 1-indexed visual columns highlighted in every line.
+    pub colorcolumn: Vec<usize>,
+    /// If commit messages highlight the column past the subject and body line limits and show the line length.
+    pub commitguide: bool,
+    /// Time in milliseconds after which a pending multi-key input is aborted. Zero waits indefinitely.
+    pub timeoutlen: u64,
+    /// The minimum amount of lines kept visible above and below the cursor.
+    pub scrolloff: usize,
+    /// What moving down at the last line or up at the first line does, `bell` to flash the info line or `wrap` to move
+    /// to the other end of the document. Empty does nothing.
+    pub edgemotion: String,
+    /// The amount of entered commands kept in the history file across sessions. Zero keeps the history only for the
+    /// session.
+    pub history: usize,
+    /// The amount of lines half-page scrolling moves by. Zero uses half the viewport height.
+    pub scroll: usize,
+    /// If colors are drawn as RGB. Otherwise they are approximated using the 256-color palette.
+    pub termguicolors: bool,
+    /// The filetype of the buffer, empty if unknown. Detected when a file is opened.
+    pub filetype: String,
+    /// The template of a line comment of the filetype, with `%s` standing for the commented text.
+    pub commentstring: String,
+    /// The shell command formatting the buffer from its standard input.
+    pub formatprg: String,
+    /// If the buffer is formatted with `formatprg` when writing it.
+    pub formatonsave: bool,
+    /// If ANSI escape sequences are removed from the output of commands the buffer is filtered through.
+    pub stripansi: bool,
+    /// If trailing whitespace is removed when writing the buffer.
+    pub trimwhitespace: bool,
+    /// If a missing line break at the end of the buffer is added when writing it.
+    pub fixeol: bool,
+    /// The line ending lines are converted to when writing the buffer, `lf` or `crlf`. Empty keeps the line endings.
+    pub fileformat: String,
+    /// If the files buffer moves removed entries to the trash instead of deleting them.
+    pub trash: bool,
+    /// The trash directory. Empty uses the trash inside the XDG data directory.
+    pub trashdir: String,
+    /// The clipboard shared by all buffers, `system`, `osc52` or `memory`. Empty selects one for the environment.
+    pub clipboard: String,
+
+    /// If the options belong to a files buffer. Options only affecting files buffers are not available otherwise.
+    pub files: bool,
+    /// The names of the options changed by `set`, which the defaults of a filetype don't override.
+    user_set: HashSet<String>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            autosave: 0,
+            preview: false,
+            tree: false,
+            iskeyword: ISKEYWORD_DEFAULT.to_string(),
+            expandtab: true,
+            shiftwidth: TAB_WIDTH,
+            textwidth: 0,
+            wrap: false,
+            listchars: ListChars::default(),
+            guicursor: GuiCursor::default(),
+            indentguides: false,
+            mixedindent: false,
+            trailingspace: false,
+            changesigns: false,
+            synmaxcol: SYNMAXCOL_DEFAULT,
+            cursorline: true,
+            cursorcolumn: false,
+            colorcolumn: Vec::new(),
+            commitguide: true,
+            timeoutlen: TIMEOUTLEN_DEFAULT,
+            scrolloff: 0,
+            edgemotion: String::new(),
+            history: HISTORY_DEFAULT,
+            scroll: 0,
+            termguicolors: crate::supports_truecolor(),
+            filetype: String::new(),
+            commentstring: String::new(),
+            formatprg: String::new(),
+            formatonsave: false,
+            stripansi: true,
+            trimwhitespace: false,
+            fixeol: false,
+            fileformat: String::new(),
+            trash: true,
+            trashdir: String::new(),
+            clipboard: String::new(),
+            files: false,
+            user_set: HashSet::new(),
+        }
+    }
+
+    /// Gets the width text is reflowed to, the text width or a default if it is 0.
+    pub const fn reflow_width(&self) -> usize {
+        if self.textwidth == 0 {
+            REFLOW_WIDTH_DEFAULT
+        } else {
+            self.textwidth
+        }
+    }
+
+    /// Sets the filetype and applies its indentation, comment and formatting defaults. Options changed by `set` keep
+    /// their value.
+    pub fn set_filetype(&mut self, filetype: &str) {
+        let defaults = filetype::defaults(filetype);
+        let default = |name: &str| !self.user_set.contains(name);
+
+        self.filetype = filetype.to_string();
+        if default("expandtab") {
+            self.expandtab = defaults.expandtab;
+        }
+        if default("shiftwidth") {
+            self.shiftwidth = defaults.shiftwidth;
+        }
+        if default("commentstring") {
+            self.commentstring = defaults.commentstring.to_string();
+        }
+        if default("formatprg") {
+            self.formatprg = defaults.formatprg.to_string();
+        }
+        if defaults.textwidth > 0 && default("textwidth") {
+            self.textwidth = defaults.textwidth;
+        }
+    }
+
+    /// Checks if the commit message guides are shown.
+    pub fn commit_guide(&self) -> bool {
+        self.commitguide && self.filetype == "gitcommit"
+    }
+
+    /// Gets the 0-indexed visual columns highlighted in a line, the `colorcolumn` columns and the first column past the
+    /// line limit of commit messages.
+    pub fn color_columns(&self, y: usize) -> Vec<usize> {
+        let mut columns = self
+            .colorcolumn
+            .iter()
+            .map(|column| column - 1)
+            .collect::<Vec<_>>();
+        if self.commit_guide() {
+            columns.push(if y == 0 {
+                filetype::COMMIT_SUBJECT_WIDTH
+            } else {
+                filetype::COMMIT_BODY_WIDTH
+            });
+        }
+
+        columns
+    }
+
+    /// Applies a setting of the form `<option>`, `no<option>` or `<option>=<value>`.
+    pub fn set(&mut self, arg: &str) -> Result<(), String> {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (arg.trim(), None),
+        };
+
+        let option = name.strip_prefix("no").unwrap_or(name);
+        if !self.files && FILES_OPTIONS.contains(&option) {
+            return Err(format!("'{option}' is only available in files buffers"));
+        }
+
+        match name {
+            "autosave" => self.autosave = parse(name, value, AUTOSAVE_DEFAULT)?,
+            "noautosave" => self.autosave = 0,
+            "preview" => self.preview = true,
+            "nopreview" => self.preview = false,
+            "tree" => self.tree = true,
+            "notree" => self.tree = false,
+            "iskeyword" => self.iskeyword = value.unwrap_or(ISKEYWORD_DEFAULT).to_string(),
+            "noiskeyword" => self.iskeyword.clear(),
+            "expandtab" => self.expandtab = true,
+            "noexpandtab" => self.expandtab = false,
+            "shiftwidth" => self.shiftwidth = parse(name, value, TAB_WIDTH)?.max(1),
+            "textwidth" => self.textwidth = parse(name, value, REFLOW_WIDTH_DEFAULT)?,
+            "wrap" => self.wrap = true,
+            "nowrap" => self.wrap = false,
+            "indentguides" => self.indentguides = true,
+            "noindentguides" => self.indentguides = false,
+            "mixedindent" => self.mixedindent = true,
+            "nomixedindent" => self.mixedindent = false,
+            "trailingspace" => self.trailingspace = true,
+            "notrailingspace" => self.trailingspace = false,
+            "changesigns" => self.changesigns = true,
+            "nochangesigns" => self.changesigns = false,
+            "synmaxcol" => self.synmaxcol = parse(name, value, SYNMAXCOL_DEFAULT)?,
+            "nosynmaxcol" => self.synmaxcol = 0,
+            "cursorline" => self.cursorline = true,
+            "nocursorline" => self.cursorline = false,
+            "cursorcolumn" => self.cursorcolumn = true,
+            "nocursorcolumn" => self.cursorcolumn = false,
+            "timeoutlen" => self.timeoutlen = parse(name, value, TIMEOUTLEN_DEFAULT)?,
+            "notimeoutlen" => self.timeoutlen = 0,
+            "colorcolumn" => {
+                self.colorcolumn = value
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|column| !column.is_empty())
+                    .map(|column| match column.trim().parse() {
+                        Ok(column) if column > 0 => Ok(column),
+                        _ => Err(format!("'{column}' is not a valid value for '{name}'")),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "nocolorcolumn" => self.colorcolumn.clear(),
+            "commitguide" => self.commitguide = true,
+            "nocommitguide" => self.commitguide = false,
+            "scrolloff" => self.scrolloff = parse(name, value, SCROLLOFF_DEFAULT)?,
+            "noscrolloff" => self.scrolloff = 0,
+            "history" => self.history = parse(name, value, HISTORY_DEFAULT)?,
+            "nohistory" => self.history = 0,
+            "scroll" => self.scroll = parse(name, value, 0)?,
+            "noscroll" => self.scroll = 0,
+            "termguicolors" => self.termguicolors = true,
+            "notermguicolors" => self.termguicolors = false,
+            "filetype" => self.set_filetype(value.unwrap_or_default()),
+            "nofiletype" => self.set_filetype(""),
+            "commentstring" => self.commentstring = value.unwrap_or_default().to_string(),
+            "nocommentstring" => self.commentstring.clear(),
+            "formatprg" => self.formatprg = value.unwrap_or_default().to_string(),
+            "noformatprg" => self.formatprg.clear(),
+            "formatonsave" => self.formatonsave = true,
+            "noformatonsave" => self.formatonsave = false,
+            "stripansi" => self.stripansi = true,
+            "nostripansi" => self.stripansi = false,
+            "trimwhitespace" => self.trimwhitespace = true,
+            "notrimwhitespace" => self.trimwhitespace = false,
+            "fixeol" => self.fixeol = true,
+            "nofixeol" => self.fixeol = false,
+            "fileformat" => match value {
+                Some(format @ ("lf" | "crlf")) => self.fileformat = format.to_string(),
+                _ => {
+                    return Err(format!(
+                        "'{}' is not a valid value for '{name}'",
+                        value.unwrap_or_default()
+                    ));
+                }
+            },
+            "nofileformat" => self.fileformat.clear(),
+            "edgemotion" => match value {
+                Some(motion @ ("bell" | "wrap")) => self.edgemotion = motion.to_string(),
+                _ => {
+                    return Err(format!(
+                        "'{}' is not a valid value for '{name}'",
+                        value.unwrap_or_default()
+                    ));
+                }
+            },
+            "noedgemotion" => self.edgemotion.clear(),
+            "trash" => self.trash = true,
+            "notrash" => self.trash = false,
+            "trashdir" => self.trashdir = value.unwrap_or_default().to_string(),
+            "notrashdir" => self.trashdir.clear(),
+            "clipboard" => match value {
+                Some(clipboard @ ("system" | "osc52" | "memory")) => {
+                    self.clipboard = clipboard.to_string();
+                }
+                _ => {
+                    return Err(format!(
+                        "'{}' is not a valid value for '{name}'",
+                        value.unwrap_or_default()
+                    ));
+                }
+            },
+            "noclipboard" => self.clipboard.clear(),
+            "listchars" => self.listchars = parse(name, value, ListChars::default())?,
+            "guicursor" => self.guicursor = parse(name, value, GuiCursor::default())?,
+            _ => return Err(format!("Unknown option: '{name}'")),
+        }
+
+        self.user_set.insert(option.to_string());
+        Ok(())
+    }
+
+    /// Lists all options and their current values.
+    pub fn list(&self) -> String {
+        let mut list = String::new();
+        writeln!(&mut list, "autosave={}", self.autosave).unwrap();
+        if self.files {
+            writeln!(&mut list, "preview={}", self.preview).unwrap();
+        }
+        writeln!(&mut list, "tree={}", self.tree).unwrap();
+        writeln!(&mut list, "iskeyword={}", self.iskeyword).unwrap();
+        writeln!(&mut list, "expandtab={}", self.expandtab).unwrap();
+        writeln!(&mut list, "shiftwidth={}", self.shiftwidth).unwrap();
+        writeln!(&mut list, "textwidth={}", self.textwidth).unwrap();
+        writeln!(&mut list, "wrap={}", self.wrap).unwrap();
+        writeln!(&mut list, "listchars={}", self.listchars).unwrap();
+        writeln!(&mut list, "guicursor={}", self.guicursor).unwrap();
+        writeln!(&mut list, "indentguides={}", self.indentguides).unwrap();
+        writeln!(&mut list, "mixedindent={}", self.mixedindent).unwrap();
+        writeln!(&mut list, "trailingspace={}", self.trailingspace).unwrap();
+        writeln!(&mut list, "changesigns={}", self.changesigns).unwrap();
+        writeln!(&mut list, "synmaxcol={}", self.synmaxcol).unwrap();
+        writeln!(&mut list, "cursorline={}", self.cursorline).unwrap();
+        writeln!(&mut list, "cursorcolumn={}", self.cursorcolumn).unwrap();
+        let colorcolumn = self
+            .colorcolumn
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        writeln!(&mut list, "colorcolumn={}", colorcolumn.join(",")).unwrap();
+        writeln!(&mut list, "commitguide={}", self.commitguide).unwrap();
+        writeln!(&mut list, "timeoutlen={}", self.timeoutlen).unwrap();
+        writeln!(&mut list, "scrolloff={}", self.scrolloff).unwrap();
+        writeln!(&mut list, "edgemotion={}", self.edgemotion).unwrap();
+        writeln!(&mut list, "history={}", self.history).unwrap();
+        writeln!(&mut list, "scroll={}", self.scroll).unwrap();
+        writeln!(&mut list, "termguicolors={}", self.termguicolors).unwrap();
+        writeln!(&mut list, "filetype={}", self.filetype).unwrap();
+        writeln!(&mut list, "commentstring={}", self.commentstring).unwrap();
+        writeln!(&mut list, "formatprg={}", self.formatprg).unwrap();
+        writeln!(&mut list, "formatonsave={}", self.formatonsave).unwrap();
+        writeln!(&mut list, "stripansi={}", self.stripansi).unwrap();
+        writeln!(&mut list, "trimwhitespace={}", self.trimwhitespace).unwrap();
+        writeln!(&mut list, "fixeol={}", self.fixeol).unwrap();
+        writeln!(&mut list, "fileformat={}", self.fileformat).unwrap();
+        writeln!(&mut list, "trash={}", self.trash).unwrap();
+        writeln!(&mut list, "trashdir={}", self.trashdir).unwrap();
+        writeln!(&mut list, "clipboard={}", self.clipboard).unwrap();
+
+        list.trim_end().to_string()
+    }
+}
+
+/// The glyphs used to render whitespace characters.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ListChars {
+    /// The glyph of the first cell of a tab.
+    pub tab_lead: char,
+    /// The glyph of the remaining cells of a tab.
+    pub tab_fill: char,
+    /// The glyph of a space.
+    pub space: char,
+    /// The glyph of a line break.
+    pub eol: char,
+}
+
+impl Default for ListChars {
+    fn default() -> Self {
+        Self {
+            tab_lead: '↦',
+            tab_fill: '·',
+            space: '·',
+            eol: '⏎',
+        }
+    }
+}
+
+impl FromStr for ListChars {
+    type Err = ();
+
+    /// Parses a comma separated list of `<kind>:<glyphs>` like `tab:↦·,space:·,eol:⏎`. Missing kinds keep their
+    /// default glyphs.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut listchars = Self::default();
+
+        for item in value.split(',') {
+            let (kind, glyphs) = item.split_once(':').ok_or(())?;
+            let glyphs = glyphs.chars().collect::<Vec<_>>();
+
+            match (kind, glyphs.as_slice()) {
+                ("tab", [lead, fill]) => (listchars.tab_lead, listchars.tab_fill) = (*lead, *fill),
+                ("space", [space]) => listchars.space = *space,
+                ("eol", [eol]) => listchars.eol = *eol,
+                _ => return Err(()),
+            }
+        }
+
+        Ok(listchars)
+    }
+}
+
+impl Display for ListChars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tab:{}{},space:{},eol:{}",
+            self.tab_lead, self.tab_fill, self.space, self.eol
+        )
+    }
+}
+
+/// The cursor styles of the modes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct GuiCursor {
+    /// The cursor style in view mode.
+    pub view: CursorStyle,
+    /// The cursor style in insert mode.
+    pub insert: CursorStyle,
+    /// The cursor style in command mode.
+    pub command: CursorStyle,
+}
+
+impl Default for GuiCursor {
+    fn default() -> Self {
+        Self {
+            view: CursorStyle::SteadyBlock,
+            insert: CursorStyle::SteadyBar,
+            command: CursorStyle::SteadyBar,
+        }
+    }
+}
+
+impl FromStr for GuiCursor {
+    type Err = ();
+
+    /// Parses a comma separated list of `<mode>:<style>` like `v:block,i:bar-blink,c:underline` with the modes `v`, `i`
+    /// and `c`. Missing modes keep their default style.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut guicursor = Self::default();
+
+        for item in value.split(',') {
+            let (mode, style) = item.split_once(':').ok_or(())?;
+            let style = style.parse()?;
+
+            match mode {
+                "v" => guicursor.view = style,
+                "i" => guicursor.insert = style,
+                "c" => guicursor.command = style,
+                _ => return Err(()),
+            }
+        }
+
+        Ok(guicursor)
+    }
+}
+
+impl Display for GuiCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v:{},i:{},c:{}", self.view, self.insert, self.command)
+    }
+}
+
+/// Parses the value of an option, using a default if no value was specified.
+fn parse<T: FromStr>(name: &str, value: Option<&str>, default: T) -> Result<T, String> {
+    value.map_or(Ok(default), |value| {
+        value
+            .parse()
+            .map_err(|_| format!("'{value}' is not a valid value for '{name}'"))
+    })
+}
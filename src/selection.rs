@@ -40,7 +40,9 @@ impl Selection {
         self.head_line_len = line_len;
     }
 
-    /// Returns the range of the selection.
+    /// Returns the range of the selection. Normal selections span from the earlier to the later one of anchor and head,
+    /// exclusive of the later one. A head behind the last character of the buffer therefore selects up to and including
+    /// that character. Line selections span their first to last line including the line break of the last line.
     pub fn range(&self) -> (Cursor, Cursor) {
         let start = self.anchor.min(self.head);
         let end = self.anchor.max(self.head);
@@ -62,7 +64,7 @@ impl Selection {
         }
     }
 
-    /// Checks if a cursor is inside the selection.
+    /// Checks if a cursor is inside the selection, agreeing with the text covered by `range`.
     pub fn contains(&self, cur: Cursor) -> bool {
         let (start, end) = self.range();
 
@@ -95,3 +97,74 @@ impl Ord for Selection {
         anchor1.cmp(&anchor2)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+
+    /// Selects whole lines from the line of the anchor to the line of the head.
+    fn lines(doc: &Document, anchor: usize, head: usize) -> Selection {
+        Selection::new(
+            Cursor::new(0, anchor),
+            Cursor::new(0, head),
+            SelectionKind::Line,
+            doc.line_count(anchor),
+            doc.line_count(head),
+        )
+    }
+
+    #[test]
+    fn line_selections_contain_every_cell_of_their_lines() {
+        let doc = Document::new(0, 0, Some(String::from("a\n\nbc\n\nd")));
+
+        for (anchor, head) in [(0, 3), (3, 0), (1, 1)] {
+            let selection = lines(&doc, anchor, head);
+            let (first, last) = (anchor.min(head), anchor.max(head));
+
+            for y in 0..doc.len() {
+                // Every character including the line break and the position after it.
+                for x in 0..=doc.line_count(y).unwrap() {
+                    let selected = (first..=last).contains(&y);
+                    assert_eq!(
+                        selection.contains(Cursor::new(x, y)),
+                        selected,
+                        "({x}, {y}) of {anchor}..{head}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn line_selections_cover_the_line_break_of_their_last_line() {
+        let doc = Document::new(0, 0, Some(String::from("a\n\nbc\n\nd")));
+        let text = |selection: Selection| {
+            let (start, end) = selection.range();
+            doc.get_range(start, end).unwrap().to_string()
+        };
+
+        assert_eq!(text(lines(&doc, 0, 1)), "a\n\n");
+        assert_eq!(text(lines(&doc, 3, 1)), "\nbc\n\n");
+        assert_eq!(text(lines(&doc, 1, 1)), "\n");
+        assert_eq!(text(lines(&doc, 3, 4)), "\nd");
+    }
+
+    #[test]
+    fn selections_to_the_end_of_the_document_contain_its_last_character() {
+        let doc = Document::new(0, 0, Some(String::from("abc\nghi")));
+        let selection = Selection::new(
+            Cursor::new(1, 0),
+            Cursor::new(3, 1),
+            SelectionKind::Normal,
+            None,
+            None,
+        );
+
+        let (start, end) = selection.range();
+        assert_eq!(doc.get_range(start, end).unwrap().to_string(), "bc\nghi");
+        assert!(selection.contains(Cursor::new(2, 1)));
+        assert!(!selection.contains(Cursor::new(3, 1)));
+        assert!(!selection.contains(Cursor::new(0, 0)));
+    }
+}
@@ -2,11 +2,12 @@ pub mod util;
 
 use crate::{
     buffer::BufferResult,
-    shell_command::util::{application_key_to_string, key_to_string},
+    shell_command::util::{application_key_to_string, key_to_string, strip_ansi},
 };
-use portable_pty::{CommandBuilder, MasterPty, PtySize, native_pty_system};
+use portable_pty::{CommandBuilder, ExitStatus, MasterPty, PtySize, native_pty_system};
 use std::{
     io::{Error, Read, Write},
+    process::{Command, Stdio},
     sync::mpsc::{self, Receiver},
     thread,
 };
@@ -14,11 +15,13 @@ use termion::event::Key;
 use vt100::Parser;
 
 const SCROLLBACK_LEN: usize = 5000;
+/// The frames of the spinner indicating received output.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
 pub enum ShellCommandResult {
     Data(Vec<u8>),
     Error(String),
-    Eof,
+    Eof(ExitStatus),
 }
 
 /// A helper to run shell commands in the background and stream the output.
@@ -36,6 +39,11 @@ pub struct ShellCommand {
 
     /// The VT100 parser maintaining the terminal state.
     pub parser: Parser,
+
+    /// Amount of output bytes received.
+    pub received: usize,
+    /// Amount of output chunks received, used to advance the spinner.
+    chunks: usize,
 }
 
 impl ShellCommand {
@@ -107,12 +115,14 @@ impl ShellCommand {
                 }
             }
 
-            if let Err(err) = child.wait() {
-                let _ = tx.send(Error(err.to_string()));
-                return;
+            match child.wait() {
+                Ok(status) => {
+                    let _ = tx.send(Eof(status));
+                }
+                Err(err) => {
+                    let _ = tx.send(Error(err.to_string()));
+                }
             }
-
-            let _ = tx.send(Eof);
         });
 
         // The indices are bound by terminal dimensions.
@@ -124,6 +134,8 @@ impl ShellCommand {
             master: pair.master,
             writer,
             parser,
+            received: 0,
+            chunks: 0,
         })
     }
 
@@ -159,6 +171,35 @@ impl ShellCommand {
         self.writer.flush()
     }
 
+    /// Processes output of the command.
+    pub fn process(&mut self, data: &[u8]) {
+        self.parser.process(data);
+        self.received += data.len();
+        self.chunks += 1;
+    }
+
+    /// Scrolls the view up into the scrollback.
+    pub fn scroll_up(&mut self, n: usize) {
+        let screen = self.parser.screen_mut();
+        screen.set_scrollback(screen.scrollback() + n);
+    }
+
+    /// Scrolls the view down towards the live output.
+    pub fn scroll_down(&mut self, n: usize) {
+        let screen = self.parser.screen_mut();
+        screen.set_scrollback(screen.scrollback().saturating_sub(n));
+    }
+
+    /// Gets the amount of lines the view is scrolled into the scrollback.
+    pub fn scrollback(&self) -> usize {
+        self.parser.screen().scrollback()
+    }
+
+    /// Gets the current frame of the spinner, advancing with each output chunk.
+    pub const fn spinner(&self) -> char {
+        SPINNER[self.chunks % SPINNER.len()]
+    }
+
     /// Get all data of the command.
     pub fn contents(&mut self) -> String {
         let screen = self.parser.screen_mut();
@@ -182,3 +223,44 @@ impl ShellCommand {
         contents
     }
 }
+
+/// Runs a shell command with the input on its standard input and waits for it to finish. Returns the standard output of
+/// the command, without ANSI escape sequences if `strip` is set, or, if it failed, an error containing its standard
+/// error.
+pub fn filter(cmd: &str, input: String, strip: bool) -> Result<String, String> {
+    let mut child = Command::new("fish")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+
+    // Write the input on another thread so a command writing output before reading all of its input can't block.
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+    // Commands are free to stop reading their input early.
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let status = output.status.code().map_or_else(
+            || "killed by a signal".to_string(),
+            |code| format!("exit code {code}"),
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut err = format!("'{cmd}' failed ({status})");
+        if !stderr.trim().is_empty() {
+            err.push_str(":\n");
+            err.push_str(stderr.trim_end());
+        }
+        return Err(err);
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|_| format!("'{cmd}' wrote output that is not valid UTF-8"))?;
+
+    // Without a terminal there is no parser to interpret the escape sequences of colored output.
+    Ok(if strip { strip_ansi(&stdout) } else { stdout })
+}
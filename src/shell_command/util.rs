@@ -92,3 +92,91 @@ pub fn application_key_to_string(key: Key) -> Option<String> {
         _ => None,
     }
 }
+
+/// Removes ANSI escape sequences like colors and cursor movements from the output of a command that did not run in a
+/// terminal. Control sequences, operating system commands like hyperlinks and other escapes are dropped as a whole.
+pub fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            stripped.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            // Control sequence: parameter and intermediate bytes followed by a final byte.
+            Some('[') => {
+                for ch in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&ch) {
+                        break;
+                    }
+                }
+            }
+            // Operating system command: terminated by BEL or ESC '\'.
+            Some(']') => {
+                while let Some(ch) = chars.next() {
+                    if ch == '\x07' {
+                        break;
+                    }
+                    if ch == '\x1b' && chars.next_if_eq(&'\\').is_some() {
+                        break;
+                    }
+                }
+            }
+            // Other escapes: intermediate bytes followed by a final byte, like ESC '(' 'B'.
+            Some(ch) if ('\x20'..='\x2f').contains(&ch) => {
+                while chars.next_if(|ch| ('\x20'..='\x2f').contains(ch)).is_some() {}
+                chars.next();
+            }
+            Some(_) | None => {}
+        }
+    }
+
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_ansi_removes_colors() {
+        // Output of 'grep --color=always fn' and 'ls --color=always'.
+        assert_eq!(
+            strip_ansi("\x1b[01;31m\x1b[Kfn\x1b[m\x1b[K main() {}\n"),
+            "fn main() {}\n"
+        );
+        assert_eq!(
+            strip_ansi("\x1b[0m\x1b[01;34msrc\x1b[0m  Cargo.toml\n"),
+            "src  Cargo.toml\n"
+        );
+        // 256 and RGB colors of 'bat' and 'delta'.
+        assert_eq!(
+            strip_ansi("\x1b[38;5;203mlet\x1b[0m \x1b[38;2;248;248;242mx\x1b[0m"),
+            "let x"
+        );
+    }
+
+    #[test]
+    fn strip_ansi_removes_other_escapes() {
+        // Hyperlinks terminated by ESC '\' and by BEL.
+        assert_eq!(
+            strip_ansi(
+                "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ and \x1b]0;title\x07text"
+            ),
+            "link and text"
+        );
+        // Cursor movement, line clearing and charset selection.
+        assert_eq!(strip_ansi("a\x1b[2K\x1b[1Gb\x1b(Bc\x1b=d"), "abcd");
+    }
+
+    #[test]
+    fn strip_ansi_keeps_plain_text() {
+        let text = "plain [text] with ] brackets\tand ünïcode\n";
+        assert_eq!(strip_ansi(text), text);
+        // A trailing lone escape is dropped.
+        assert_eq!(strip_ansi("end\x1b"), "end");
+    }
+}
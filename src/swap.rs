@@ -0,0 +1,94 @@
+use crate::{cursor::Cursor, document::Document};
+use std::{
+    fs::{self, OpenOptions, Permissions},
+    io::{BufWriter, Error, ErrorKind, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Gets the path of the swap file of a file, `.<name>.swp` next to the file.
+fn swap_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    Some(path.with_file_name(format!(".{name}.swp")))
+}
+
+/// Writes the contents and cursor of a document to the swap file of a file.
+pub fn write(path: &Path, doc: &Document) -> Result<(), Error> {
+    let swap_path = swap_path(path)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Path has no file name"))?;
+
+    // The swap holds the contents of the file, which might be private, so only the user may read it. Swap files left
+    // by earlier versions are restricted as well.
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(swap_path)?;
+    file.set_permissions(Permissions::from_mode(0o600))?;
+
+    let mut file = BufWriter::new(file);
+    writeln!(file, "{}:{}", doc.cur.y, doc.cur.x)?;
+    doc.write_to(&mut file)?;
+    file.flush()
+}
+
+/// Reads the contents and cursor of the swap file of a file.
+pub fn read(path: &Path) -> Result<(String, Cursor), Error> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "Invalid swap file");
+
+    let contents = fs::read_to_string(swap_path(path).ok_or_else(invalid)?)?;
+    let (cursor, contents) = contents.split_once('\n').ok_or_else(invalid)?;
+    let (y, x) = cursor.split_once(':').ok_or_else(invalid)?;
+    let cursor = Cursor::new(
+        x.parse().map_err(|_| invalid())?,
+        y.parse().map_err(|_| invalid())?,
+    );
+
+    Ok((contents.to_string(), cursor))
+}
+
+/// Removes the swap file of a file if it exists.
+pub fn remove(path: &Path) {
+    if let Some(swap_path) = swap_path(path) {
+        let _ = fs::remove_file(swap_path);
+    }
+}
+
+/// Checks if the swap file of a file exists and was modified after the file.
+pub fn is_newer(path: &Path) -> bool {
+    let Some(swap) = swap_path(path).and_then(|swap_path| fs::metadata(swap_path).ok()) else {
+        return false;
+    };
+
+    // A swap file without its file is always worth recovering.
+    fs::metadata(path)
+        .and_then(|file| file.modified())
+        .map_or(true, |modified| {
+            swap.modified().is_ok_and(|swap| swap > modified)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_file_is_only_readable_by_the_user() {
+        let dir = std::env::temp_dir().join(format!("mini-swap-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("secret");
+        let swap = swap_path(&path).unwrap();
+
+        // An existing swap file readable by others is restricted too.
+        fs::write(&swap, "").unwrap();
+        fs::set_permissions(&swap, Permissions::from_mode(0o644)).unwrap();
+
+        write(&path, &Document::new(0, 0, Some("secret".to_string()))).unwrap();
+        let mode = fs::metadata(&swap).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(read(&path).unwrap().0, "secret");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
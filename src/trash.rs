@@ -0,0 +1,136 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Gets the trash directory, either the configured one or the XDG trash inside the data directory.
+fn trash_dir(dir: &str) -> Result<PathBuf, Error> {
+    if !dir.is_empty() {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "No data directory found"))?;
+
+    Ok(data.join("Trash"))
+}
+
+/// Moves a file or directory to the trash following the freedesktop.org trash layout. The entry is moved to `files`
+/// and its original location is recorded in `info`. Returns the path of the trashed entry.
+pub fn trash(path: &Path, dir: &str) -> Result<PathBuf, Error> {
+    let path = std::path::absolute(path)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Path has no file name"))?
+        .to_string_lossy()
+        .to_string();
+
+    let trash = trash_dir(dir)?;
+    let files = trash.join("files");
+    let info = trash.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    // Find a name that is not taken in the trash yet.
+    let mut trash_name = name.clone();
+    let mut n = 1;
+    while files.join(&trash_name).symlink_metadata().is_ok()
+        || info.join(format!("{trash_name}.trashinfo")).exists()
+    {
+        n += 1;
+        trash_name = format!("{name}.{n}");
+    }
+
+    let target = files.join(&trash_name);
+    let info = info.join(format!("{trash_name}.trashinfo"));
+    fs::write(
+        &info,
+        format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&path),
+            deletion_date()
+        ),
+    )?;
+
+    // Renaming fails across file systems, copy the entry over instead. An entry that could not be copied is not in the
+    // trash, so its partial copy and its info are removed again.
+    if fs::rename(&path, &target).is_err() {
+        if let Err(err) = copy_all(&path, &target) {
+            let _ = remove_all(&target);
+            let _ = fs::remove_file(&info);
+            return Err(err);
+        }
+        remove_all(&path)?;
+    }
+
+    Ok(target)
+}
+
+/// Removes a file or a directory with all of its contents. Symlinks are removed, not followed.
+fn remove_all(path: &Path) -> Result<(), Error> {
+    if path.is_dir() && !path.is_symlink() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Recursively copies a file or directory. Symlinks are copied as symlinks.
+fn copy_all(from: &Path, to: &Path) -> Result<(), Error> {
+    let meta = from.symlink_metadata()?;
+    if meta.is_symlink() {
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)
+    } else if meta.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// Percent-encodes a path for a trash info file, keeping slashes and unreserved characters.
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.as_os_str().as_encoded_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(byte) {
+            encoded.push(*byte as char);
+        } else {
+            write!(&mut encoded, "%{byte:02X}").unwrap();
+        }
+    }
+
+    encoded
+}
+
+/// Formats the current local time as `YYYY-MM-DDThh:mm:ss`, as the trash specification requires.
+fn deletion_date() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let time = libc::time_t::try_from(secs).unwrap_or(libc::time_t::MAX);
+
+    // SAFETY: `localtime_r` only writes to the given `tm`, which is valid for writes and only read if it succeeded.
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    if unsafe { libc::localtime_r(&raw const time, &raw mut tm) }.is_null() {
+        return "1970-01-01T00:00:00".to_string();
+    }
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
@@ -1,9 +1,14 @@
+use crate::buffer::BufferResult;
 use std::{
+    fmt::Write,
     fs::{File, OpenOptions},
-    io::Error,
-    path::Path,
+    io::{Error, ErrorKind},
+    iter::{Enumerate, Peekable},
+    path::{Path, PathBuf},
+    str::Chars,
 };
 
+use termion::event::Key;
 use unicode_width::UnicodeWidthChar;
 
 pub const TAB_WIDTH: usize = 4;
@@ -15,6 +20,12 @@ pub fn file_name<P: AsRef<Path>>(path: P) -> Option<String> {
         .map(|p| p.to_string_lossy().to_string())
 }
 
+/// Makes a path absolute using the current directory, keeping it unchanged if that fails. Buffers store absolute paths
+/// so they keep referring to the same file after the current directory changed.
+pub fn absolute_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    std::path::absolute(&path).unwrap_or_else(|_| path.as_ref().to_path_buf())
+}
+
 /// Opens a file as rw+truncate.
 pub fn open_file<P: AsRef<Path>>(path: P) -> Result<File, Error> {
     // Create parent directories if they don't exist.
@@ -32,6 +43,203 @@ pub fn open_file<P: AsRef<Path>>(path: P) -> Result<File, Error> {
         .open(path)
 }
 
+/// Opens a file as rw+truncate, falling back to opening it read-only if writing is not permitted. Returns the file and
+/// if it was opened read-only.
+pub fn open_file_or_read_only<P: AsRef<Path>>(path: P) -> Result<(File, bool), Error> {
+    match open_file(&path) {
+        Ok(file) => Ok((file, false)),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => OpenOptions::new()
+            .read(true)
+            .open(path)
+            .map(|file| (file, true))
+            .map_err(|_| err),
+        Err(err) => Err(err),
+    }
+}
+
+/// Quotes a string to be passed as a single argument to a shell command.
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// A parsed path argument.
+pub struct PathArg {
+    /// The path with `~` and environment variables expanded.
+    pub path: String,
+    /// A warning listing environment variables that are not set and were left as-is.
+    pub warning: Option<String>,
+}
+
+/// Parses whitespace separated command arguments. Arguments can be quoted using '"' or '\'' and characters can be
+/// escaped using '\\' outside of single quotes. A leading unquoted '~' expands to the home directory and '$VAR' or
+/// '${VAR}' outside of single quotes to the value of the environment variable. Variables that are not set are kept
+/// as-is and collected in `unknown`.
+pub fn parse_args(input: &str, unknown: &mut Vec<String>) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    let mut chars = input.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (None | Some('"'), '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| "Trailing '\\' in arguments".to_string())?;
+                arg.get_or_insert_default().push(escaped);
+            }
+            (None | Some('"'), '$') => {
+                let value = expand_var(&mut chars, unknown);
+                arg.get_or_insert_default().push_str(&value);
+            }
+            (None, '~')
+                if arg.is_none()
+                    && chars
+                        .peek()
+                        .is_none_or(|ch| *ch == '/' || ch.is_whitespace()) =>
+            {
+                arg = Some(home_dir().unwrap_or_else(|| "~".to_string()));
+            }
+            (None, '\'' | '"') => {
+                quote = Some(ch);
+                arg.get_or_insert_default();
+            }
+            (None, ch) if ch.is_whitespace() => args.extend(arg.take()),
+            (_, ch) => arg.get_or_insert_default().push(ch),
+        }
+    }
+
+    if let Some(quote) = quote {
+        return Err(format!("Unterminated quote {quote} in arguments"));
+    }
+    args.extend(arg);
+
+    Ok(args)
+}
+
+/// Parses a single path argument. Arguments without quotes or escapes are used verbatim to allow unquoted spaces, only
+/// expanding '~' and environment variables.
+pub fn parse_path(input: &str) -> Result<PathArg, String> {
+    let mut unknown = Vec::new();
+
+    let path = if input.contains(['"', '\'', '\\']) {
+        let mut args = parse_args(input, &mut unknown)?;
+        if args.len() != 1 {
+            return Err(format!(
+                "Expected a single path but got {} arguments",
+                args.len()
+            ));
+        }
+
+        args.remove(0)
+    } else {
+        let mut path = String::new();
+        let mut chars = input.chars().peekable();
+        if chars.next_if_eq(&'~').is_some() {
+            if chars.peek().is_none_or(|ch| *ch == '/') {
+                path = home_dir().unwrap_or_else(|| "~".to_string());
+            } else {
+                path.push('~');
+            }
+        }
+        while let Some(ch) = chars.next() {
+            if ch == '$' {
+                path.push_str(&expand_var(&mut chars, &mut unknown));
+            } else {
+                path.push(ch);
+            }
+        }
+
+        path
+    };
+
+    let warning = unknown_vars_warning(&unknown);
+
+    Ok(PathArg { path, warning })
+}
+
+/// Parses the path argument of a command like `parse_path`, turning an invalid argument into the error result of the
+/// command.
+pub fn path_arg(input: &str) -> Result<PathArg, BufferResult> {
+    parse_path(input).map_err(BufferResult::Error)
+}
+
+/// Creates the warning listing environment variables that are not set, if there are any.
+pub fn unknown_vars_warning(unknown: &[String]) -> Option<String> {
+    (!unknown.is_empty()).then(|| {
+        let label = if unknown.len() == 1 {
+            "variable"
+        } else {
+            "variables"
+        };
+        format!(
+            "Environment {label} {} not set, kept as-is",
+            unknown.join(", ")
+        )
+    })
+}
+
+/// Expands the environment variable following a '$'. Returns the variable as written if it is not set.
+fn expand_var(chars: &mut Peekable<Chars>, unknown: &mut Vec<String>) -> String {
+    let is_name_char = |ch: &char| *ch == '_' || ch.is_ascii_alphanumeric();
+
+    let mut name = String::new();
+    let written = if chars.next_if_eq(&'{').is_some() {
+        let mut closed = false;
+        for ch in chars.by_ref() {
+            if ch == '}' {
+                closed = true;
+                break;
+            }
+            name.push(ch);
+        }
+
+        // Unterminated braces are kept literally.
+        if !closed {
+            return format!("${{{name}");
+        }
+
+        format!("${{{name}}}")
+    } else {
+        while let Some(ch) = chars.next_if(is_name_char) {
+            name.push(ch);
+        }
+
+        format!("${name}")
+    };
+
+    if name.is_empty() {
+        return written;
+    }
+
+    std::env::var(&name).unwrap_or_else(|_| {
+        if !unknown.contains(&written) {
+            unknown.push(written.clone());
+        }
+        written
+    })
+}
+
+/// Returns the home directory of the user.
+fn home_dir() -> Option<String> {
+    std::env::var("HOME").ok()
+}
+
+/// Quotes an argument if needed so it is parsed back unchanged by `parse_path`.
+pub fn quote_path(path: &str) -> String {
+    if !path.contains(['"', '\'', '\\', '$']) && !path.starts_with('~') && path.trim() == path {
+        return path.to_string();
+    }
+
+    format!(
+        "\"{}\"",
+        path.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$")
+    )
+}
+
 /// Parses a line column string 'y:x' where y is the line and x is the column.
 pub fn line_column(input: &str) -> (Option<usize>, Option<usize>) {
     let mut y: Option<usize> = None;
@@ -48,12 +256,290 @@ pub fn line_column(input: &str) -> (Option<usize>, Option<usize>) {
     (x, y)
 }
 
+/// A character of a line together with its position and width when displayed.
+#[derive(Clone, Copy)]
+pub struct VisualCell {
+    /// The character index in the line.
+    pub idx: usize,
+    /// The character.
+    pub ch: char,
+    /// The visual column the character starts at.
+    pub x: usize,
+    /// The amount of columns the character takes up. Zero for characters that are not displayed.
+    pub width: usize,
+}
+
+/// An iterator over the visual cells of a line. Tabs expand to the next tab stop, line breaks and spaces take up one
+/// column and all other characters their unicode width.
+pub struct VisualCells<I> {
+    chars: Enumerate<I>,
+    x: usize,
+}
+
+impl<I: Iterator<Item = char>> Iterator for VisualCells<I> {
+    type Item = VisualCell;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, ch) = self.chars.next()?;
+        let width = match ch {
+            ' ' | '\n' | '\r' => 1,
+            '\t' => TAB_WIDTH - (self.x % TAB_WIDTH),
+            // Control characters are displayed as their control picture.
+            ch if ch.is_ascii_control() => 1,
+            ch => ch.width().unwrap_or(0),
+        };
+
+        let cell = VisualCell {
+            idx,
+            ch,
+            x: self.x,
+            width,
+        };
+        self.x += width;
+
+        Some(cell)
+    }
+}
+
+/// Creates an iterator over the visual cells of a line.
+pub fn visual_cells<I: IntoIterator<Item = char>>(chars: I) -> VisualCells<I::IntoIter> {
+    VisualCells {
+        chars: chars.into_iter().enumerate(),
+        x: 0,
+    }
+}
+
+/// Gets the symbol representing an ASCII control character, like '␛' for the escape character.
+pub const fn control_picture(ch: char) -> Option<char> {
+    match ch {
+        '\x7f' => Some('␡'),
+        ch if ch.is_ascii_control() => char::from_u32(0x2400 + ch as u32),
+        _ => None,
+    }
+}
+
+/// Converts a key to the character it represents when inserted literally.
+pub const fn literal_char(key: Key) -> Option<char> {
+    match key {
+        Key::Char(ch) => Some(ch),
+        Key::Esc => Some('\x1b'),
+        Key::Backspace => Some('\x7f'),
+        Key::Ctrl(ch) if ch.is_ascii_lowercase() => char::from_u32(ch as u32 - 'a' as u32 + 1),
+        _ => None,
+    }
+}
+
 /// Calculates the width of text up to a character index.
 pub fn text_width(text: &str, char_idx: usize) -> usize {
-    text.chars().take(char_idx).fold(0, |acc, ch| {
-        acc + match ch {
-            '\t' => TAB_WIDTH - (acc % TAB_WIDTH),
-            ch => ch.width().unwrap_or(0),
+    visual_cells(text.chars())
+        .take(char_idx)
+        .map(|cell| cell.width)
+        .sum()
+}
+
+/// Describes a character by its codepoint, its class and its UTF-8 bytes, like `'é' U+00E9 lowercase letter, non-ASCII,
+/// UTF-8 C3 A9`.
+pub fn char_info(ch: char) -> String {
+    let class = if ch.is_control() {
+        "control character"
+    } else if ch.is_whitespace() {
+        "whitespace"
+    } else if ch.is_uppercase() {
+        "uppercase letter"
+    } else if ch.is_lowercase() {
+        "lowercase letter"
+    } else if ch.is_alphabetic() {
+        "letter"
+    } else if ch.is_numeric() {
+        "digit"
+    } else if ch.is_ascii_punctuation() {
+        "punctuation"
+    } else {
+        "symbol"
+    };
+
+    let mut info = format!("'{}' U+{:04X} {class}", ch.escape_debug(), u32::from(ch));
+    // Invisible and look-alike characters are easy to mistake for ASCII ones.
+    if !ch.is_control() && ch.width() == Some(0) {
+        info.push_str(", zero-width");
+    }
+    if !ch.is_ascii() {
+        info.push_str(", non-ASCII");
+    }
+
+    let mut buf = [0; 4];
+    let bytes = ch
+        .encode_utf8(&mut buf)
+        .bytes()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>();
+    write!(&mut info, ", UTF-8 {}", bytes.join(" ")).unwrap();
+
+    info
+}
+
+/// Formats an amount of bytes using binary units with one decimal, like `1.5 KiB`.
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    // Scale by ten to keep one decimal.
+    let mut scaled = bytes.saturating_mul(10);
+    let mut unit = 0;
+    while scaled >= 1024 * 10 && unit + 1 < UNITS.len() {
+        scaled /= 1024;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{}.{} {}", scaled / 10, scaled % 10, UNITS[unit])
+    }
+}
+
+/// Calculates the index of the character covering a visual column of a text. Returns the count of characters if the
+/// text is shorter than the column.
+pub fn char_idx_at_width(text: &str, width: usize) -> usize {
+    visual_cells(text.chars())
+        .find(|cell| cell.x + cell.width > width)
+        .map_or_else(|| text.chars().count(), |cell| cell.idx)
+}
+
+/// Changes the case of a text to follow the case pattern of another text. If the pattern is all caps the text is
+/// uppercased, if it is capitalized the text is capitalized and otherwise the text is lowercased.
+pub fn match_case(text: &str, pattern: &str) -> String {
+    let mut letters = pattern.chars().filter(|ch| ch.is_alphabetic());
+    let Some(first) = letters.next() else {
+        return text.to_string();
+    };
+
+    let rest = letters.collect::<Vec<_>>();
+    if first.is_uppercase() && !rest.is_empty() && rest.iter().all(|ch| ch.is_uppercase()) {
+        return text.to_uppercase();
+    }
+
+    let lower = text.to_lowercase();
+    if first.is_uppercase() {
+        let mut chars = lower.chars();
+        return chars
+            .next()
+            .map(|ch| ch.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+    }
+
+    lower
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args_splits_on_unquoted_whitespace() {
+        let mut unknown = Vec::new();
+        let args = parse_args(
+            r#"a "my file.txt" 'it''s' my\ file.txt "say \"hi\"" '' $MINI_UNSET_VAR"#,
+            &mut unknown,
+        );
+
+        assert_eq!(
+            args.unwrap(),
+            [
+                "a",
+                "my file.txt",
+                "its",
+                "my file.txt",
+                "say \"hi\"",
+                "",
+                "$MINI_UNSET_VAR"
+            ]
+        );
+        assert_eq!(unknown, ["$MINI_UNSET_VAR"]);
+
+        // Single quotes keep everything verbatim.
+        assert_eq!(parse_args(r"'a\ $b'", &mut unknown).unwrap(), [r"a\ $b"]);
+    }
+
+    #[test]
+    fn parse_args_rejects_unterminated_input() {
+        let mut unknown = Vec::new();
+        assert!(parse_args("\"my file.txt", &mut unknown).is_err());
+        assert!(parse_args("'my file.txt", &mut unknown).is_err());
+        assert!(parse_args("file\\", &mut unknown).is_err());
+    }
+
+    #[test]
+    fn parse_path_accepts_spaces() {
+        let path = |input| parse_path(input).map(|arg| arg.path);
+
+        // Unquoted paths are used verbatim.
+        assert_eq!(path("my file.txt").unwrap(), "my file.txt");
+        assert_eq!(path("\"my file.txt\"").unwrap(), "my file.txt");
+        assert_eq!(path("my\\ file.txt").unwrap(), "my file.txt");
+        assert_eq!(path("'quote\"s.txt'").unwrap(), "quote\"s.txt");
+        assert!(path("\"a\" \"b\"").is_err());
+
+        if let Some(home) = home_dir() {
+            assert_eq!(
+                path("~/my file.txt").unwrap(),
+                format!("{home}/my file.txt")
+            );
+            assert_eq!(path("\"~/my file.txt\"").unwrap(), "~/my file.txt");
         }
-    })
+
+        let arg = parse_path("$MINI_UNSET_VAR/file").unwrap();
+        assert_eq!(arg.path, "$MINI_UNSET_VAR/file");
+        assert!(arg.warning.unwrap().contains("MINI_UNSET_VAR"));
+    }
+
+    #[test]
+    fn parse_path_expands_home_and_variables() {
+        let path = |input| parse_path(input).unwrap();
+
+        if let Ok(home) = std::env::var("HOME") {
+            assert_eq!(path("~/notes.txt").path, format!("{home}/notes.txt"));
+            assert_eq!(path("$HOME/notes.txt").path, format!("{home}/notes.txt"));
+            assert_eq!(path("${HOME}/notes.txt").path, format!("{home}/notes.txt"));
+            assert!(path("$HOME/notes.txt").warning.is_none());
+        }
+
+        // Only a leading '~' refers to the home directory.
+        assert_eq!(path("~notes.txt").path, "~notes.txt");
+        assert_eq!(path("notes~.txt").path, "notes~.txt");
+
+        // A '$' not followed by a variable name is literal.
+        for literal in ["price$", "a$.txt", "$", "${unterminated"] {
+            let arg = path(literal);
+            assert_eq!(arg.path, literal);
+            assert!(arg.warning.is_none());
+        }
+        assert_eq!(path("'$HOME'").path, "$HOME");
+        assert_eq!(path("\\$HOME").path, "$HOME");
+
+        let arg = path("${MINI_UNSET_VAR}/$MINI_OTHER_VAR");
+        assert_eq!(arg.path, "${MINI_UNSET_VAR}/$MINI_OTHER_VAR");
+        assert_eq!(
+            arg.warning.as_deref(),
+            Some("Environment variables ${MINI_UNSET_VAR}, $MINI_OTHER_VAR not set, kept as-is")
+        );
+    }
+
+    #[test]
+    fn quote_path_round_trips() {
+        for path in [
+            "plain.txt",
+            "my file.txt",
+            "~/x",
+            "$HOME",
+            "it's \"x\".txt",
+            " padded ",
+            "back\\slash",
+        ] {
+            assert_eq!(
+                parse_path(&quote_path(path)).unwrap().path,
+                path,
+                "{path:?}"
+            );
+        }
+    }
 }
@@ -1,12 +1,14 @@
 use crate::{
-    cursor::{Cursor, CursorStyle},
-    display::{Cell, Display, PLACEHOLDER},
+    cursor::{self, Cursor, CursorStyle},
+    display::{Attrs, Cell, Display, PLACEHOLDER},
     document::Document,
     message::{Message, MessageKind},
-    selection::Selection,
+    options::Options,
+    selection::{Selection, SelectionKind},
     shell_command::util::vt100_color_to_rgb,
-    util::{TAB_WIDTH, text_width},
+    util::{TAB_WIDTH, VisualCell, char_idx_at_width, control_picture, text_width, visual_cells},
 };
+use ropey::RopeSlice;
 use termion::color::{self, Bg, Fg};
 use unicode_width::UnicodeWidthChar;
 use vt100::Parser;
@@ -18,6 +20,11 @@ macro_rules! shift {
         $self.base.doc_view.$func(&mut $self.base.doc, 1);
         $self.base.update_selection();
     }};
+    ($self:ident, $func:ident, $n:expr) => {{
+        let n = $n;
+        $self.base.doc_view.$func(&mut $self.base.doc, n);
+        $self.base.update_selection();
+    }};
 }
 
 /// Background color.
@@ -28,8 +35,14 @@ pub const HIGHLIGHT: Bg<color::Rgb> = Bg(color::Rgb(51, 53, 59));
 pub const INFO: Bg<color::Rgb> = Bg(color::Rgb(59, 61, 66));
 /// Selection highlight background color
 pub const SEL: Bg<color::Rgb> = Bg(color::Rgb(75, 78, 87));
+/// Color column background color.
+const COLUMN: Bg<color::Rgb> = Bg(color::Rgb(61, 50, 55));
+/// Match highlight background color.
+const MATCH: Bg<color::Rgb> = Bg(color::Rgb(84, 76, 52));
 /// Text color.
 pub const TXT: Fg<color::Rgb> = Fg(color::Rgb(172, 178, 190));
+/// Text color of the gutter separator of lines changed since the last write.
+const CHANGE_SIGN: Fg<color::Rgb> = Fg(color::Rgb(209, 154, 102));
 /// Relative number text color.
 const REL_NUMS: Fg<color::Rgb> = Fg(color::Rgb(101, 103, 105));
 /// Whitespace symbol text color.
@@ -41,6 +54,28 @@ const INFO_TXT: Fg<color::Rgb> = Fg(color::Rgb(55, 131, 181));
 /// Error message text color.
 const ERROR_TXT: Fg<color::Rgb> = Fg(color::Rgb(181, 59, 59));
 
+/// The state of the last document render, used to only rerender changed lines.
+struct RenderState {
+    /// The epoch of the `Display` when rendering.
+    epoch: usize,
+    /// The scroll x offset.
+    scroll_x: usize,
+    /// The scroll y offset.
+    scroll_y: usize,
+    /// The width of the gutter.
+    gutter_w: usize,
+    /// The line of the cursor.
+    cur_y: usize,
+    /// The visual column of the cursor if it is highlighted.
+    cur_x: Option<usize>,
+    /// The highlighted columns of the first and of all other lines.
+    columns: (Vec<usize>, Vec<usize>),
+    /// The ranges of all selections.
+    selections: Vec<(Cursor, Cursor, SelectionKind)>,
+    /// The ranges of all highlighted matches.
+    highlights: Vec<(Cursor, Cursor)>,
+}
+
 /// The viewport of a (section of a) `Display`.
 pub struct Viewport {
     /// The total width of the viewport.
@@ -59,15 +94,21 @@ pub struct Viewport {
     pub gutter_w: usize,
     /// The width of the buffer content.
     pub buff_w: usize,
+    /// If lines longer than the viewport continue on the following rows instead of being scrolled horizontally.
+    pub wrap: bool,
+    /// The amount of rows of the first wrapped line that are scrolled out of view.
+    skip_rows: usize,
     /// If the viewport displays line numbers or not.
     gutter: bool,
+    /// The state of the last document render.
+    last_render: Option<RenderState>,
 }
 
 impl Viewport {
     pub fn new(w: usize, h: usize, x_off: usize, y_off: usize, count: Option<usize>) -> Self {
         let (gutter_w, buff_w) = count.map_or((0, w), |count| {
-            let digits = count.ilog10() as usize + 1;
-            (digits + 4, w - digits - 4)
+            let gutter_w = gutter_width(count);
+            (gutter_w, w - gutter_w)
         });
 
         Self {
@@ -79,15 +120,18 @@ impl Viewport {
             scroll_y: 0,
             gutter_w,
             buff_w,
+            wrap: false,
+            skip_rows: 0,
             gutter: count.is_some(),
+            last_render: None,
         }
     }
 
     /// Resizes the viewport.
     pub fn resize(&mut self, w: usize, h: usize, x_off: usize, y_off: usize, count: Option<usize>) {
         let (gutter_w, buff_w) = count.map_or((0, w), |count| {
-            let digits = count.ilog10() as usize + 1;
-            (digits + 4, w - digits - 4)
+            let gutter_w = gutter_width(count);
+            (gutter_w, w - gutter_w)
         });
 
         self.w = w;
@@ -97,45 +141,136 @@ impl Viewport {
         self.gutter_w = gutter_w;
         self.buff_w = buff_w;
         self.gutter = count.is_some();
+        self.invalidate();
     }
 
-    pub fn recalculate_viewport(&mut self, doc: &Document) {
-        let line = doc
-            .line(doc.cur.y)
-            .map(|l| l.to_string())
-            .unwrap_or_default();
-        let visual_x = text_width(&line, doc.cur.x);
+    /// Forces the next document render to update every line.
+    pub fn invalidate(&mut self) {
+        self.last_render = None;
+    }
+
+    /// Scrolls the viewport to keep the cursor in view with at least `scrolloff` lines above and below it, unless the
+    /// beginning or end of the document is reached.
+    pub fn recalculate_viewport(&mut self, doc: &Document, scrolloff: usize) {
+        let visual_x = doc.line_width(doc.cur.y, doc.cur.x);
+
+        if self.wrap {
+            self.recalculate_wrapped_lines(doc, scrolloff, visual_x);
+            return;
+        }
 
         self.scroll_x = self
             .scroll_x
             .clamp(visual_x.saturating_sub(self.buff_w - 1), visual_x);
+
+        // A margin of more than half the height would not leave a position for the cursor.
+        let scrolloff = scrolloff.min(self.h.saturating_sub(1) / 2);
+        let bottom = (doc.cur.y + scrolloff).min(doc.len().saturating_sub(1));
+        let top = doc.cur.y.saturating_sub(scrolloff);
         self.scroll_y = self
             .scroll_y
-            .clamp(doc.cur.y.saturating_sub(self.h - 1), doc.cur.y);
+            .clamp(bottom.saturating_sub(self.h - 1).min(top), top);
     }
 
-    /// Sets the gutter width.
-    pub const fn set_gutter_width(&mut self, n: usize) {
-        self.gutter_w = n + 4;
-        self.buff_w = self.w - n - 4;
+    /// Scrolls the viewport of wrapped lines to keep the row of the cursor in view with at least `scrolloff` lines above
+    /// and below it. Lines are scrolled as a whole unless the line of the cursor is higher than the viewport.
+    fn recalculate_wrapped_lines(&mut self, doc: &Document, scrolloff: usize, visual_x: usize) {
+        let cur_row = visual_x / self.buff_w.max(1);
+
+        self.scroll_x = 0;
+
+        // A margin of more than half the height would not leave a position for the cursor.
+        let scrolloff = scrolloff.min(self.h.saturating_sub(1) / 2);
+        let top = doc.cur.y.saturating_sub(scrolloff);
+        if self.scroll_y > top || (self.scroll_y == doc.cur.y && self.skip_rows > cur_row) {
+            self.scroll_y = top;
+            self.skip_rows = 0;
+        }
+
+        // The rows from the top of the viewport to the end of the margin below the cursor.
+        let bottom = (doc.cur.y + scrolloff).min(doc.len().saturating_sub(1));
+        let above = (self.scroll_y..doc.cur.y)
+            .map(|y| self.line_rows(doc, y))
+            .sum::<usize>();
+        let below = (doc.cur.y + 1..=bottom)
+            .map(|y| self.line_rows(doc, y))
+            .sum::<usize>();
+        let mut rows = (above + cur_row + 1 + below).saturating_sub(self.skip_rows);
+
+        while rows > self.h && self.scroll_y < doc.cur.y {
+            rows -= self.line_rows(doc, self.scroll_y) - self.skip_rows;
+            self.scroll_y += 1;
+            self.skip_rows = 0;
+        }
+
+        // Skip the rows of the cursor line that do not fit.
+        if self.scroll_y == doc.cur.y && cur_row + 1 > self.skip_rows + self.h {
+            self.skip_rows = cur_row + 1 - self.h;
+        }
+    }
+
+    /// Calculates the amount of rows a line takes up, including the cell after its content. Lines only take up more than
+    /// one row if they wrap.
+    fn line_rows(&self, doc: &Document, y: usize) -> usize {
+        if !self.wrap {
+            return 1;
+        }
+
+        doc.line_count(y).map_or(1, |count| {
+            let len = count - usize::from(doc.ends_with_newline(y));
+            doc.line_width(y, len) / self.buff_w.max(1) + 1
+        })
+    }
+
+    /// Gets the position on the `Display` of a visual column of a line drawn from row y, with `skip` of its rows
+    /// scrolled out of view. Returns `None` if the column is not in view.
+    fn cell_position(&self, x: usize, y: usize, skip: usize) -> Option<(usize, usize)> {
+        if self.wrap {
+            let buff_w = self.buff_w.max(1);
+            let row = (x / buff_w).checked_sub(skip)?;
+            (y + row < self.h).then(|| {
+                (
+                    self.x_off + self.gutter_w + x % buff_w,
+                    self.y_off + y + row,
+                )
+            })
+        } else {
+            (x >= self.scroll_x && x < self.scroll_x + self.buff_w).then(|| {
+                (
+                    self.x_off + self.gutter_w + x - self.scroll_x,
+                    self.y_off + y,
+                )
+            })
+        }
+    }
+
+    /// Sets the gutter width to fit the line numbers of a document with `count` lines.
+    pub const fn set_gutter_width(&mut self, count: usize) {
+        self.gutter_w = gutter_width(count);
+        self.buff_w = self.w - self.gutter_w;
     }
 
     /// Renders a message overlay to the `Display`. Should be called after `render_document` because it will get
     /// overwritten otherwise. This function assumes that `MessageIter` correctly calculates the lines and does
     /// NO bounds-checking when updating the display.
-    pub fn render_message(&self, display: &mut Display, message: &Message) {
+    pub fn render_message(&mut self, display: &mut Display, message: &Message) {
+        // The message overwrites lines of the document.
+        self.invalidate();
+
         let count = (message.lines.saturating_sub(message.scroll)).min(self.h / 3);
 
         let lines = message.iter(self.w).skip(message.scroll).take(count);
         for (y, line) in lines.enumerate() {
             let mut x = 0;
             let display_y = self.y_off + y;
+            let (line_fg, attrs) = match message.kind {
+                MessageKind::Info => (INFO_TXT, Attrs::NONE),
+                MessageKind::Error => (ERROR_TXT, Attrs::NONE),
+                MessageKind::Help => help_style(line),
+            };
 
-            for ch in line.chars() {
-                let mut fg = match message.kind {
-                    MessageKind::Info => INFO_TXT,
-                    MessageKind::Error => ERROR_TXT,
-                };
+            for VisualCell { ch, width, .. } in visual_cells(line.chars()) {
+                let mut fg = line_fg;
                 let mut bg = INFO;
 
                 // Layer 1: Character replacement.
@@ -150,21 +285,24 @@ impl Viewport {
                         bg = CHAR_WARN;
                         '↦'
                     }
-                    _ => ch,
+                    ch => control_picture(ch).map_or(ch, |picture| {
+                        fg = TXT;
+                        bg = CHAR_WARN;
+                        picture
+                    }),
                 };
 
-                let width = match ch {
-                    '\r' => 1,
-                    '\t' => TAB_WIDTH - (x % TAB_WIDTH),
-                    ch => ch.width().unwrap_or(0),
-                };
                 if width == 0 {
                     continue;
                 }
 
                 // Assert that MessageIter correctly calculates lines.
                 assert!(x + width <= self.w);
-                display.update(Cell::new(display_ch, fg, bg), self.x_off + x, display_y);
+                display.update(
+                    Cell::new(display_ch, fg, bg).with_attrs(attrs),
+                    self.x_off + x,
+                    display_y,
+                );
 
                 // Layer 2: Expand tabs.
                 if ch == '\t' {
@@ -194,32 +332,130 @@ impl Viewport {
         }
     }
 
-    /// Renders a document to the `Display`.
+    /// Renders a document to the `Display`. Only lines that changed since the last render are updated.
     pub fn render_document(
-        &self,
+        &mut self,
         display: &mut Display,
-        doc: &Document,
-        selections: &Vec<Selection>,
+        doc: &mut Document,
+        selections: &[Selection],
+        highlights: &[(Cursor, Cursor)],
+        options: &Options,
     ) {
-        for y in 0..self.h {
-            let doc_y = self.scroll_y + y;
+        let listchars = options.listchars;
+        let synmaxcol = if options.synmaxcol == 0 {
+            usize::MAX
+        } else {
+            options.synmaxcol
+        };
+
+        // The column of the cursor is highlighted across all lines.
+        let cur_x = options
+            .cursorcolumn
+            .then(|| doc.line_width(doc.cur.y, doc.cur.x));
+
+        let state = RenderState {
+            epoch: display.epoch(),
+            scroll_x: self.scroll_x,
+            scroll_y: self.scroll_y,
+            gutter_w: self.gutter_w,
+            cur_y: doc.cur.y,
+            cur_x,
+            columns: (options.color_columns(0), options.color_columns(1)),
+            selections: selections
+                .iter()
+                .map(|sel| {
+                    let (start, end) = sel.range();
+                    (start, end, sel.kind)
+                })
+                .collect(),
+            highlights: highlights.to_vec(),
+        };
+
+        // Changes to the layout or selections affect every line. Otherwise only lines that were edited or gained or
+        // lost the cursor line highlight need to be updated.
+        let prev_cur_y = self.last_render.as_ref().and_then(|last| {
+            (last.epoch == state.epoch
+                && last.scroll_x == state.scroll_x
+                && last.scroll_y == state.scroll_y
+                && last.gutter_w == state.gutter_w
+                && last.cur_x == state.cur_x
+                && last.columns == state.columns
+                && last.selections == state.selections
+                && last.highlights == state.highlights)
+                .then_some(last.cur_y)
+        });
+        // Indent guides of blank lines depend on their surrounding lines. Edits of wrapped lines can move all following
+        // lines.
+        let full = prev_cur_y.is_none() || self.wrap || (options.indentguides && doc.has_dirty());
+
+        let mut next_y = 0;
+        for doc_y in self.scroll_y.. {
+            let y = next_y;
+            if y >= self.h {
+                break;
+            }
+            let skip = if doc_y == self.scroll_y {
+                self.skip_rows
+            } else {
+                0
+            };
+            let rows = self.line_rows(doc, doc_y);
+            next_y = y + rows - skip;
+            // The visual column after the last row of the line in view.
+            let end_x = if self.wrap {
+                (skip + self.h - y).min(rows) * self.buff_w.max(1)
+            } else {
+                self.scroll_x + self.buff_w
+            };
             let mut x = 0;
 
+            if !full && !doc.is_dirty(doc_y) && doc_y != doc.cur.y && Some(doc_y) != prev_cur_y {
+                continue;
+            }
+
+            let columns = options.color_columns(doc_y);
+            let mixed = if options.mixedindent {
+                doc.line(doc_y).map_or(0, mixed_indent)
+            } else {
+                0
+            };
+            let trailing = if options.trailingspace {
+                doc.line(doc_y).map_or(usize::MAX, trailing_whitespace)
+            } else {
+                usize::MAX
+            };
+            let guide_indent = if options.indentguides {
+                guide_indent(doc, doc_y)
+            } else {
+                0
+            };
+
+            let line_bg = if options.cursorline && doc_y == doc.cur.y {
+                HIGHLIGHT
+            } else {
+                BG
+            };
+
             // Draw the contents of the line.
-            if let Some(line) = doc.line(doc_y) {
-                for (idx, ch) in line.chars().enumerate() {
+            if let Some(cells) = doc.visual_cells(doc_y) {
+                for VisualCell { idx, ch, width, .. } in cells {
+                    // Nothing past the right edge or the bottom of the viewport is drawn.
+                    if x >= end_x {
+                        break;
+                    }
+
                     let mut fg = TXT;
-                    let mut bg = if doc_y == doc.cur.y { HIGHLIGHT } else { BG };
+                    let mut bg = line_bg;
 
                     // Layer 1: Character replacement.
                     let mut display_ch = ch;
                     match ch {
                         ' ' => {
-                            display_ch = '·';
+                            display_ch = listchars.space;
                             fg = WHITESPACE;
                         }
                         '\n' => {
-                            display_ch = '⏎';
+                            display_ch = listchars.eol;
                             fg = WHITESPACE;
                         }
                         '\r' => {
@@ -227,66 +463,116 @@ impl Viewport {
                             fg = TXT;
                             bg = CHAR_WARN;
                         }
-                        '\t' => {
+                        // Tabs are unexpected if they are expanded when typed.
+                        '\t' if options.expandtab => {
                             display_ch = '↦';
                             fg = TXT;
                             bg = CHAR_WARN;
                         }
-                        _ => {}
+                        '\t' => {
+                            display_ch = listchars.tab_lead;
+                            fg = WHITESPACE;
+                        }
+                        ch => {
+                            if let Some(picture) = control_picture(ch) {
+                                display_ch = picture;
+                                bg = CHAR_WARN;
+                            }
+                        }
+                    }
+
+                    // Indentation mixing tabs and spaces is unexpected.
+                    if idx < mixed {
+                        bg = CHAR_WARN;
+                    }
+
+                    // So is whitespace at the end of a line.
+                    if idx >= trailing && matches!(ch, ' ' | '\t') {
+                        bg = CHAR_WARN;
+                    }
+
+                    // Draw indent guides over indenting whitespace.
+                    if x < guide_indent
+                        && x.is_multiple_of(TAB_WIDTH)
+                        && (ch == ' ' || (ch == '\t' && !options.expandtab))
+                    {
+                        display_ch = '│';
                     }
 
-                    let width = match ch {
-                        ' ' | '\n' | '\r' => 1,
-                        '\t' => TAB_WIDTH - (x % TAB_WIDTH),
-                        ch => ch.width().unwrap_or(0),
-                    };
                     if width == 0 {
                         continue;
                     }
 
+                    // Wide characters split across two rows can not be drawn.
+                    let split = self.wrap
+                        && ch != '\t'
+                        && x % self.buff_w.max(1) + width > self.buff_w.max(1);
+                    if split {
+                        display_ch = '\u{FFFD}';
+                    }
+
                     // If any part of the character is visible, render that.
-                    if x + width >= self.scroll_x && x < self.scroll_x + self.buff_w {
-                        // Layer 2: Selection.
-                        for selection in selections {
-                            if selection.contains(Cursor::new(idx, doc_y)) {
-                                bg = SEL;
-                                break;
-                            }
+                    if x + width >= self.scroll_x && x < end_x {
+                        // Layer 2: Selection and highlighted matches.
+                        let pos = Cursor::new(idx, doc_y);
+                        if selections.iter().any(|selection| selection.contains(pos)) {
+                            bg = SEL;
+                        } else if idx < synmaxcol
+                            && highlights
+                                .iter()
+                                .any(|(start, end)| *start <= pos && pos < *end)
+                        {
+                            bg = MATCH;
+                        } else if bg.0 == line_bg.0
+                            && cur_x.is_some_and(|cur_x| x <= cur_x && cur_x < x + width)
+                        {
+                            bg = HIGHLIGHT;
+                        } else if bg.0 == line_bg.0
+                            && columns
+                                .iter()
+                                .any(|column| x <= *column && *column < x + width)
+                        {
+                            bg = COLUMN;
                         }
 
-                        let display_y = self.y_off + y;
-
-                        if x >= self.scroll_x {
-                            let display_x = self.x_off + self.gutter_w + x - self.scroll_x;
+                        if let Some((display_x, display_y)) = self.cell_position(x, y, skip) {
                             display.update(Cell::new(display_ch, fg, bg), display_x, display_y);
                         }
 
                         // Layer 3: Expand tabs.
                         if ch == '\t' {
-                            // Write as many spaces as needed after the tab character.
+                            let fill = if options.expandtab {
+                                ' '
+                            } else {
+                                listchars.tab_fill
+                            };
+
+                            // Write as many fill characters as needed after the tab character.
                             for n in 1..=width {
-                                if x + n < self.scroll_x || x + n >= self.scroll_x + self.buff_w {
+                                let Some((display_x, display_y)) =
+                                    self.cell_position(x + n, y, skip)
+                                else {
                                     continue;
-                                }
+                                };
 
-                                let display_x = self.x_off + self.gutter_w + x + n - self.scroll_x;
-                                display.update(Cell::new(' ', fg, bg), display_x, display_y);
+                                display.update(Cell::new(fill, fg, bg), display_x, display_y);
                             }
                         } else {
                             // Mark all following cells of wide characters as taken.
                             for n in 1..width {
-                                if x + n < self.scroll_x || x + n >= self.scroll_x + self.buff_w {
+                                let Some((display_x, display_y)) =
+                                    self.cell_position(x + n, y, skip)
+                                else {
                                     continue;
-                                }
+                                };
 
-                                // Use unknown character if the initial character was outside the viewport to avoid
-                                // ghosting.
-                                let display_ch = if x >= self.scroll_x {
+                                // Use unknown character if the initial character was outside the viewport or on the
+                                // previous row to avoid ghosting.
+                                let display_ch = if x >= self.scroll_x && !split {
                                     PLACEHOLDER
                                 } else {
                                     '\u{FFFD}'
                                 };
-                                let display_x = self.x_off + self.gutter_w + x + n - self.scroll_x;
                                 display.update(Cell::new(display_ch, fg, bg), display_x, display_y);
                             }
                         }
@@ -296,17 +582,50 @@ impl Viewport {
                 }
             }
 
+            // Lines without a newline have no cell marking their end, like the last line, so mark the cell after their
+            // content instead to keep selected empty lines visible.
+            let sel_x = (doc_y < doc.len()
+                && !doc.ends_with_newline(doc_y)
+                && selections.iter().any(|selection| {
+                    selection.kind == SelectionKind::Line
+                        && selection.contains(Cursor::new(0, doc_y))
+                }))
+            .then_some(x);
+
             // Clear the remaining line.
-            let base_bg = if doc_y == doc.cur.y { HIGHLIGHT } else { BG };
-            let start = self.gutter_w + x.saturating_sub(self.scroll_x);
-            for x in start..self.w {
-                display.update(Cell::new(' ', TXT, base_bg), self.x_off + x, self.y_off + y);
+            for visual_x in x.max(self.scroll_x)..end_x {
+                let Some((display_x, display_y)) = self.cell_position(visual_x, y, skip) else {
+                    continue;
+                };
+                let base_bg = if sel_x == Some(visual_x) {
+                    SEL
+                } else if cur_x == Some(visual_x) {
+                    HIGHLIGHT
+                } else if columns.contains(&visual_x) {
+                    COLUMN
+                } else {
+                    line_bg
+                };
+
+                // Continue the indent guides of blank lines.
+                let cell = if visual_x < guide_indent && visual_x.is_multiple_of(TAB_WIDTH) {
+                    Cell::new('│', WHITESPACE, base_bg)
+                } else {
+                    Cell::new(' ', TXT, base_bg)
+                };
+                display.update(cell, display_x, display_y);
             }
         }
+
+        doc.clear_dirty();
+        self.last_render = Some(state);
     }
 
     /// Renders a vt100 parser state to the `Display`.
-    pub fn render_terminal(&self, display: &mut Display, parser: &Parser) {
+    pub fn render_terminal(&mut self, display: &mut Display, parser: &Parser) {
+        // The terminal overwrites lines of the document.
+        self.invalidate();
+
         let screen = parser.screen();
 
         // Render cells from the terminal screen.
@@ -342,7 +661,8 @@ impl Viewport {
             }
         }
 
-        if screen.hide_cursor() {
+        // The cursor is part of the live output.
+        if screen.hide_cursor() || screen.scrollback() > 0 {
             display.set_cursor(Cursor::new(0, 0), CursorStyle::Hidden);
         } else {
             let (row, col) = screen.cursor_position();
@@ -356,19 +676,29 @@ impl Viewport {
     }
 
     /// Renders line numbers to the `Display`.
-    pub fn render_gutter(&mut self, display: &mut Display, doc: &Document) {
+    pub fn render_gutter(&mut self, display: &mut Display, doc: &Document, options: &Options) {
         if !self.gutter {
             return;
         }
 
-        // Update the nums width if the supplied buffer is not correct.
-        // log10 + 1 for length + 4 for whitespace and separator.
-        if self.gutter && doc.len().ilog10() as usize + 5 != self.gutter_w {
-            self.resize(self.w, self.h, self.x_off, self.y_off, Some(doc.len()));
+        // The width only changes when the line count gains or loses a digit. The next document render notices the
+        // changed gutter width and updates every line.
+        if gutter_width(doc.len()) != self.gutter_w {
+            self.set_gutter_width(doc.len());
         }
 
-        for y in 0..self.h {
-            let doc_y = self.scroll_y + y;
+        let mut next_y = 0;
+        for doc_y in self.scroll_y.. {
+            let y = next_y;
+            if y >= self.h {
+                break;
+            }
+            let skip = if doc_y == self.scroll_y {
+                self.skip_rows
+            } else {
+                0
+            };
+            next_y = y + self.line_rows(doc, doc_y) - skip;
             let mut x = self.x_off;
 
             // Set base background color and move to the start of the line.
@@ -387,68 +717,131 @@ impl Viewport {
                 continue;
             }
 
+            // The separator of lines changed since the last write is colored.
+            let sign_fg = if options.changesigns && doc.is_changed(doc_y) {
+                CHANGE_SIGN
+            } else {
+                base_fg
+            };
+
             let padding = self.gutter_w - 3;
-            for ch in format!("{:>padding$} ┃ ", doc_y + 1).chars() {
-                display.update(Cell::new(ch, base_fg, base_bg), x, self.y_off + y);
-                x += 1;
+            for row in y..next_y.min(self.h) {
+                // Only the first row of a wrapped line shows its number.
+                let number = if row == y && skip == 0 {
+                    (doc_y + 1).to_string()
+                } else {
+                    String::new()
+                };
+
+                x = self.x_off;
+                for ch in format!("{number:>padding$} ┃ ").chars() {
+                    let fg = if ch == '┃' { sign_fg } else { base_fg };
+                    display.update(Cell::new(ch, fg, base_bg), x, self.y_off + row);
+                    x += 1;
+                }
             }
         }
     }
 
-    /// Renders a bar to the `Display`.
-    pub fn render_bar(&self, line: &str, y: usize, display: &mut Display) {
-        let start = self.scroll_x;
-        let end = (start + self.w).min(line.chars().count());
+    /// Renders a bar with a segment flush to the right edge to the `Display`. The left content is truncated if it
+    /// would collide with the right segment. The colors are inverted while the visual `bell` rings.
+    pub fn render_split_bar(
+        &self,
+        left: &str,
+        right: &str,
+        y: usize,
+        bell: bool,
+        display: &mut Display,
+    ) {
+        // The visual bell shows the bar in reverse video.
+        let style = (TXT, INFO, Attrs::NONE.with(Attrs::REVERSE, bell));
+        let right_w = text_width(right, usize::MAX).min(self.w);
+        let left_w = if right.is_empty() {
+            self.w
+        } else {
+            // Keep a gap between both segments.
+            self.w.saturating_sub(right_w + 1)
+        };
 
-        let start_idx = line
+        let start_idx = left
             .char_indices()
-            .nth(start)
-            .map_or(line.len(), |(idx, _)| idx);
-        let end_idx = line
-            .char_indices()
-            .nth(end)
-            .map_or(line.len(), |(idx, _)| idx);
-        let cmd = &line[start_idx..end_idx];
+            .nth(self.scroll_x)
+            .map_or(left.len(), |(idx, _)| idx);
+        let mut x = self.render_bar_text(&left[start_idx..], 0, left_w, y, style, display);
+
+        // Clear the space between the segments.
+        while x < self.w - right_w {
+            display.update(
+                Cell::new(' ', style.0, style.1).with_attrs(style.2),
+                self.x_off + x,
+                self.y_off + y,
+            );
+            x += 1;
+        }
+
+        let x = self.render_bar_text(right, x, right_w, y, style, display);
+        debug_assert!(x <= self.w);
+    }
 
-        let mut x = 0;
-        for ch in cmd.chars() {
+    /// Renders text of a bar starting at x, limited to a maximum width. Returns the x position after the text.
+    fn render_bar_text(
+        &self,
+        text: &str,
+        start: usize,
+        max_w: usize,
+        y: usize,
+        (fg, bg, attrs): (Fg<color::Rgb>, Bg<color::Rgb>, Attrs),
+        display: &mut Display,
+    ) -> usize {
+        let mut x = start;
+        for ch in text.chars() {
             let width = ch.width().unwrap_or(0);
             if width == 0 {
                 continue;
             }
+            if x + width > start + max_w {
+                break;
+            }
 
-            if x + width <= self.w {
-                display.update(Cell::new(ch, TXT, INFO), self.x_off + x, self.y_off + y);
+            display.update(
+                Cell::new(ch, fg, bg).with_attrs(attrs),
+                self.x_off + x,
+                self.y_off + y,
+            );
 
-                // Mark all following cells of wide characters as taken.
-                for n in 1..width {
-                    display.update(
-                        Cell::new(PLACEHOLDER, TXT, INFO),
-                        self.x_off + x + n,
-                        self.y_off + y,
-                    );
-                }
+            // Mark all following cells of wide characters as taken.
+            for n in 1..width {
+                display.update(
+                    Cell::new(PLACEHOLDER, fg, bg).with_attrs(attrs),
+                    self.x_off + x + n,
+                    self.y_off + y,
+                );
             }
             x += width;
         }
 
-        // Clear the remaining line.
-        while x < self.w {
-            display.update(Cell::new(' ', TXT, INFO), self.x_off + x, self.y_off + y);
-            x += 1;
-        }
+        x
     }
 
     /// Renders the `Cursor` of a `Document` to the `Display`.
     pub fn render_cursor(&self, display: &mut Display, doc: &Document, style: CursorStyle) {
-        let line = doc
-            .line(doc.cur.y)
-            .map(|l| l.to_string())
-            .unwrap_or_default();
-        let visual_x = text_width(&line, doc.cur.x);
+        let visual_x = doc.line_width(doc.cur.y, doc.cur.x);
 
-        let x = visual_x.saturating_sub(self.scroll_x);
-        let y = doc.cur.y.saturating_sub(self.scroll_y);
+        let (x, y) = if self.wrap {
+            let buff_w = self.buff_w.max(1);
+            let above = (self.scroll_y..doc.cur.y)
+                .map(|y| self.line_rows(doc, y))
+                .sum::<usize>();
+            (
+                visual_x % buff_w,
+                (above + visual_x / buff_w).saturating_sub(self.skip_rows),
+            )
+        } else {
+            (
+                visual_x.saturating_sub(self.scroll_x),
+                doc.cur.y.saturating_sub(self.scroll_y),
+            )
+        };
 
         assert!(x < self.buff_w && y < self.h);
         display.set_cursor(
@@ -457,27 +850,127 @@ impl Viewport {
         );
     }
 
+    /// Scrolls the viewport to the row of the cursor of a single line `Document` wrapped at the viewport width.
+    pub fn recalculate_wrapped_viewport(&mut self, doc: &Document) {
+        let line = doc.line(0).map(|l| l.to_string()).unwrap_or_default();
+        let (row, _) = wrap_positions(line.trim_end_matches(['\n', '\r']), self.w)[doc.cur.x];
+
+        self.scroll_x = 0;
+        self.scroll_y = self.scroll_y.clamp(row.saturating_sub(self.h - 1), row);
+    }
+
+    /// Renders a bar with its text wrapped at the viewport width across all rows to the `Display`.
+    pub fn render_wrapped_bar(&self, text: &str, display: &mut Display) {
+        let positions = wrap_positions(text, self.w);
+
+        for y in 0..self.h {
+            let row = self.scroll_y + y;
+            let mut x = 0;
+
+            for (ch, &(_, col)) in text.chars().zip(&positions).filter(|(_, (r, _))| *r == row) {
+                let width = ch.width().unwrap_or(0);
+                if width == 0 {
+                    continue;
+                }
+
+                display.update(Cell::new(ch, TXT, INFO), self.x_off + col, self.y_off + y);
+
+                // Mark all following cells of wide characters as taken.
+                for n in 1..width {
+                    display.update(
+                        Cell::new(PLACEHOLDER, TXT, INFO),
+                        self.x_off + col + n,
+                        self.y_off + y,
+                    );
+                }
+                x = col + width;
+            }
+
+            // Clear the rest of the row.
+            while x < self.w {
+                display.update(Cell::new(' ', TXT, INFO), self.x_off + x, self.y_off + y);
+                x += 1;
+            }
+        }
+    }
+
+    /// Renders the `Cursor` of a single line `Document` wrapped at the viewport width to the `Display`.
+    pub fn render_wrapped_cursor(&self, display: &mut Display, doc: &Document, style: CursorStyle) {
+        let line = doc.line(0).map(|l| l.to_string()).unwrap_or_default();
+        let (row, col) = wrap_positions(line.trim_end_matches(['\n', '\r']), self.w)[doc.cur.x];
+
+        let y = row.saturating_sub(self.scroll_y);
+
+        assert!(col < self.w && y < self.h);
+        display.set_cursor(Cursor::new(self.x_off + col, self.y_off + y), style);
+    }
+
     /// Shifts the viewport to the left.
     pub fn shift_left(&mut self, doc: &Document, n: usize) {
+        let x = doc.line_width(doc.cur.y, doc.cur.x);
+
+        self.scroll_x = (self.scroll_x + n).min(x);
+    }
+
+    /// Shifts the viewport to the right.
+    pub fn shift_right(&mut self, doc: &Document, n: usize) {
+        let x = doc.line_width(doc.cur.y, doc.cur.x);
+
+        let limit = (x + 1).saturating_sub(self.buff_w);
+        self.scroll_x = self.scroll_x.saturating_sub(n).max(limit);
+    }
+
+    /// Scrolls the viewport to the left independent of the cursor. The cursor is moved into the viewport if it would
+    /// leave it.
+    pub fn scroll_left(&mut self, doc: &mut Document, n: usize) {
+        if self.wrap {
+            return;
+        }
+
+        self.scroll_x = self.scroll_x.saturating_sub(n);
+        self.cursor_into_view(doc);
+    }
+
+    /// Scrolls the viewport to the right independent of the cursor, until the end of the current line is reached. The
+    /// cursor is moved into the viewport if it would leave it.
+    pub fn scroll_right(&mut self, doc: &mut Document, n: usize) {
+        if self.wrap {
+            return;
+        }
+
         let line = doc
             .line(doc.cur.y)
             .map(|l| l.to_string())
             .unwrap_or_default();
-        let x = text_width(&line, doc.cur.x);
+        let width = text_width(line.trim_end_matches('\n'), usize::MAX);
 
-        self.scroll_x = (self.scroll_x + n).min(x);
+        self.scroll_x = (self.scroll_x + n).min(width);
+        self.cursor_into_view(doc);
     }
 
-    /// Shifts the viewport to the right.
-    pub fn shift_right(&mut self, doc: &Document, n: usize) {
+    /// Moves the cursor horizontally into the visible area of the viewport.
+    fn cursor_into_view(&self, doc: &mut Document) {
         let line = doc
             .line(doc.cur.y)
             .map(|l| l.to_string())
             .unwrap_or_default();
-        let x = text_width(&line, doc.cur.x);
+        let visual_x = text_width(&line, doc.cur.x);
 
-        let limit = (x + 1).saturating_sub(self.buff_w);
-        self.scroll_x = self.scroll_x.saturating_sub(n).max(limit);
+        let x = if visual_x < self.scroll_x {
+            // Move to the first character fully in view.
+            let idx = char_idx_at_width(&line, self.scroll_x);
+            if text_width(&line, idx) < self.scroll_x {
+                idx + 1
+            } else {
+                idx
+            }
+        } else if visual_x >= self.scroll_x + self.buff_w {
+            char_idx_at_width(&line, self.scroll_x + self.buff_w - 1)
+        } else {
+            return;
+        };
+
+        cursor::move_to(doc, Cursor::new(x, doc.cur.y));
     }
 
     /// Shifts the viewport up.
@@ -485,9 +978,345 @@ impl Viewport {
         self.scroll_y = (self.scroll_y + n).min(doc.cur.y);
     }
 
+    /// Scrolls the viewport and the cursor down by `n` lines, keeping the cursor at the same screen position. Once the
+    /// end of the document is visible only the cursor moves.
+    pub fn page_down(&mut self, doc: &mut Document, n: usize) {
+        let limit = doc.len().saturating_sub(self.h);
+        self.scroll_y = (self.scroll_y + n).min(limit.max(self.scroll_y));
+        cursor::down(doc, n);
+    }
+
+    /// Scrolls the viewport and the cursor up by `n` lines, keeping the cursor at the same screen position. Once the
+    /// beginning of the document is visible only the cursor moves.
+    pub fn page_up(&mut self, doc: &mut Document, n: usize) {
+        self.scroll_y = self.scroll_y.saturating_sub(n);
+        cursor::up(doc, n);
+    }
+
     /// Shifts the viewport up.
     pub fn shift_down(&mut self, doc: &Document, n: usize) {
         let limit = (doc.cur.y + 1).saturating_sub(self.h);
         self.scroll_y = self.scroll_y.saturating_sub(n).max(limit);
     }
 }
+
+/// Calculates the width of the gutter for a document with `count` lines, the digits of the largest line number plus
+/// whitespace and the separator.
+pub const fn gutter_width(count: usize) -> usize {
+    count.ilog10() as usize + 1 + 4
+}
+
+/// Calculates the number of rows needed to display text wrapped at a width, including the position after the text.
+pub fn wrapped_rows(text: &str, w: usize) -> usize {
+    wrap_positions(text, w)
+        .last()
+        .map_or(1, |&(row, _)| row + 1)
+}
+
+/// Lays out text wrapped at a width. Returns the row and column of every character followed by the position after
+/// the text. Characters that do not fit in the rest of a row move to the next one.
+fn wrap_positions(text: &str, w: usize) -> Vec<(usize, usize)> {
+    let (mut row, mut col) = (0, 0);
+    let mut positions = Vec::new();
+
+    for ch in text.chars().map(Some).chain([None]) {
+        let width = ch.map_or(0, |ch| ch.width().unwrap_or(0));
+        if col + width.max(1) > w {
+            row += 1;
+            col = 0;
+        }
+
+        positions.push((row, col));
+        col += width;
+    }
+
+    positions
+}
+
+/// Calculates the visual width of the indentation of a line. Returns `None` for blank lines.
+fn indent_width(line: RopeSlice) -> Option<usize> {
+    let mut width = 0;
+    for ch in line.chars() {
+        match ch {
+            ' ' => width += 1,
+            '\t' => width += TAB_WIDTH - (width % TAB_WIDTH),
+            '\n' | '\r' => return None,
+            _ => return Some(width),
+        }
+    }
+
+    None
+}
+
+/// Calculates the amount of leading whitespace characters of a line if they mix tabs and spaces, otherwise zero.
+fn mixed_indent(line: RopeSlice) -> usize {
+    let (mut tabs, mut spaces) = (0, 0);
+    for ch in line.chars() {
+        match ch {
+            ' ' => spaces += 1,
+            '\t' => tabs += 1,
+            _ => break,
+        }
+    }
+
+    if tabs > 0 && spaces > 0 {
+        tabs + spaces
+    } else {
+        0
+    }
+}
+
+/// Calculates the character index where the whitespace at the end of a line starts. Returns `usize::MAX` if the line
+/// has no trailing whitespace.
+fn trailing_whitespace(line: RopeSlice) -> usize {
+    let mut start = usize::MAX;
+    for (idx, ch) in line.chars().enumerate() {
+        match ch {
+            ' ' | '\t' => start = start.min(idx),
+            // The carriage return of a CRLF line ending is rendered as a warning already.
+            '\r' => {}
+            '\n' => break,
+            _ => start = usize::MAX,
+        }
+    }
+
+    start
+}
+
+/// Calculates up to which column indent guides are drawn on a line. Blank lines continue the guides of the block they
+/// are in, which is the smaller indentation of the surrounding non-blank lines.
+fn guide_indent(doc: &Document, y: usize) -> usize {
+    if y >= doc.len() {
+        return 0;
+    }
+
+    indent_width(doc.line(y).unwrap()).unwrap_or_else(|| {
+        let prev = (0..y)
+            .rev()
+            .find_map(|y| doc.line(y).and_then(indent_width));
+        let next = (y + 1..doc.len()).find_map(|y| doc.line(y).and_then(indent_width));
+        prev.unwrap_or(0).min(next.unwrap_or(0))
+    })
+}
+
+/// Gets the text color and attributes of a line of the help message. Headings like "Text Buffer:" are bold and notes
+/// starting with '>' italic.
+fn help_style(line: &str) -> (Fg<color::Rgb>, Attrs) {
+    let trimmed = line.trim_start();
+    let heading = trimmed.ends_with(':')
+        && trimmed.split_whitespace().count() <= 2
+        && trimmed.starts_with(char::is_uppercase);
+
+    if heading {
+        (TXT, Attrs::BOLD)
+    } else if trimmed.starts_with('>') {
+        (INFO_TXT, Attrs::ITALIC)
+    } else {
+        (INFO_TXT, Attrs::NONE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::{ColorMode, Screen};
+
+    /// Renders the gutter, the document and the cursor of a wrapping viewport. Returns the rows of the screen and the
+    /// row and column of the cursor.
+    fn render_wrapped(
+        contents: &str,
+        cur: Cursor,
+        w: usize,
+        h: usize,
+    ) -> (Viewport, Vec<String>, (u16, u16)) {
+        let mut doc = Document::new(0, 0, None);
+        doc.from(contents);
+        cursor::move_to(&mut doc, cur);
+        let options = Options::new();
+
+        let mut view = Viewport::new(w, h, 0, 0, Some(doc.len()));
+        view.wrap = true;
+        view.recalculate_viewport(&doc, 0);
+
+        let mut display = Display::new(w, h, false, ColorMode::Monochrome);
+        view.render_gutter(&mut display, &doc, &options);
+        view.render_document(&mut display, &mut doc, &[], &[], &options);
+        view.render_cursor(&mut display, &doc, CursorStyle::SteadyBlock);
+
+        let mut out = Vec::new();
+        display.draw(&mut out).unwrap();
+        let screen = Screen::new(&out, w, h);
+
+        (view, screen.rows(), screen.cursor())
+    }
+
+    #[test]
+    fn wrap_continues_long_lines_on_the_following_rows() {
+        let (_, rows, cursor) = render_wrapped("abcdefghij\nxy", Cursor::new(9, 0), 9, 5);
+
+        assert_eq!(
+            rows,
+            [" 1 ┃ abcd", "   ┃ efgh", "   ┃ ij⏎", " 2 ┃ xy", "   ┃"]
+        );
+        assert_eq!(cursor, (2, 6));
+    }
+
+    #[test]
+    fn wrap_scrolls_by_whole_lines() {
+        let (view, rows, cursor) = render_wrapped("abcdefghij\nxy", Cursor::new(1, 1), 9, 2);
+
+        assert_eq!(view.scroll_y, 1);
+        assert_eq!(rows, [" 2 ┃ xy", "   ┃"]);
+        assert_eq!(cursor, (0, 6));
+    }
+
+    #[test]
+    fn wrap_skips_rows_of_lines_higher_than_the_viewport() {
+        let (_, rows, cursor) = render_wrapped("abcdefghijklmnop", Cursor::new(13, 0), 9, 2);
+
+        assert_eq!(rows, ["   ┃ ijkl", "   ┃ mnop"]);
+        assert_eq!(cursor, (1, 6));
+    }
+
+    #[test]
+    fn render_expands_tabs_and_covers_wide_characters() {
+        let mut doc = Document::new(0, 0, None);
+        doc.from("\tx\n漢字y");
+        let options = Options::new();
+        let selections = [Selection::new(
+            Cursor::new(1, 1),
+            Cursor::new(2, 1),
+            SelectionKind::Normal,
+            None,
+            None,
+        )];
+
+        let mut view = Viewport::new(12, 3, 0, 0, Some(doc.len()));
+        let mut display = Display::new(12, 3, false, ColorMode::Monochrome);
+        view.render_gutter(&mut display, &doc, &options);
+        view.render_document(&mut display, &mut doc, &selections, &[], &options);
+
+        let mut out = Vec::new();
+        display.draw(&mut out).unwrap();
+        let screen = Screen::new(&out, 12, 3);
+
+        assert_eq!(screen.rows(), [" 1 ┃ ↦   x⏎", " 2 ┃ 漢字y", "   ┃"]);
+
+        // Only the selected wide character is highlighted.
+        assert!(!screen.cell(1, 5).inverse());
+        assert!(screen.cell(1, 7).inverse());
+        assert!(!screen.cell(1, 9).inverse());
+    }
+
+    #[test]
+    fn wrapped_bar_keeps_trailing_whitespace_for_the_cursor() {
+        for (text, w, rows, cursor) in [("s ", 10, 1, (0, 2)), ("abcd ", 5, 2, (1, 0))] {
+            let mut doc = Document::new(0, 0, Some(text.to_string()));
+            cursor::move_to(&mut doc, Cursor::new(text.len(), 0));
+            assert_eq!(wrapped_rows(text, w), rows);
+
+            let mut view = Viewport::new(w, rows, 0, 0, None);
+            let mut display = Display::new(w, rows, false, ColorMode::Monochrome);
+            view.recalculate_wrapped_viewport(&doc);
+            view.render_wrapped_bar(text, &mut display);
+            view.render_wrapped_cursor(&mut display, &doc, CursorStyle::SteadyBar);
+
+            let mut out = Vec::new();
+            display.draw(&mut out).unwrap();
+            let screen = Screen::new(&out, w, rows);
+            assert_eq!(screen.rows()[0], text.trim_end());
+            assert_eq!(screen.cursor(), cursor);
+        }
+    }
+
+    #[test]
+    fn gutter_width_changes_only_with_the_digits_of_the_line_count() {
+        let mut doc = Document::new(0, 0, Some("a\n".repeat(8) + "a"));
+        let options = Options::new();
+        let mut view = Viewport::new(20, 4, 0, 0, Some(doc.len()));
+        let mut display = Display::new(20, 4, false, ColorMode::Monochrome);
+
+        // Everything drawn is kept to check that no columns of a wider gutter are left over.
+        let mut out = Vec::new();
+        let mut widths = Vec::new();
+        while doc.len() < 120 {
+            view.render_gutter(&mut display, &doc, &options);
+            view.render_document(&mut display, &mut doc, &[], &[], &options);
+            display.draw(&mut out).unwrap();
+            if widths.last().is_none_or(|&(_, w)| w != view.gutter_w) {
+                widths.push((doc.len(), view.gutter_w));
+            }
+            assert_eq!(view.gutter_w + view.buff_w, 20);
+
+            doc.insert_line(doc.len());
+        }
+        assert_eq!(widths, [(9, 5), (10, 6), (100, 7)]);
+
+        // Deleting lines shrinks the gutter again.
+        doc.remove_range(Cursor::new(0, 9), Cursor::new(0, doc.len()));
+        assert_eq!(doc.len(), 10);
+        view.render_gutter(&mut display, &doc, &options);
+        view.render_document(&mut display, &mut doc, &[], &[], &options);
+        display.draw(&mut out).unwrap();
+        assert_eq!(view.gutter_w, 6);
+
+        doc.remove_range(Cursor::new(1, 8), Cursor::new(0, 9));
+        view.render_gutter(&mut display, &doc, &options);
+        view.render_document(&mut display, &mut doc, &[], &[], &options);
+        assert_eq!(view.gutter_w, gutter_width(9));
+
+        display.draw(&mut out).unwrap();
+        assert_eq!(
+            Screen::new(&out, 20, 4).rows(),
+            [" 1 ┃ a⏎", " 2 ┃ a⏎", " 3 ┃ a⏎", " 4 ┃ a⏎"]
+        );
+    }
+
+    #[test]
+    fn unchanged_lines_are_not_rendered_again() {
+        let (w, h) = (60, 20);
+        let mut doc = Document::new(
+            0,
+            0,
+            Some("let value = some_function(argument, other);\t// comment\n".repeat(200)),
+        );
+        cursor::move_to(&mut doc, Cursor::new(10, 5));
+        let options = Options::new();
+        let mut view = Viewport::new(w, h, 0, 0, Some(doc.len()));
+        let mut display = Display::new(w, h, false, ColorMode::Monochrome);
+
+        // Marks the last column of every row and renders a frame. Rows that still show the mark were skipped.
+        let mut out = Vec::new();
+        let mut skipped = |view: &mut Viewport, doc: &mut Document| {
+            for y in 0..h {
+                display.update(Cell::new('§', TXT, BG), w - 1, y);
+            }
+            view.render_gutter(&mut display, doc, &options);
+            view.render_document(&mut display, doc, &[], &[], &options);
+            display.draw(&mut out).unwrap();
+
+            Screen::new(&out, w, h)
+                .rows()
+                .iter()
+                .filter(|row| row.ends_with('§'))
+                .count()
+        };
+
+        assert_eq!(skipped(&mut view, &mut doc), 0);
+
+        // Frames without changes, like the cursor blinking, only render the line of the cursor.
+        assert_eq!(skipped(&mut view, &mut doc), h - 1);
+
+        // Moving the cursor to another line also renders the line it left.
+        cursor::down(&mut doc, 1);
+        assert_eq!(skipped(&mut view, &mut doc), h - 2);
+
+        // Edits only render the edited lines.
+        doc.write_char('x', doc.cur.x, doc.cur.y);
+        assert_eq!(skipped(&mut view, &mut doc), h - 1);
+
+        // Invalidating the viewport renders everything again.
+        view.invalidate();
+        assert_eq!(skipped(&mut view, &mut doc), 0);
+    }
+}